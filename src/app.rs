@@ -1,57 +1,1079 @@
-use crate::rendering::backends::{Backend, WindowBackend};
-use crate::rendering::{FrameBuffer, RenderOpts, Sampling};
-use crate::scene::Scene;
+use crate::error::Error;
+use crate::rendering::aov::AovImage;
+use crate::rendering::backends::{
+    AnimationBackend, Backend, FileBackend, ImageFormat, TerminalBackend,
+};
+#[cfg(feature = "window")]
+use crate::rendering::backends::{FlyController, LiveWindow, WindowBackend};
+use crate::rendering::denoise::{DenoiseGuides, Denoiser};
+#[cfg(feature = "window")]
+use crate::rendering::FrameBuffer;
+use crate::rendering::{
+    Checkpoint, Film, HdrBuffer, Pixel, PixelRect, PixelSize, PostStack, RenderOpts, SampleCounts,
+};
+#[cfg(feature = "window")]
+use crate::scene::description::camera_pose;
+use crate::scene::{Eye, FrameRange, Projection, Scene, StereoLayout, Turntable};
 use log::*;
+#[cfg(feature = "window")]
+use minifb::Key;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// The periodic saving of the state of a render into a checkpoint file.
+#[derive(Debug, Clone)]
+pub struct CheckpointOpts {
+    /// the checkpoint file
+    file: String,
+    /// the minimum duration between two saves
+    interval: Duration,
+    /// continues the render saved in the file, if it exists
+    resume: bool,
+}
+
+impl CheckpointOpts {
+    /// The longest duration between two checkpoints, in seconds (a day).
+    pub const MAX_INTERVAL: f32 = 86400.0;
+
+    pub fn new(file: &str, interval: Duration, resume: bool) -> Self {
+        CheckpointOpts {
+            file: String::from(file),
+            interval,
+            resume,
+        }
+    }
+
+    /// Parses a duration between two checkpoints, in seconds, from 0 to [MAX_INTERVAL].
+    ///
+    /// [MAX_INTERVAL]: CheckpointOpts::MAX_INTERVAL
+    pub fn parse_interval(s: &str) -> Result<Duration, String> {
+        match s.trim().parse::<f32>() {
+            Ok(n) if (0.0..=Self::MAX_INTERVAL).contains(&n) => Ok(Duration::from_secs_f32(n)),
+            _ => Err(format!(
+                "{} is not a valid interval, in seconds from 0 to {}",
+                s,
+                Self::MAX_INTERVAL
+            )),
+        }
+    }
+}
 
 /// The parameters for the run command.
 #[derive(Debug)]
 pub struct RunOpts {
-    /// be verbose
-    verbose: bool,
-    output_file: String,
+    /// the image file to write, or `None` to display the render in a window
+    output_file: Option<String>,
+    /// displays the render in the terminal instead of a window
+    terminal: bool,
+    /// overrides the image format guessed from the output file extension
+    format: Option<ImageFormat>,
+    /// the scene file to load, or `None` for the built-in scene
+    scene_file: Option<String>,
+    /// overrides the projection of the scene camera
+    projection: Option<Projection>,
+    /// renders a stereo pair with the specified layout
+    stereo: Option<StereoLayout>,
+    /// overrides the interpupillary distance of the scene camera
+    ipd: Option<f32>,
+    /// overrides the convergence distance of the scene camera
+    convergence: Option<f32>,
+    /// the parameters of the raytracing pass
+    render: RenderOpts,
+    /// saves the state of the render periodically, and resumes it
+    checkpoint: Option<CheckpointOpts>,
+    /// denoises the render before presenting it
+    denoiser: Option<Denoiser>,
+    /// with a crop window, outputs the full frame instead of the window only
+    border: bool,
+    /// overrides the post-processing effects of the scene
+    post: Option<PostStack>,
+    /// skips all the post-processing effects
+    bypass_post: bool,
+    /// writes the AOVs into separate files, even if the output format supports layers
+    separate_aovs: bool,
+    /// the image file to write the per-pixel sample counts to, as a heatmap
+    sample_heatmap: Option<String>,
+    /// renders in successive passes of one sample per pixel, displayed in a window
+    progressive: bool,
+    /// renders these frames of the animation into numbered files
+    frames: Option<FrameRange>,
+    /// replaces the animation of the camera with an orbit around the scene
+    turntable: Option<Turntable>,
+    /// the number of frames per second of animated images
+    fps: f32,
 }
 
 impl RunOpts {
-    pub fn new(output_file: &str, verbose: bool) -> Self {
+    pub fn new(output_file: Option<&str>) -> Self {
         RunOpts {
-            output_file: String::from(output_file),
-            verbose,
+            output_file: output_file.map(String::from),
+            terminal: false,
+            format: None,
+            scene_file: None,
+            projection: None,
+            stereo: None,
+            ipd: None,
+            convergence: None,
+            render: RenderOpts::new().with_samples(4),
+            checkpoint: None,
+            denoiser: None,
+            border: false,
+            post: None,
+            bypass_post: false,
+            separate_aovs: false,
+            sample_heatmap: None,
+            progressive: false,
+            frames: None,
+            turntable: None,
+            fps: AnimationBackend::DEFAULT_FPS,
         }
     }
+
+    /// Enables progressive rendering. The render opts samples become the number of passes.
+    pub fn with_progressive(self) -> Self {
+        let mut s = self;
+        s.progressive = true;
+        s
+    }
+
+    /// Displays the render in the terminal instead of a window, see [TerminalBackend].
+    pub fn with_terminal(self) -> Self {
+        let mut s = self;
+        s.terminal = true;
+        s
+    }
+
+    /// Renders the frames of the animation into numbered files, e.g `out_0001.png`.
+    pub fn with_frames(self, frames: FrameRange) -> Self {
+        let mut s = self;
+        s.frames = Some(frames);
+        s
+    }
+
+    /// Orbits the camera around the scene. Unless specified, the frames are the ones of the orbit.
+    pub fn with_turntable(self, turntable: Turntable) -> Self {
+        let mut s = self;
+        s.turntable = Some(turntable);
+        s
+    }
+
+    /// Sets the number of frames per second of animated outputs, e.g `.gif`.
+    pub fn with_fps(self, fps: f32) -> Self {
+        let mut s = self;
+        s.fps = fps;
+        s
+    }
+
+    pub fn with_format(self, format: ImageFormat) -> Self {
+        let mut s = self;
+        s.format = Some(format);
+        s
+    }
+
+    pub fn with_checkpoint(self, checkpoint: CheckpointOpts) -> Self {
+        let mut s = self;
+        s.checkpoint = Some(checkpoint);
+        s
+    }
+
+    pub fn with_denoiser(self, denoiser: Denoiser) -> Self {
+        let mut s = self;
+        s.denoiser = Some(denoiser);
+        s
+    }
+
+    /// Outputs the full frame when rendering a crop window. Only the window is updated:
+    /// the rest of the frame is read from the existing output file, if possible.
+    pub fn with_border(self) -> Self {
+        let mut s = self;
+        s.border = true;
+        s
+    }
+
+    pub fn with_post(self, post: PostStack) -> Self {
+        let mut s = self;
+        s.post = Some(post);
+        s
+    }
+
+    pub fn with_bypass_post(self) -> Self {
+        let mut s = self;
+        s.bypass_post = true;
+        s
+    }
+
+    /// Writes the AOVs into separate files, instead of layers of the output file.
+    pub fn with_separate_aovs(self) -> Self {
+        let mut s = self;
+        s.separate_aovs = true;
+        s
+    }
+
+    pub fn with_sample_heatmap(self, file: &str) -> Self {
+        let mut s = self;
+        s.sample_heatmap = Some(String::from(file));
+        s
+    }
+
+    pub fn with_render_opts(self, render: RenderOpts) -> Self {
+        let mut s = self;
+        s.render = render;
+        s
+    }
+
+    pub fn render_opts(&self) -> RenderOpts {
+        self.render
+    }
+
+    pub fn with_scene_file(self, scene_file: &str) -> Self {
+        let mut s = self;
+        s.scene_file = Some(String::from(scene_file));
+        s
+    }
+
+    pub fn with_projection(self, projection: Projection) -> Self {
+        let mut s = self;
+        s.projection = Some(projection);
+        s
+    }
+
+    pub fn with_stereo(
+        self,
+        layout: StereoLayout,
+        ipd: Option<f32>,
+        convergence: Option<f32>,
+    ) -> Self {
+        let mut s = self;
+        s.stereo = Some(layout);
+        s.ipd = ipd;
+        s.convergence = convergence;
+        s
+    }
 }
 
 #[doc(hidden)]
 fn progress_func(progress: f32) {
     let percent = (progress * 100f32) as u32;
-    if percent % 10 == 0 {
+    if percent.is_multiple_of(10) {
         info!("progress: {}%", percent);
     }
 }
 
+/// Returns the filename with a suffix appended to its stem, e.g `out.png` -> `out_left.png`
+fn with_suffix(filename: &str, suffix: &str) -> String {
+    let path = Path::new(filename);
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}_{}.{}", stem, suffix, ext),
+        None => format!("{}_{}", stem, suffix),
+    };
+
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// Returns the [FileBackend] for the file, using the format if specified.
+fn file_backend(file: &str, format: Option<ImageFormat>) -> FileBackend<'_> {
+    let backend = FileBackend::new(file);
+    match format {
+        Some(format) => backend.with_format(format),
+        None => backend,
+    }
+}
+
+/// Presents the [HdrBuffer] into the output file, or if there is none, in the terminal or
+/// in a window.
+fn present(
+    target: &HdrBuffer,
+    output_file: Option<&str>,
+    format: Option<ImageFormat>,
+    terminal: bool,
+) -> Result<(), Error> {
+    match output_file {
+        Some(file) => {
+            let backend = file_backend(file, format);
+            info!("saving render to {}", backend);
+            backend.present_hdr(target)
+        }
+        None if terminal => {
+            let backend = TerminalBackend::new();
+            info!("presenting render in {}", backend);
+            backend.present_hdr(target)
+        }
+        #[cfg(feature = "window")]
+        None => {
+            let backend = WindowBackend::new();
+            info!("presenting render in {}", backend);
+            backend.present_hdr(target)
+        }
+        #[cfg(not(feature = "window"))]
+        None => Err(no_window()),
+    }
+}
+
+/// Writes the beauty image and the AOVs into the output file: as layers if the format
+/// supports them, or else into separate files, e.g `out_depth.png`.
+fn present_with_aovs(
+    target: &HdrBuffer,
+    aovs: &[AovImage],
+    output_file: &str,
+    format: Option<ImageFormat>,
+    separate: bool,
+) -> Result<(), Error> {
+    let backend = file_backend(output_file, format);
+    let format = format
+        .or_else(|| ImageFormat::from_path(output_file))
+        .unwrap_or_default();
+    if format == ImageFormat::Exr && !separate {
+        info!(
+            "saving render with {} AOV layers to {}",
+            aovs.len(),
+            backend
+        );
+        return backend.present_layers(target, aovs);
+    }
+
+    info!("saving render to {}", backend);
+    backend.present_hdr(target)?;
+    for aov in aovs {
+        let file = with_suffix(output_file, aov.aov.name());
+        let backend = file_backend(&file, Some(format));
+        info!("saving {} AOV to {}", aov.aov, backend);
+        backend.present_aov(aov)?;
+    }
+
+    Ok(())
+}
+
+/// Renders the [Scene] into a new [HdrBuffer], and denoises it if requested.
+///
+/// Returns the render with its sample counts and the AOVs requested by the [RenderOpts].
+/// The AOVs that guide the [Denoiser] are rendered as well, but not returned unless requested.
+fn render(
+    scene: &Scene,
+    size: PixelSize,
+    opts: &RenderOpts,
+    denoiser: Option<Denoiser>,
+    checkpoint: Option<&CheckpointOpts>,
+) -> Result<(HdrBuffer, SampleCounts, Vec<AovImage>), Error> {
+    let mut render_opts = *opts;
+    if denoiser.is_some() {
+        let mut aovs = opts.aovs;
+        DenoiseGuides::AOVS.iter().for_each(|a| aovs.insert(*a));
+        render_opts = render_opts.with_aovs(aovs);
+    }
+
+    let mut hdr = HdrBuffer::try_new(size.width, size.height)?;
+    hdr.clear(scene.camera().clear_color());
+    info!("rendering into {}", hdr);
+    let (counts, mut aovs) = match checkpoint {
+        Some(checkpoint) => render_resumable(scene, &mut hdr, &render_opts, checkpoint)?,
+        None => scene.render_hdr(&mut hdr, &render_opts, &progress_func),
+    };
+
+    if let Some(denoiser) = denoiser {
+        info!("denoising with {}", denoiser);
+        let guides = DenoiseGuides::from_aovs(&aovs)?;
+        hdr = denoiser.denoise(&hdr, &guides);
+    }
+
+    aovs.retain(|a| opts.aovs.contains(a.aov));
+    Ok((hdr, counts, aovs))
+}
+
+/// Renders the [Scene] pass by pass, and saves its [Checkpoint] periodically and at the end.
+/// If requested, the render resumes from the checkpoint file: with more samples than the
+/// checkpoint has, samples are added to it. Note that the strata of the stratified sampler
+/// depend on the number of samples, so the added samples are stratified separately.
+fn render_resumable(
+    scene: &Scene,
+    target: &mut HdrBuffer,
+    opts: &RenderOpts,
+    checkpoint_opts: &CheckpointOpts,
+) -> Result<(SampleCounts, Vec<AovImage>), Error> {
+    let file = checkpoint_opts.file.as_str();
+    let size = target.size();
+    let mut checkpoint = if checkpoint_opts.resume && Path::new(file).exists() {
+        let checkpoint = Checkpoint::load(file, scene, size, opts).map_err(Error::io(file))?;
+        info!("resuming from {}: {}", file, checkpoint);
+        checkpoint
+    } else {
+        if checkpoint_opts.resume {
+            warn!(
+                "no checkpoint to resume from in {}, starting a new render",
+                file
+            );
+        }
+        Checkpoint::new(scene, size, opts)
+    };
+
+    let save = |checkpoint: &Checkpoint| {
+        info!(
+            "saving checkpoint ({} passes) to {}",
+            checkpoint.passes(),
+            file
+        );
+        checkpoint.save(file).map_err(Error::io(file))
+    };
+
+    // a failed save stops the render
+    let mut result = Ok(());
+    let mut last_save = Instant::now();
+    scene.render_resumable(&mut checkpoint, opts, &progress_func, &mut |checkpoint| {
+        if last_save.elapsed() >= checkpoint_opts.interval {
+            result = save(checkpoint);
+            last_save = Instant::now();
+        }
+        result.is_ok()
+    });
+    result?;
+    save(&checkpoint)?;
+
+    checkpoint.film().resolve_hdr(target);
+    Ok((checkpoint.sample_counts(), checkpoint.film().aov_images()))
+}
+
+/// Reads the existing image the crop window of a render is copied into, if it exists and
+/// has the size of the render.
+fn border_frame(
+    output_file: Option<&str>,
+    format: Option<ImageFormat>,
+    size: PixelSize,
+) -> Option<HdrBuffer> {
+    let backend = file_backend(output_file?, format);
+    match backend.load() {
+        Some(frame) if frame.size() == size => {
+            info!("updating the crop window of {}", backend);
+            Some(frame)
+        }
+        _ => {
+            info!(
+                "could not read {} to update, the frame is filled with the background",
+                backend
+            );
+            None
+        }
+    }
+}
+
+/// Outputs the crop window of a full-frame render: either the window alone, or with `border`,
+/// the full frame, where the window is copied into `frame` if specified.
+fn crop_output(
+    hdr: &HdrBuffer,
+    window: Option<PixelRect>,
+    frame: Option<&HdrBuffer>,
+    border: bool,
+) -> Result<HdrBuffer, Error> {
+    match (window, frame) {
+        (Some(window), _) if !border => hdr.crop(window),
+        (Some(window), Some(frame)) => {
+            let mut output = frame.clone();
+            output.blit(&hdr.crop(window)?, window.origin);
+            Ok(output)
+        }
+        _ => Ok(hdr.clone()),
+    }
+}
+
+/// Logs the sample counts, and writes them as a heatmap if requested.
+fn report_sample_counts(counts: &SampleCounts, heatmap_file: Option<&str>) -> Result<(), Error> {
+    info!("{}", counts);
+
+    if let Some(file) = heatmap_file {
+        let backend = FileBackend::new(file);
+        info!("saving sample heatmap to {}", backend);
+        backend.present(&counts.heatmap())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "window")]
+/// The file the current estimate is saved to when 'Ctrl+S' is pressed during a progressive
+/// render without output file.
+const PROGRESSIVE_SAVE_FILE: &str = "render.png";
+
+#[cfg(feature = "window")]
+/// The downscaling factor of the preview displayed while the camera is moving.
+const PREVIEW_SCALE: u32 = 4;
+
+#[cfg(feature = "window")]
+/// The longest frame duration taken into account for camera movement, in seconds, so that
+/// a slow frame does not make the camera jump.
+const MAX_FRAME_TIME: f32 = 0.1;
+
+#[cfg(feature = "window")]
+/// The time spent rendering rows of the current pass in each frame, so that the window stays
+/// responsive however long a pass takes.
+const PASS_TIME_BUDGET: Duration = Duration::from_millis(30);
+
+/// The frame the estimates of a progressive render are shown and saved in: the background,
+/// and with a crop window, the existing image the window is copied into.
+struct ProgressiveFrame {
+    crop: Option<PixelRect>,
+    frame: Option<HdrBuffer>,
+    background: HdrBuffer,
+}
+
+impl ProgressiveFrame {
+    fn new(
+        scene: &Scene,
+        size: PixelSize,
+        opts: &RenderOpts,
+        border: bool,
+        output_file: Option<&str>,
+        format: Option<ImageFormat>,
+    ) -> Result<Self, Error> {
+        let crop = opts.crop.map(|c| c.rect(size));
+        let frame = match crop {
+            Some(_) if border => border_frame(output_file, format, size),
+            _ => None,
+        };
+        let mut background = HdrBuffer::try_new(size.width, size.height)?;
+        background.clear(scene.camera().clear_color());
+        Ok(ProgressiveFrame {
+            crop,
+            frame,
+            background,
+        })
+    }
+
+    /// Creates the [Film] the passes are rendered into, restricted to the crop window.
+    fn film(&self, opts: &RenderOpts) -> Film {
+        Film::new(self.background.size(), opts.filter).with_region(self.crop)
+    }
+
+    /// Returns the full frame estimate of the [Film], after post-processing.
+    fn estimate(&self, film: &Film, post: &PostStack) -> HdrBuffer {
+        let mut hdr = self.background.clone();
+        film.resolve_hdr(&mut hdr);
+        post.apply(&mut hdr);
+        hdr
+    }
+
+    /// Returns the output of the full frame `hdr`, see [crop_output].
+    fn output(&self, hdr: &HdrBuffer, border: bool) -> Result<HdrBuffer, Error> {
+        crop_output(hdr, self.crop, self.frame.as_ref(), border)
+    }
+}
+
+/// Renders the [Scene] progressively: each pass adds one sample per pixel to the [Film],
+/// and the current estimate is displayed in a window. The passes are split into rows rendered
+/// for at most [PASS_TIME_BUDGET] per frame, so a pass can span several frames.
+///
+/// The camera can be moved with the [FlyController]. While it moves, a low-resolution,
+/// single-sample preview is displayed, and the accumulation restarts when it stops.
+/// Pressing 'P' logs the camera pose as a scene file table, and 'Ctrl+S' saves the current
+/// estimate.
+///
+/// The render stops refining after `opts.samples` passes, and exits when the window is
+/// closed. The final estimate is saved into the output file, if any.
+///
+/// The post-processing effects are applied to the estimates, but not to the preview.
+///
+/// With a crop window, the passes only refine the window, but the full frame is displayed,
+/// and the preview covers it entirely. The estimates are saved as with [RunOpts::with_border].
+#[cfg(feature = "window")]
+fn run_progressive(
+    scene: &mut Scene,
+    size: PixelSize,
+    opts: &RenderOpts,
+    post: &PostStack,
+    border: bool,
+    output_file: Option<&str>,
+    format: Option<ImageFormat>,
+) -> Result<(), Error> {
+    let target = ProgressiveFrame::new(scene, size, opts, border, output_file, format)?;
+    let mut fb = target.output(&target.background, true)?.to_frame_buffer();
+    let mut film = target.film(opts);
+    let mut sampler = opts.sampler.create(opts.samples, opts.seed);
+
+    let preview_size = PixelSize::new(
+        (size.width / PREVIEW_SCALE).max(1),
+        (size.height / PREVIEW_SCALE).max(1),
+    );
+    let mut preview_fb = FrameBuffer::try_new(preview_size.width, preview_size.height)?;
+    let mut preview_film = Film::new(preview_size, opts.filter);
+
+    let mut window = LiveWindow::new(size.width, size.height)?;
+    let mut controller = FlyController::default();
+    info!(
+        "rendering progressively ({} passes) into {}",
+        opts.samples, window
+    );
+    info!("move with WASD, Q/E and mouse drag, 'P' prints the camera pose, 'Ctrl+S' saves");
+
+    let mut start = Instant::now();
+    let mut last_frame = Instant::now();
+    let mut passes = 0;
+    // the next row of the current pass, relative to the sampled region
+    let mut row = 0;
+    let rows = film.sampled_region().size.height;
+    let mut elapsed = 0.0;
+    while window.is_open() {
+        let dt = last_frame.elapsed().as_secs_f32().min(MAX_FRAME_TIME);
+        last_frame = Instant::now();
+
+        if controller.update(&window, scene.camera_mut(), dt) {
+            preview_film.clear();
+            scene.render_pass(
+                &mut preview_film,
+                &opts.with_crop(None),
+                sampler.as_mut(),
+                0,
+            );
+            preview_film.resolve(&mut preview_fb);
+            fb.blit_scaled(&preview_fb);
+
+            film.clear();
+            passes = 0;
+            row = 0;
+            start = Instant::now();
+            window.set_title("raytracer - moving - Press 'P' to print the pose, 'Esc' to exit");
+        } else if passes < opts.samples {
+            let frame_start = Instant::now();
+            while row < rows && frame_start.elapsed() < PASS_TIME_BUDGET {
+                scene.render_pass_rows(&mut film, opts, sampler.as_mut(), passes, row..row + 1);
+                row += 1;
+            }
+            let pass_done = row == rows;
+            if pass_done {
+                passes += 1;
+                row = 0;
+            }
+            // the post-processed estimate is only refreshed once per pass, as it is slower
+            if post.is_empty() && target.frame.is_none() {
+                film.resolve(&mut fb);
+            } else if pass_done {
+                fb = target
+                    .output(&target.estimate(&film, post), true)?
+                    .to_frame_buffer();
+            }
+            elapsed = start.elapsed().as_secs_f32();
+
+            let status = if passes < opts.samples {
+                "rendering"
+            } else {
+                "done"
+            };
+            window.set_title(&format!(
+                "raytracer - {} spp, {:.1}s ({}) - Press 'Ctrl+S' to save, 'Esc' to exit",
+                passes, elapsed, status
+            ));
+        }
+
+        window.update(&fb)?;
+
+        if window.is_key_pressed(Key::P) {
+            info!("camera pose:\n{}", camera_pose(scene.camera()));
+        }
+
+        let ctrl = window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl);
+        if ctrl && window.is_key_pressed(Key::S) {
+            let file = output_file.unwrap_or(PROGRESSIVE_SAVE_FILE);
+            info!("saving current estimate ({} spp) to {}", passes, file);
+            let hdr = target.output(&target.estimate(&film, post), border)?;
+            if let Err(e) = file_backend(file, format).present_hdr(&hdr) {
+                error!("could not save the current estimate: {}", e);
+            }
+        }
+    }
+
+    info!("stopped after {} passes ({:.1}s)", passes, elapsed);
+    if output_file.is_some() {
+        let hdr = target.output(&target.estimate(&film, post), border)?;
+        present(&hdr, output_file, format, false)?;
+    }
+
+    Ok(())
+}
+
+/// Fails without the `window` feature: progressive renders are displayed in a window.
+#[cfg(not(feature = "window"))]
+fn run_progressive(
+    _: &mut Scene,
+    _: PixelSize,
+    _: &RenderOpts,
+    _: &PostStack,
+    _: bool,
+    _: Option<&str>,
+    _: Option<ImageFormat>,
+) -> Result<(), Error> {
+    Err(no_window())
+}
+
+/// Returns the error of a render presented in a window, without the `window` feature.
+#[cfg(not(feature = "window"))]
+fn no_window() -> Error {
+    Error::NoDisplay(String::from(
+        "built without the window feature, use --output or --terminal",
+    ))
+}
+
+/// The minimum duration between two refreshes of the terminal during a progressive render.
+const TERMINAL_REFRESH: Duration = Duration::from_millis(500);
+
+/// Renders the [Scene] progressively as [run_progressive] does, but displays the current
+/// estimate in the terminal with a [TerminalBackend], e.g on a remote machine. The camera
+/// cannot be moved: the render stops after `opts.samples` passes, and the final estimate is
+/// saved into the output file, if any.
+fn run_progressive_terminal(
+    scene: &Scene,
+    size: PixelSize,
+    opts: &RenderOpts,
+    post: &PostStack,
+    border: bool,
+    output_file: Option<&str>,
+    format: Option<ImageFormat>,
+) -> Result<(), Error> {
+    let target = ProgressiveFrame::new(scene, size, opts, border, output_file, format)?;
+    let mut film = target.film(opts);
+    let mut sampler = opts.sampler.create(opts.samples, opts.seed);
+
+    let mut terminal = TerminalBackend::new();
+    info!(
+        "rendering progressively ({} passes) into {}",
+        opts.samples, terminal
+    );
+
+    let start = Instant::now();
+    let mut last_refresh = None;
+    for pass in 0..opts.samples {
+        scene.render_pass(&mut film, opts, sampler.as_mut(), pass);
+        let last = pass + 1 == opts.samples;
+        if last || last_refresh.is_none_or(|t: Instant| t.elapsed() >= TERMINAL_REFRESH) {
+            let fb = target
+                .output(&target.estimate(&film, post), true)?
+                .to_frame_buffer();
+            terminal.update(&fb)?;
+            last_refresh = Some(Instant::now());
+        }
+    }
+
+    info!(
+        "finished {} passes ({:.1}s)",
+        opts.samples,
+        start.elapsed().as_secs_f32()
+    );
+    if output_file.is_some() {
+        let hdr = target.output(&target.estimate(&film, post), border)?;
+        present(&hdr, output_file, format, false)?;
+    }
+
+    Ok(())
+}
+
 /// Runs the raytracer using the specified [RunOpts]
-pub fn run(opts: RunOpts) {
+pub fn run(opts: RunOpts) -> Result<(), Error> {
     info!("running raytracer");
-    info!("output file is {}", opts.output_file);
+    match &opts.output_file {
+        Some(file) => info!("output file is {}", file),
+        None if opts.terminal => info!("no output file, presenting in the terminal"),
+        None => info!("no output file, presenting in a window"),
+    }
 
-    info!("start rendering...");
+    debug!("start rendering...");
 
-    let scene = Scene::new();
+    let mut scene = match &opts.scene_file {
+        Some(file) => {
+            info!("loading scene {}", file);
+            Scene::from_file(file)?
+        }
+        None => Scene::new(),
+    };
+
+    if let Some(projection) = opts.projection {
+        scene.camera_mut().set_projection(projection);
+    }
+    let mut stereo = scene.camera().stereo();
+    if let Some(ipd) = opts.ipd {
+        stereo.ipd = ipd;
+    }
+    if opts.convergence.is_some() {
+        stereo.convergence = opts.convergence;
+    }
+    scene.camera_mut().set_stereo(stereo);
+    if let Some(turntable) = opts.turntable {
+        info!(
+            "orbiting the camera around the scene in {} frames",
+            turntable.frames
+        );
+        let mut animation = scene.animation().clone();
+        animation.camera = turntable.camera_animation(&scene)?;
+        scene.set_animation(animation);
+        scene.set_frame(1);
+    }
+    debug!("camera: {}", scene.camera());
 
-    let ratio = scene.camera().aspect();
-    let height = 512;
-    let width = (ratio * height as f32) as u32;
-    let mut fb = FrameBuffer::new(width, height);
-    info!("created {}", fb);
+    let size = scene.render_size();
+    size.check_not_empty()?;
+    if let Some(crop) = opts.render.crop {
+        crop.check(size)?;
+    }
 
-    let opts = RenderOpts::new().with_samples(Sampling::Samples4);
+    let render_opts = opts.render;
+    debug!("{} reconstruction filter", render_opts.filter);
+    if !render_opts.aovs.is_empty() {
+        debug!("AOVs: {}", render_opts.aovs);
+    }
+    match render_opts.adaptive {
+        Some(adaptive) => debug!(
+            "adaptive sampling ({} to {} samples per pixel, threshold {}), {} sampler (seed {})",
+            adaptive.min_samples,
+            adaptive.max_samples,
+            adaptive.threshold,
+            render_opts.sampler,
+            render_opts.seed
+        ),
+        None => debug!(
+            "{} samples per pixel, {} sampler (seed {})",
+            render_opts.samples, render_opts.sampler, render_opts.seed
+        ),
+    }
+    let mut post = opts.post.clone().unwrap_or_else(|| scene.post().clone());
+    post.bypass |= opts.bypass_post;
+    debug!("post-processing: {}", post);
 
-    scene.render(&mut fb, &opts, &progress_func);
+    let window = render_opts.crop.map(|c| c.rect(size));
+    if let Some(window) = window {
+        let output = if opts.border {
+            "full frame"
+        } else {
+            "window only"
+        };
+        info!("crop window: {} ({})", window, output);
+    }
 
-    // let backend = FileBackend::new(opts.output_file.as_str());
-    let backend = WindowBackend::new();
+    let heatmap_file = opts.sample_heatmap.as_deref();
+    let output_file = opts.output_file.as_deref();
 
-    info!("saving render to {}", backend);
-    backend.present(&fb);
+    if opts.progressive && opts.terminal {
+        run_progressive_terminal(
+            &scene,
+            size,
+            &render_opts,
+            &post,
+            opts.border,
+            output_file,
+            opts.format,
+        )?;
+        info!("finished.");
+        return Ok(());
+    }
+    if opts.progressive {
+        run_progressive(
+            &mut scene,
+            size,
+            &render_opts,
+            &post,
+            opts.border,
+            output_file,
+            opts.format,
+        )?;
+        info!("finished.");
+        return Ok(());
+    }
+
+    let frames = opts
+        .frames
+        .or_else(|| opts.turntable.map(|t| FrameRange::new(1, t.frames)));
+    if let Some(backend) = output_file.and_then(AnimationBackend::new) {
+        if opts.stereo.is_some() || !render_opts.aovs.is_empty() {
+            return Err(Error::InvalidOption(String::from(
+                "animated images cannot have stereo pairs or AOVs",
+            )));
+        }
+
+        let mut images = Vec::new();
+        for frame in frames.iter().flat_map(FrameRange::frames) {
+            info!("rendering frame {}", frame);
+            scene.set_frame(frame);
+            let frame_heatmap_file = heatmap_file.map(|f| with_suffix(f, &format!("{:04}", frame)));
+            images.push(
+                render_mono(
+                    &scene,
+                    &opts,
+                    size,
+                    &post,
+                    None,
+                    frame_heatmap_file.as_deref(),
+                )?
+                .0
+                .to_frame_buffer(),
+            );
+        }
+        if images.is_empty() {
+            images.push(
+                render_mono(&scene, &opts, size, &post, None, heatmap_file)?
+                    .0
+                    .to_frame_buffer(),
+            );
+        }
+
+        let backend = backend.with_fps(opts.fps);
+        info!("saving {} frames to {}", images.len(), backend);
+        backend.present_frames(&images)?;
+        info!("finished.");
+        return Ok(());
+    }
+
+    match frames {
+        Some(frames) => {
+            info!("rendering frames {} of the animation", frames);
+            for frame in frames.frames() {
+                info!("rendering frame {}", frame);
+                scene.set_frame(frame);
+                let suffix = format!("{:04}", frame);
+                let frame_file = output_file.map(|f| with_suffix(f, &suffix));
+                let frame_heatmap_file = heatmap_file.map(|f| with_suffix(f, &suffix));
+                render_still(
+                    &scene,
+                    &opts,
+                    size,
+                    &post,
+                    frame_file.as_deref(),
+                    frame_heatmap_file.as_deref(),
+                )?;
+            }
+        }
+        None => render_still(&scene, &opts, size, &post, output_file, heatmap_file)?,
+    }
 
     info!("finished.");
+    Ok(())
+}
+
+/// Renders the [Scene] with its post-processing effects, and crops the render and its AOVs.
+/// With a border, the crop window is copied into the existing output file, if any.
+fn render_mono(
+    scene: &Scene,
+    opts: &RunOpts,
+    size: PixelSize,
+    post: &PostStack,
+    output_file: Option<&str>,
+    heatmap_file: Option<&str>,
+) -> Result<(HdrBuffer, Vec<AovImage>), Error> {
+    let (mut hdr, counts, mut aovs) = render(
+        scene,
+        size,
+        &opts.render,
+        opts.denoiser,
+        opts.checkpoint.as_ref(),
+    )?;
+    report_sample_counts(&counts, heatmap_file)?;
+    post.apply(&mut hdr);
+
+    if let Some(window) = opts.render.crop.map(|c| c.rect(size)) {
+        let frame = if opts.border {
+            border_frame(output_file, opts.format, size)
+        } else {
+            None
+        };
+        hdr = crop_output(&hdr, Some(window), frame.as_ref(), opts.border)?;
+        if !opts.border {
+            aovs = aovs.iter().map(|a| a.crop(window)).collect();
+        }
+    }
+
+    Ok((hdr, aovs))
+}
+
+/// Renders a still image of the [Scene], or a stereo pair, and presents it into the output
+/// file if any.
+fn render_still(
+    scene: &Scene,
+    opts: &RunOpts,
+    size: PixelSize,
+    post: &PostStack,
+    output_file: Option<&str>,
+    heatmap_file: Option<&str>,
+) -> Result<(), Error> {
+    let render_opts = opts.render;
+    let window = render_opts.crop.map(|c| c.rect(size));
+
+    let layout = match opts.stereo {
+        None => {
+            let (hdr, aovs) = render_mono(scene, opts, size, post, output_file, heatmap_file)?;
+            match output_file {
+                Some(file) if !aovs.is_empty() => {
+                    present_with_aovs(&hdr, &aovs, file, opts.format, opts.separate_aovs)?
+                }
+                _ => present(&hdr, output_file, opts.format, opts.terminal)?,
+            }
+
+            return Ok(());
+        }
+        Some(layout) => layout,
+    };
+
+    info!(
+        "rendering stereo pair ({:?}, {:?})",
+        layout,
+        scene.camera().stereo()
+    );
+    let mut eyes = Vec::new();
+    for eye in [Eye::Left, Eye::Right].iter() {
+        info!("rendering {} eye", eye);
+        let (mut hdr, counts, _) = render(
+            scene,
+            size,
+            &render_opts.with_eye(Some(*eye)),
+            opts.denoiser,
+            None,
+        )?;
+        post.apply(&mut hdr);
+        // the existing output is only updated for monoscopic renders
+        hdr = crop_output(&hdr, window, None, opts.border)?;
+        let eye_heatmap_file = heatmap_file.map(|f| with_suffix(f, &eye.to_string()));
+        report_sample_counts(&counts, eye_heatmap_file.as_deref())?;
+        eyes.push(hdr);
+    }
+
+    let (left, right) = (&eyes[0], &eyes[1]);
+    let (width, height) = (left.size().width, left.size().height);
+    match layout {
+        StereoLayout::Separate => {
+            let left_file = output_file.map(|f| with_suffix(f, "left"));
+            let right_file = output_file.map(|f| with_suffix(f, "right"));
+            present(left, left_file.as_deref(), opts.format, opts.terminal)?;
+            present(right, right_file.as_deref(), opts.format, opts.terminal)?;
+        }
+        StereoLayout::SideBySide => {
+            let mut hdr = HdrBuffer::try_new(width * 2, height)?;
+            hdr.blit(left, Pixel::new(0, 0));
+            hdr.blit(right, Pixel::new(width, 0));
+            present(&hdr, output_file, opts.format, opts.terminal)?;
+        }
+        StereoLayout::TopBottom => {
+            let mut hdr = HdrBuffer::try_new(width, height * 2)?;
+            hdr.blit(left, Pixel::new(0, 0));
+            hdr.blit(right, Pixel::new(0, height));
+            present(&hdr, output_file, opts.format, opts.terminal)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_checkpoint_intervals() {
+        assert_eq!(
+            Ok(Duration::from_secs(90)),
+            CheckpointOpts::parse_interval("90")
+        );
+        assert_eq!(Ok(Duration::ZERO), CheckpointOpts::parse_interval("0"));
+        for invalid in ["-1", "inf", "NaN", "1e30", "soon"] {
+            assert!(
+                CheckpointOpts::parse_interval(invalid).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
 }
@@ -0,0 +1,269 @@
+//! The subcommands of the command line application that print their results, on top of
+//! the library.
+use log::info;
+use raytracer::{
+    read_image, Backend, Benchmark, Comparison, Error, FileBackend, Film, PixelSize, RayStats,
+    RenderOpts, Scene, SceneInfo, Vec3,
+};
+use std::fs;
+use std::time::Instant;
+
+/// Compares the image with the reference, and prints the error metrics (see [Comparison]).
+/// The perceptual errors are written as a false-color heatmap if requested.
+pub(crate) fn compare(
+    reference: &str,
+    image: &str,
+    heatmap_file: Option<&str>,
+) -> Result<(), Error> {
+    info!("comparing {} with the reference {}", image, reference);
+    let comparison = Comparison::new(&read_image(reference)?, &read_image(image)?)?;
+
+    println!("MSE:  {:.6}", comparison.mse);
+    println!("PSNR: {:.2} dB", comparison.psnr);
+    println!("SSIM: {:.4}", comparison.ssim);
+    println!("FLIP: {:.4}", comparison.flip);
+
+    if let Some(file) = heatmap_file {
+        let backend = FileBackend::new(file);
+        info!("saving error heatmap to {}", backend);
+        backend.present(&comparison.heatmap())?;
+    }
+
+    Ok(())
+}
+
+/// The measurements of a [Benchmark].
+struct BenchResult {
+    benchmark: Benchmark,
+    entities: usize,
+    size: PixelSize,
+    seconds: f64,
+    stats: RayStats,
+    /// The peak memory used while building and rendering the scene, above the memory in
+    /// use before, if known.
+    peak_memory: Option<u64>,
+}
+
+/// Renders the [Benchmark]s, and writes their timings and [RayStats] as JSON into the
+/// output file, or on the standard output if there is none.
+pub(crate) fn bench(
+    benchmarks: &[Benchmark],
+    width: u32,
+    opts: &RenderOpts,
+    output_file: Option<&str>,
+) -> Result<(), Error> {
+    let mut results = Vec::new();
+    for benchmark in benchmarks {
+        let memory_before = reset_peak_memory();
+        let scene = benchmark.build();
+        let height = (width as f32 / scene.camera().aspect()) as u32;
+        let size = PixelSize::new(width, height);
+        size.check_not_empty()?;
+
+        info!(
+            "benchmarking {} ({} entities, {}*{})",
+            benchmark,
+            scene.entities().len(),
+            width,
+            height
+        );
+        let mut film = Film::new(size, opts.filter);
+        let start = Instant::now();
+        scene.camera().render_film(&scene, &mut film, opts, &|_| {});
+        let seconds = start.elapsed().as_secs_f64();
+
+        let stats = film.ray_stats();
+        info!("{}: {:.3}s, {}", benchmark, seconds, stats);
+        results.push(BenchResult {
+            benchmark: *benchmark,
+            entities: scene.entities().len(),
+            size,
+            seconds,
+            stats,
+            peak_memory: memory_before
+                .and_then(|before| Some(memory_status("VmHWM")?.saturating_sub(before))),
+        });
+    }
+
+    let json = bench_json(&results, opts);
+    match output_file {
+        Some(file) => {
+            info!("saving benchmark results to {}", file);
+            fs::write(file, json).map_err(Error::io(file))
+        }
+        None => {
+            print!("{}", json);
+            Ok(())
+        }
+    }
+}
+
+/// Formats the results of the benchmarks as a JSON document.
+fn bench_json(results: &[BenchResult], opts: &RenderOpts) -> String {
+    let scenes: Vec<String> = results
+        .iter()
+        .map(|r| {
+            let peak_memory = r
+                .peak_memory
+                .map_or(String::from("null"), |m| m.to_string());
+            format!(
+                concat!(
+                    "    {{\n",
+                    "      \"name\": \"{}\",\n",
+                    "      \"entities\": {},\n",
+                    "      \"width\": {},\n",
+                    "      \"height\": {},\n",
+                    "      \"seconds\": {:.6},\n",
+                    "      \"rays\": {},\n",
+                    "      \"rays_per_second\": {:.0},\n",
+                    "      \"primary_rays\": {},\n",
+                    "      \"shadow_rays\": {},\n",
+                    "      \"secondary_rays\": {},\n",
+                    "      \"bounds_tests_per_ray\": {:.4},\n",
+                    "      \"intersection_tests_per_ray\": {:.4},\n",
+                    "      \"peak_memory_delta_bytes\": {}\n",
+                    "    }}"
+                ),
+                r.benchmark,
+                r.entities,
+                r.size.width,
+                r.size.height,
+                r.seconds,
+                r.stats.rays(),
+                r.stats.rays() as f64 / r.seconds.max(f64::EPSILON),
+                r.stats.primary_rays,
+                r.stats.shadow_rays,
+                r.stats.secondary_rays,
+                r.stats.bounds_tests_per_ray(),
+                r.stats.intersection_tests_per_ray(),
+                peak_memory,
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\n  \"version\": \"{}\",\n  \"samples\": {},\n  \"sampler\": \"{}\",\n  \"scenes\": [\n{}\n  ]\n}}\n",
+        env!("CARGO_PKG_VERSION"),
+        opts.samples,
+        opts.sampler,
+        scenes.join(",\n")
+    )
+}
+
+/// Resets the peak resident memory of the process to its current resident memory, and
+/// returns it, so that the peak only measures what follows. Only available on Linux.
+fn reset_peak_memory() -> Option<u64> {
+    fs::write("/proc/self/clear_refs", "5").ok()?;
+    memory_status("VmRSS")
+}
+
+/// Reads a memory field of the process status, e.g `VmHWM` for the peak resident memory,
+/// in bytes. Only available on Linux.
+fn memory_status(field: &str) -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| {
+        l.strip_prefix(field)
+            .is_some_and(|rest| rest.starts_with(':'))
+    })?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Loads the scene file without rendering it, and prints its content, its camera, an
+/// estimate of the memory needed to render it, and the problems found in it.
+pub(crate) fn info(scene_file: &str) -> Result<(), Error> {
+    info!("loading scene {}", scene_file);
+    let scene = Scene::from_file(scene_file)?;
+    let info = SceneInfo::new(&scene);
+    let camera = scene.camera();
+
+    let primitives: Vec<String> = info
+        .primitives
+        .iter()
+        .map(|(name, n)| format!("{}: {}", name, n))
+        .collect();
+    let vec = |v: Vec3| format!("[{}, {}, {}]", v.x, v.y, v.z);
+    println!("scene:      {}", scene_file);
+    println!("entities:   {}", info.entities);
+    println!(
+        "primitives: {} ({}), {} distinct",
+        info.primitives.values().sum::<usize>(),
+        primitives.join(", "),
+        info.distinct_primitives
+    );
+    println!("triangles:  {}", info.triangles);
+    println!("materials:  {}", info.materials);
+    println!("lights:     {}", info.lights);
+    println!("textures:   {}", info.textures);
+    match info.bounds {
+        Some((min, max)) => println!("bounds:     {} to {}", vec(min), vec(max)),
+        None => println!("bounds:     none"),
+    }
+
+    let (yaw, pitch) = camera.orientation();
+    let (open, close) = camera.shutter();
+    let stereo = camera.stereo();
+    println!(
+        "camera:     {} at {}, yaw {:.2}, pitch {:.2}",
+        camera.projection(),
+        vec(camera.position()),
+        yaw,
+        pitch
+    );
+    println!(
+        "            shutter [{}, {}], aspect {:.3}, background {}",
+        open,
+        close,
+        camera.aspect(),
+        camera.clear_color()
+    );
+    match stereo.convergence {
+        Some(convergence) => println!(
+            "            stereo ipd {}, convergence {}",
+            stereo.ipd, convergence
+        ),
+        None => println!("            stereo ipd {}, parallel", stereo.ipd),
+    }
+    if let Some((first, last)) = info.keyframes {
+        println!(
+            "animation:  {} animated nodes, keyframes from frame {} to {}",
+            info.animated_nodes, first, last
+        );
+    }
+
+    println!(
+        "memory:     {} for the scene, {} to render it at {}*{}",
+        format_bytes(info.memory),
+        format_bytes(info.render_memory),
+        info.render_size.width,
+        info.render_size.height
+    );
+
+    if info.warnings.is_empty() {
+        println!("warnings:   none");
+    } else {
+        println!("warnings:   {}", info.warnings.len());
+        for warning in &info.warnings {
+            println!("  - {}", warning);
+        }
+    }
+
+    Ok(())
+}
+
+/// Formats a number of bytes with a binary unit, e.g `1.5 MiB`.
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -0,0 +1,95 @@
+//! The errors of the raytracer.
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// An error that prevents a render from completing.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read or written.
+    Io { path: String, source: io::Error },
+    /// An image could not be encoded into a file.
+    Encoding {
+        path: String,
+        source: image::ImageError,
+    },
+    /// An image file could not be decoded.
+    Decoding {
+        path: String,
+        source: image::ImageError,
+    },
+    /// A scene description is invalid.
+    Scene(String),
+    /// The options of the render are invalid, e.g a crop window outside of the image.
+    InvalidOption(String),
+    /// No window can be opened, e.g because there is no display.
+    NoDisplay(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path, source),
+            Error::Encoding { path, source } => write!(f, "could not encode {}: {}", path, source),
+            Error::Decoding { path, source } => write!(f, "could not decode {}: {}", path, source),
+            Error::Scene(message) => write!(f, "invalid scene: {}", message),
+            Error::InvalidOption(message) => write!(f, "invalid option: {}", message),
+            Error::NoDisplay(message) => write!(f, "could not open a window: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Encoding { source, .. } | Error::Decoding { source, .. } => Some(source),
+            Error::Scene(_) | Error::InvalidOption(_) | Error::NoDisplay(_) => None,
+        }
+    }
+}
+
+impl Error {
+    /// Returns a function that wraps an [io::Error] on the file, to use with `map_err`.
+    pub fn io(path: &str) -> impl Fn(io::Error) -> Error + '_ {
+        move |source| Error::Io {
+            path: String::from(path),
+            source,
+        }
+    }
+
+    /// Returns a function that wraps an [image::ImageError] on the file, to use with `map_err`.
+    pub fn encoding(path: &str) -> impl Fn(image::ImageError) -> Error + '_ {
+        move |source| Error::Encoding {
+            path: String::from(path),
+            source,
+        }
+    }
+
+    /// Returns a function that wraps an [image::ImageError] on reading the file, to use with
+    /// `map_err`. I/O errors, e.g a missing file, become [Error::Io].
+    pub fn decoding(path: &str) -> impl Fn(image::ImageError) -> Error + '_ {
+        move |source| match source {
+            image::ImageError::IoError(source) => Error::io(path)(source),
+            source => Error::Decoding {
+                path: String::from(path),
+                source,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let e = Error::io("out.png")(io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+        assert_eq!("out.png: denied", e.to_string());
+        assert!(std::error::Error::source(&e).is_some());
+        assert_eq!(
+            "invalid scene: oops",
+            Error::Scene(String::from("oops")).to_string()
+        );
+    }
+}
@@ -0,0 +1,65 @@
+//! A basic raytracer.
+//!
+//! The most common types are exported at the root of the crate: [Scene]s are made of
+//! [Entity]s (a [Transform], a [Material] and a [Primitive]) and a [Camera]. They are
+//! rendered with [RenderOpts] into [RenderTarget]s, which [Backend]s present, e.g into
+//! image files.
+//!
+//! ```
+//! use raytracer::{Camera, Entity, Material, RenderOpts, RenderTarget, FrameBuffer, Scene, Sphere, Transform, Vec3, RED};
+//! use std::sync::Arc;
+//!
+//! let sphere = Entity::new(
+//!     Transform::default().with_position(Vec3::new(0.0, 0.0, -3.0)),
+//!     Material::from_diffuse(RED),
+//!     Arc::new(Sphere::new(1.0)),
+//! );
+//! let scene = Scene::from_parts(vec![sphere], Camera::new());
+//!
+//! let mut target = FrameBuffer::new(32, 18);
+//! let opts = RenderOpts::new().with_samples(4);
+//! let counts = scene.render(&mut target, &opts, &|_progress| {});
+//!
+//! assert_eq!(4, counts.max());
+//! assert_eq!(RED, target.get(raytracer::Pixel::new(16, 9)));
+//! ```
+//!
+//! Scenes can also be loaded from [SceneDescription] files. The [run] function and its
+//! [RunOpts] run renders for the command line application, e.g with a [PostStack] or the
+//! [Denoiser]. The [Benchmark] scenes, the [SceneInfo] and the [Comparison] of images back
+//! its other subcommands.
+//!
+//! The [WindowBackend] is only built with the `window` feature, enabled by default.
+
+mod app;
+mod error;
+mod math;
+mod rendering;
+mod scene;
+
+pub use app::{run, CheckpointOpts, RunOpts};
+pub use error::Error;
+pub use math::{Rotation, Vec3};
+#[cfg(feature = "window")]
+pub use rendering::backends::WindowBackend;
+pub use rendering::backends::{
+    read_image, AnimationBackend, AnimationFormat, Backend, FileBackend, ImageFormat, NullBackend,
+    TerminalBackend,
+};
+pub use rendering::denoise::{DenoiseGuides, Denoiser};
+pub use rendering::metrics::Comparison;
+pub use rendering::{
+    Adaptive, Aov, AovImage, AovSet, Color, CropWindow, Film, Filter, FrameBuffer, HdrBuffer,
+    Material, Pixel, PixelRect, PixelSize, PostEffect, PostStack, RayStats, RenderOpts,
+    RenderTarget, SampleCounts, SamplerKind,
+};
+pub use rendering::{BLACK, BLUE, DARK_GRAY, DARK_GREEN, GRAY, GREEN, RED, WHITE};
+pub use scene::benchmarks::Benchmark;
+pub use scene::camera::Camera;
+pub use scene::description::camera_pose;
+pub use scene::info::SceneInfo;
+pub use scene::primitives::{Mesh, Sphere};
+pub use scene::{
+    Entity, Eye, FrameRange, Node, NodeId, Primitive, Projection, Scene, SceneDescription,
+    SceneGraph, Stereo, StereoLayout, Transform, Turntable,
+};
@@ -1,19 +1,20 @@
-//! A simple Raytracer
+//! A simple Raytracer: the command line interface of the library.
 
-use crate::app::RunOpts;
 use clap::{
     crate_authors, crate_description, crate_name, crate_version, App, Arg, ArgMatches, SubCommand,
 };
+use log::error;
+use raytracer::{
+    Adaptive, AovSet, Benchmark, CheckpointOpts, CropWindow, Denoiser, Error, Filter, FrameRange,
+    ImageFormat, PostStack, Projection, RenderOpts, RunOpts, SamplerKind, StereoLayout, Turntable,
+};
 use simplelog::*;
+use std::process;
+use std::time::Duration;
 
-mod app;
-mod math;
-mod rendering;
-mod scene;
+mod commands;
 
 fn main() {
-    configure_logger();
-
     let matches = App::new(crate_name!())
         .version(crate_version!())
         .author(crate_authors!())
@@ -24,38 +25,493 @@ fn main() {
                 .arg(
                     Arg::with_name("output")
                         .short("o")
-                        .help("the output image file to write"),
+                        .long("output")
+                        .takes_value(true)
+                        .help("the output image file to write (displays the render in a window if absent)"),
+                )
+                .arg(
+                    Arg::with_name("terminal")
+                        .long("terminal")
+                        .conflicts_with("output")
+                        .help("displays the render in the terminal with 24-bit colors instead of a window, e.g over SSH. With --progressive, the terminal is refreshed during the render, and the camera cannot be moved"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .requires("output")
+                        .validator(|v| v.parse::<ImageFormat>().map(|_| ()))
+                        .help("the format of the output file, instead of guessing it from its extension: png, jpeg[:quality], bmp, tga, ppm, pfm, hdr or exr. pfm, hdr and exr store linear, unclamped values"),
+                )
+                .arg(
+                    Arg::with_name("scene")
+                        .short("s")
+                        .long("scene")
+                        .takes_value(true)
+                        .help("the scene description file (TOML) to render"),
+                )
+                .arg(
+                    Arg::with_name("projection")
+                        .short("p")
+                        .long("projection")
+                        .takes_value(true)
+                        .validator(|v| v.parse::<Projection>().map(|_| ()))
+                        .help("the camera projection: perspective, orthographic[:height], fisheye[:fov] or equirectangular"),
+                )
+                .arg(
+                    Arg::with_name("stereo")
+                        .long("stereo")
+                        .takes_value(true)
+                        .validator(|v| v.parse::<StereoLayout>().map(|_| ()))
+                        .help("renders a stereo pair: separate, side-by-side or top-bottom"),
+                )
+                .arg(
+                    Arg::with_name("ipd")
+                        .long("ipd")
+                        .takes_value(true)
+                        .requires("stereo")
+                        .validator(is_positive_number)
+                        .help("the interpupillary distance of the stereo pair, in scene units"),
+                )
+                .arg(
+                    Arg::with_name("convergence")
+                        .long("convergence")
+                        .takes_value(true)
+                        .requires("stereo")
+                        .validator(is_positive_number)
+                        .help("the distance at which the eyes of the stereo pair converge"),
+                )
+                .arg(
+                    Arg::with_name("samples")
+                        .short("n")
+                        .long("samples")
+                        .takes_value(true)
+                        .validator(is_positive_integer)
+                        .help("the number of samples per pixel"),
+                )
+                .arg(
+                    Arg::with_name("adaptive")
+                        .long("adaptive")
+                        .takes_value(true)
+                        .value_name("threshold")
+                        .validator(is_positive_number)
+                        .help("enables adaptive sampling: pixels get more samples until their relative error is below the threshold (--samples becomes the minimum, at least 2)"),
+                )
+                .arg(
+                    Arg::with_name("max-samples")
+                        .long("max-samples")
+                        .takes_value(true)
+                        .requires("adaptive")
+                        .validator(is_positive_integer)
+                        .help("the maximum number of samples per pixel with adaptive sampling (default: 16 times --samples)"),
+                )
+                .arg(
+                    Arg::with_name("sample-heatmap")
+                        .long("sample-heatmap")
+                        .takes_value(true)
+                        .value_name("file")
+                        .help("writes the number of samples of each pixel as a heatmap image"),
+                )
+                .arg(
+                    Arg::with_name("sampler")
+                        .long("sampler")
+                        .takes_value(true)
+                        .validator(|v| v.parse::<SamplerKind>().map(|_| ()))
+                        .help("the sampler: independent, stratified, halton or sobol"),
+                )
+                .arg(
+                    Arg::with_name("filter")
+                        .long("filter")
+                        .takes_value(true)
+                        .validator(|v| v.parse::<Filter>().map(|_| ()))
+                        .help("the pixel reconstruction filter: box, tent, gaussian, mitchell or lanczos, with an optional radius in pixels (e.g gaussian:1.5)"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .takes_value(true)
+                        .validator(|v| v.parse::<u32>().map(|_| ()).map_err(|e| e.to_string()))
+                        .help("the seed of the sampler"),
+                )
+                .arg(
+                    Arg::with_name("aov")
+                        .long("aov")
+                        .takes_value(true)
+                        .value_name("aovs")
+                        .requires("output")
+                        .conflicts_with_all(&["stereo", "progressive"])
+                        .validator(|v| v.parse::<AovSet>().map(|_| ()))
+                        .help("renders arbitrary output variables along the image, as a comma-separated list or 'all': depth, normal, camera-normal, albedo, position, uv, object-id, material-id, direct, indirect. They are written as layers of EXR outputs, or else as separate files (e.g out_depth.png)"),
+                )
+                .arg(
+                    Arg::with_name("separate-aovs")
+                        .long("separate-aovs")
+                        .requires("aov")
+                        .help("writes the AOVs into separate files, even for EXR outputs"),
+                )
+                .arg(
+                    Arg::with_name("denoise")
+                        .long("denoise")
+                        .takes_value(true)
+                        .value_name("strength")
+                        .min_values(0)
+                        .conflicts_with("progressive")
+                        .validator(|v| match v.parse::<f32>() {
+                            Ok(n) if n >= 0.0 => Ok(()),
+                            _ => Err(format!("{} is not a valid strength", v)),
+                        })
+                        .help("denoises the render with a filter guided by the albedo and normal AOVs. The optional strength defaults to 1"),
+                )
+                .arg(
+                    Arg::with_name("crop")
+                        .long("crop")
+                        .takes_value(true)
+                        .value_name("window")
+                        .validator(|v| v.parse::<CropWindow>().map(|_| ()))
+                        .help("only renders a window of the image, as 'x,y,width,height' in pixels, or 'normalized:x0,y0,x1,y1' where (1, 1) is the bottom-right corner"),
+                )
+                .arg(
+                    Arg::with_name("border")
+                        .long("border")
+                        .requires("crop")
+                        .help("outputs the full frame instead of the crop window. Only the window is updated: the rest of the frame is read from the existing output file, if possible"),
+                )
+                .arg(
+                    Arg::with_name("post")
+                        .long("post")
+                        .takes_value(true)
+                        .value_name("effects")
+                        .validator(|v| v.parse::<PostStack>().map(|_| ()))
+                        .help("overrides the post-processing effects of the scene with a comma-separated list, applied in order, e.g 'bloom:0.8,vignette:0.3'. Possible effects: bloom[:threshold], vignette[:strength], grain[:amount], chromatic-aberration[:strength], white-balance[:kelvin], lut:<file.cube>, or 'none'"),
+                )
+                .arg(
+                    Arg::with_name("no-post")
+                        .long("no-post")
+                        .conflicts_with("post")
+                        .help("bypasses all the post-processing effects"),
+                )
+                .arg(
+                    Arg::with_name("checkpoint")
+                        .long("checkpoint")
+                        .takes_value(true)
+                        .value_name("file")
+                        .conflicts_with_all(&["progressive", "stereo"])
+                        .help("renders pass by pass, and periodically saves the state of the render into the file"),
+                )
+                .arg(
+                    Arg::with_name("checkpoint-interval")
+                        .long("checkpoint-interval")
+                        .takes_value(true)
+                        .value_name("seconds")
+                        .requires("checkpoint")
+                        .validator(|v| CheckpointOpts::parse_interval(&v).map(|_| ()))
+                        .help("the minimum duration between two checkpoints. Defaults to 60 seconds"),
+                )
+                .arg(
+                    Arg::with_name("resume")
+                        .long("resume")
+                        .requires("checkpoint")
+                        .help("continues the render saved in the checkpoint file, which requires the same scene, camera and options. With more samples, adds samples to a finished render"),
+                )
+                .arg(
+                    Arg::with_name("frames")
+                        .long("frames")
+                        .takes_value(true)
+                        .value_name("range")
+                        .requires("output")
+                        .conflicts_with_all(&["progressive", "checkpoint"])
+                        .validator(|v| v.parse::<FrameRange>().map(|_| ()))
+                        .help("renders the frames of the scene animation, as 'start..end' (both included) or a single frame, into numbered files, e.g out_0001.png, or into an animated .gif or .apng. Frames are independent, so a sequence can be split across machines"),
+                )
+                .arg(
+                    Arg::with_name("turntable")
+                        .long("turntable")
+                        .takes_value(true)
+                        .value_name("frames")
+                        .requires("output")
+                        .conflicts_with_all(&["progressive", "checkpoint"])
+                        .validator(is_positive_integer)
+                        .help("orbits the camera around the scene in the number of frames, which are rendered unless --frames is specified"),
+                )
+                .arg(
+                    Arg::with_name("fps")
+                        .long("fps")
+                        .takes_value(true)
+                        .validator(is_positive_number)
+                        .help("the number of frames per second of animated outputs (24 by default). The output is an animated image if its extension is .gif or .apng: GIF colors are quantized into a palette, with dithering"),
+                )
+                .arg(
+                    Arg::with_name("progressive")
+                        .long("progressive")
+                        .conflicts_with_all(&["stereo", "adaptive"])
+                        .help("renders in passes of one sample per pixel, displaying the current estimate in a window (--samples becomes the number of passes, 1024 by default). Move the camera with WASD, Q/E and mouse drag, press 'P' to print the camera pose and 'Ctrl+S' to save the current estimate"),
                 )
                 .arg(
                     Arg::with_name("verbose")
                         .short("v")
-                        .help("print detailed messages"),
+                        .help("prints detailed messages, e.g the render settings"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compare")
+                .about("compares an image with a reference, and prints the MSE, PSNR, SSIM and a FLIP-like perceptual error")
+                .arg(
+                    Arg::with_name("reference")
+                        .required(true)
+                        .help("the reference image"),
+                )
+                .arg(
+                    Arg::with_name("image")
+                        .required(true)
+                        .help("the image to compare with the reference, of the same size"),
+                )
+                .arg(
+                    Arg::with_name("heatmap")
+                        .long("heatmap")
+                        .takes_value(true)
+                        .value_name("file")
+                        .help("writes the perceptual error of each pixel as a false-color heatmap, from blue (none) to red"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("loads and validates a scene file without rendering it, and prints its statistics and warnings")
+                .arg(
+                    Arg::with_name("scene")
+                        .required(true)
+                        .help("the scene description file (TOML)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("renders standard scenes, and outputs the timings and ray statistics as JSON")
+                .arg(
+                    Arg::with_name("scenes")
+                        .long("scenes")
+                        .takes_value(true)
+                        .validator(|v| v.split(',').try_for_each(|s| s.parse::<Benchmark>().map(|_| ())))
+                        .help("a comma-separated list of scenes: default, spheres, motion-blur or instances (all by default)"),
+                )
+                .arg(
+                    Arg::with_name("samples")
+                        .short("n")
+                        .long("samples")
+                        .takes_value(true)
+                        .validator(is_positive_integer)
+                        .help("the number of samples per pixel (4 by default)"),
+                )
+                .arg(
+                    Arg::with_name("width")
+                        .long("width")
+                        .takes_value(true)
+                        .validator(is_positive_integer)
+                        .help("the width of the renders in pixels (256 by default)"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .help("the JSON file to write (writes on the standard output if absent)"),
                 ),
         )
         .get_matches();
 
-    match matches.subcommand() {
+    let run = matches.subcommand_matches("run");
+    let verbose = run.is_some_and(|m| m.is_present("verbose"));
+    // the terminal backend draws on the standard output, so the logs must not interleave with it
+    let mode = if run.is_some_and(|m| m.is_present("terminal")) {
+        TerminalMode::Stderr
+    } else {
+        TerminalMode::Mixed
+    };
+    configure_logger(
+        if verbose {
+            LevelFilter::Trace
+        } else {
+            LevelFilter::Info
+        },
+        mode,
+    );
+
+    let result = match matches.subcommand() {
         ("run", Some(subcommand)) => prepare_run(subcommand),
-        _ => println!("{}", matches.usage()),
+        ("bench", Some(subcommand)) => prepare_bench(subcommand),
+        ("info", Some(subcommand)) => commands::info(subcommand.value_of("scene").unwrap()),
+        ("compare", Some(subcommand)) => commands::compare(
+            subcommand.value_of("reference").unwrap(),
+            subcommand.value_of("image").unwrap(),
+            subcommand.value_of("heatmap"),
+        ),
+        _ => {
+            println!("{}", matches.usage());
+            Ok(())
+        }
+    };
+
+    if let Err(e) = result {
+        error!("{}", e);
+        process::exit(1);
     }
 }
 
+/// The default number of passes of progressive rendering.
+const PROGRESSIVE_PASSES: u32 = 1024;
+
+/// The default minimum duration between two checkpoints.
+const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
 #[doc(hidden)]
-fn prepare_run(p0: &ArgMatches) {
-    let output_file = p0.value_of("output").unwrap_or("run.png");
-    let verbose = p0.value_of("verbose");
+fn prepare_run(p0: &ArgMatches) -> Result<(), Error> {
+    let mut run_opts = RunOpts::new(p0.value_of("output"));
+    if p0.is_present("terminal") {
+        run_opts = run_opts.with_terminal();
+    }
+    if let Some(format) = p0.value_of("format") {
+        run_opts = run_opts.with_format(format.parse().unwrap());
+    }
+    if let Some(scene) = p0.value_of("scene") {
+        run_opts = run_opts.with_scene_file(scene);
+    }
+    if let Some(projection) = p0.value_of("projection") {
+        run_opts = run_opts.with_projection(projection.parse().unwrap());
+    }
 
-    let run_opts = RunOpts::new(output_file, verbose.is_some());
+    let mut render_opts = run_opts.render_opts();
+    if p0.is_present("progressive") {
+        run_opts = run_opts.with_progressive();
+        render_opts = render_opts.with_samples(PROGRESSIVE_PASSES);
+    }
+    if let Some(samples) = p0.value_of("samples") {
+        render_opts = render_opts.with_samples(samples.parse().unwrap());
+    }
+    if let Some(threshold) = p0.value_of("adaptive") {
+        let min_samples = render_opts.samples;
+        let max_samples = p0
+            .value_of("max-samples")
+            .map(|v| v.parse().unwrap())
+            .unwrap_or_else(|| min_samples.saturating_mul(16));
+        let adaptive = Adaptive::new(min_samples, max_samples, threshold.parse().unwrap());
+        if max_samples < adaptive.min_samples {
+            return Err(Error::InvalidOption(format!(
+                "--max-samples ({}) is below the minimum number of samples ({})",
+                max_samples, adaptive.min_samples
+            )));
+        }
+        render_opts = render_opts.with_adaptive(Some(adaptive));
+    }
+    if let Some(heatmap) = p0.value_of("sample-heatmap") {
+        run_opts = run_opts.with_sample_heatmap(heatmap);
+    }
+    if let Some(sampler) = p0.value_of("sampler") {
+        render_opts = render_opts.with_sampler(sampler.parse().unwrap());
+    }
+    if let Some(filter) = p0.value_of("filter") {
+        render_opts = render_opts.with_filter(filter.parse().unwrap());
+    }
+    if let Some(seed) = p0.value_of("seed") {
+        render_opts = render_opts.with_seed(seed.parse().unwrap());
+    }
+    if let Some(aovs) = p0.value_of("aov") {
+        render_opts = render_opts.with_aovs(aovs.parse().unwrap());
+    }
+    if let Some(file) = p0.value_of("checkpoint") {
+        let interval = p0
+            .value_of("checkpoint-interval")
+            .map(|v| CheckpointOpts::parse_interval(v).unwrap())
+            .unwrap_or(DEFAULT_CHECKPOINT_INTERVAL);
+        run_opts =
+            run_opts.with_checkpoint(CheckpointOpts::new(file, interval, p0.is_present("resume")));
+    }
+    if p0.is_present("denoise") {
+        let strength = p0
+            .value_of("denoise")
+            .map(|v| v.parse().unwrap())
+            .unwrap_or(1.0);
+        run_opts = run_opts.with_denoiser(Denoiser::new(strength));
+    }
+    if let Some(crop) = p0.value_of("crop") {
+        render_opts = render_opts.with_crop(Some(crop.parse().unwrap()));
+    }
+    if p0.is_present("border") {
+        run_opts = run_opts.with_border();
+    }
+    if let Some(post) = p0.value_of("post") {
+        run_opts = run_opts.with_post(post.parse().unwrap());
+    }
+    if p0.is_present("no-post") {
+        run_opts = run_opts.with_bypass_post();
+    }
+    if let Some(frames) = p0.value_of("frames") {
+        run_opts = run_opts.with_frames(frames.parse().unwrap());
+    }
+    if let Some(frames) = p0.value_of("turntable") {
+        run_opts = run_opts.with_turntable(Turntable::new(frames.parse().unwrap()));
+    }
+    if let Some(fps) = p0.value_of("fps") {
+        run_opts = run_opts.with_fps(fps.parse().unwrap());
+    }
+    if p0.is_present("separate-aovs") {
+        run_opts = run_opts.with_separate_aovs();
+    }
+    run_opts = run_opts.with_render_opts(render_opts);
+
+    if let Some(layout) = p0.value_of("stereo") {
+        let ipd = p0.value_of("ipd").map(|v| v.parse().unwrap());
+        let convergence = p0.value_of("convergence").map(|v| v.parse().unwrap());
+        run_opts = run_opts.with_stereo(layout.parse().unwrap(), ipd, convergence);
+    }
+
+    raytracer::run(run_opts)
+}
+
+/// The default width of the benchmark renders.
+const BENCH_WIDTH: u32 = 256;
 
-    app::run(run_opts);
+fn prepare_bench(p0: &ArgMatches) -> Result<(), Error> {
+    let benchmarks = match p0.value_of("scenes") {
+        Some(scenes) => scenes.split(',').map(|s| s.parse().unwrap()).collect(),
+        None => Benchmark::ALL.to_vec(),
+    };
+    let samples = p0
+        .value_of("samples")
+        .map(|v| v.parse().unwrap())
+        .unwrap_or(4);
+    let width = p0
+        .value_of("width")
+        .map(|v| v.parse().unwrap())
+        .unwrap_or(BENCH_WIDTH);
+
+    commands::bench(
+        &benchmarks,
+        width,
+        &RenderOpts::new().with_samples(samples),
+        p0.value_of("output"),
+    )
+}
+
+#[doc(hidden)]
+fn is_positive_number(v: String) -> Result<(), String> {
+    match v.parse::<f32>() {
+        Ok(n) if n > 0.0 && n.is_finite() => Ok(()),
+        _ => Err(format!("{} is not a positive number", v)),
+    }
+}
+
+#[doc(hidden)]
+fn is_positive_integer(v: String) -> Result<(), String> {
+    match v.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(format!("{} is not a positive integer", v)),
+    }
 }
 
 #[doc(hidden)]
-fn configure_logger() {
+fn configure_logger(level: LevelFilter, mode: TerminalMode) {
     CombinedLogger::init(vec![TermLogger::new(
-        LevelFilter::Trace,
+        level,
         Config::default(),
-        TerminalMode::Mixed,
+        mode,
         ColorChoice::Auto,
     )])
     .unwrap();
@@ -0,0 +1,117 @@
+use crate::math::{Ray, Rotation, Vec3};
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Returns a box larger than any scene, for shapes whose bounds are unknown. Its
+    /// coordinates are finite, so that it can be rotated and moved.
+    pub fn unbounded() -> Self {
+        const EXTENT: f32 = 1e30;
+        Self::new(
+            Vec3::new(-EXTENT, -EXTENT, -EXTENT),
+            Vec3::new(EXTENT, EXTENT, EXTENT),
+        )
+    }
+
+    /// Returns the [Aabb] of the sphere with the specified center and radius.
+    pub fn from_sphere(center: Vec3, radius: f32) -> Self {
+        let extent = Vec3::new(radius, radius, radius);
+        Self::new(center - extent, center + extent)
+    }
+
+    /// Returns the smallest [Aabb] that contains both boxes.
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self::new(self.min.inf(&other.min), self.max.sup(&other.max))
+    }
+
+    /// Returns the smallest [Aabb] that contains the points, or `None` if there are none.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self> {
+        points
+            .into_iter()
+            .map(|p| Self::new(p, p))
+            .reduce(|a, b| a.union(&b))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    /// Returns the [Aabb] of this box rotated around the origin.
+    pub fn rotated(&self, rotation: &Rotation) -> Self {
+        let corners = (0..8).map(|corner| {
+            let pick = |axis: usize| {
+                if corner & (1 << axis) == 0 {
+                    self.min[axis]
+                } else {
+                    self.max[axis]
+                }
+            };
+            rotation * Vec3::new(pick(0), pick(1), pick(2))
+        });
+        Self::from_points(corners).expect("a box has corners")
+    }
+
+    /// Returns a copy of this [Aabb] moved by the specified offset.
+    pub fn translated(&self, offset: Vec3) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+
+    /// Tests the [Ray] against this box using the slab method.
+    pub fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+
+        for axis in 0..3 {
+            let inv_d = 1.0 / ray.direction()[axis];
+            let mut t0 = (self.min[axis] - ray.origin()[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - ray.origin()[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hit() {
+        let aabb = Aabb::from_sphere(Vec3::new(0.0, 0.0, -5.0), 1.0);
+        let towards = Ray::new(Vec3::zeros(), Vec3::new(0.0, 0.0, -1.0));
+        let away = Ray::new(Vec3::zeros(), Vec3::new(0.0, 0.0, 1.0));
+        let aside = Ray::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        assert!(aabb.hit(&towards, 0.0, f32::MAX));
+        assert!(!aabb.hit(&away, 0.0, f32::MAX));
+        assert!(!aabb.hit(&aside, 0.0, f32::MAX));
+        assert!(!aabb.hit(&towards, 0.0, 3.0));
+    }
+
+    #[test]
+    fn rotated() {
+        let aabb = Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(2.0, 1.0, 1.0));
+        let rotation = Rotation::from_axis_angle(&Vec3::y_axis(), std::f32::consts::FRAC_PI_2);
+
+        let rotated = aabb.rotated(&rotation);
+        assert!((rotated.min - Vec3::new(0.0, 0.0, -2.0)).magnitude() < 1e-6);
+        assert!((rotated.max - Vec3::new(1.0, 1.0, 0.0)).magnitude() < 1e-6);
+    }
+}
@@ -0,0 +1,209 @@
+//! A bounding volume hierarchy, to find the items hit by a [Ray] without testing them all.
+use crate::math::{Aabb, Ray};
+use std::mem::size_of;
+
+/// The maximum number of items in a leaf.
+const MAX_LEAF_SIZE: usize = 2;
+
+/// The size of the traversal stack. The tree is balanced, so it is enough for any count.
+const STACK_SIZE: usize = 64;
+
+#[derive(Debug, Copy, Clone)]
+struct BvhNode {
+    bounds: Aabb,
+    /// For leaves, the index of their first item in [Bvh::items]. For interior nodes, the
+    /// index of their second child: the first one follows them.
+    offset: u32,
+    /// The number of items of leaves, or 0 for interior nodes.
+    count: u32,
+    /// The axis along which the children of interior nodes are split.
+    axis: u8,
+}
+
+/// A binary tree of [Aabb]s over items identified by their index.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    /// The indices of the items, ordered by leaf.
+    items: Vec<u32>,
+}
+
+impl Bvh {
+    /// Builds the hierarchy of the items with the bounds, by splitting them at the median of
+    /// their centers along their longest axis.
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            items: (0..bounds.len() as u32).collect(),
+        };
+        if !bounds.is_empty() {
+            bvh.build(bounds, 0, bounds.len());
+        }
+
+        bvh
+    }
+
+    /// Adds the node of the items from `start` to `end`, and returns its index.
+    fn build(&mut self, bounds: &[Aabb], start: usize, end: usize) -> u32 {
+        let items = &mut self.items[start..end];
+        let node_bounds = items
+            .iter()
+            .map(|i| bounds[*i as usize])
+            .reduce(|a, b| a.union(&b))
+            .expect("nodes have items");
+        let index = self.nodes.len() as u32;
+        self.nodes.push(BvhNode {
+            bounds: node_bounds,
+            offset: start as u32,
+            count: items.len() as u32,
+            axis: 0,
+        });
+        if items.len() <= MAX_LEAF_SIZE {
+            return index;
+        }
+
+        let centers = Aabb::from_points(items.iter().map(|i| bounds[*i as usize].center()))
+            .expect("nodes have items");
+        let axis = (centers.max - centers.min).imax();
+        let half = items.len() / 2;
+        items.select_nth_unstable_by(half, |a, b| {
+            bounds[*a as usize].center()[axis].total_cmp(&bounds[*b as usize].center()[axis])
+        });
+
+        self.build(bounds, start, start + half);
+        let second = self.build(bounds, start + half, end);
+        let node = &mut self.nodes[index as usize];
+        node.offset = second;
+        node.count = 0;
+        node.axis = axis as u8;
+
+        index
+    }
+
+    /// The number of items.
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns the bounds of all the items, or `None` if there are none.
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.nodes.first().map(|n| n.bounds)
+    }
+
+    /// Estimates the memory used by the hierarchy, in bytes.
+    pub fn memory_size(&self) -> usize {
+        size_of::<Self>()
+            + self.nodes.len() * size_of::<BvhNode>()
+            + self.items.len() * size_of::<u32>()
+    }
+
+    /// Visits the items whose bounds the [Ray] hits before `t_max`, the nearest nodes first.
+    /// `visit` returns the distance of the hit of the item, if any, so that the nodes behind
+    /// it are skipped. Returns the number of bounding boxes tested.
+    pub fn traverse(
+        &self,
+        ray: &Ray,
+        t_max: f32,
+        mut visit: impl FnMut(usize) -> Option<f32>,
+    ) -> u64 {
+        if self.nodes.is_empty() {
+            return 0;
+        }
+
+        let mut t_max = t_max;
+        let mut tests = 0;
+        let mut stack = [0u32; STACK_SIZE];
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let index = stack[len];
+            let node = &self.nodes[index as usize];
+            tests += 1;
+            if !node.bounds.hit(ray, 0.0, t_max) {
+                continue;
+            }
+
+            if node.count > 0 {
+                let start = node.offset as usize;
+                for item in &self.items[start..start + node.count as usize] {
+                    if let Some(t) = visit(*item as usize) {
+                        t_max = t_max.min(t);
+                    }
+                }
+            } else {
+                // pushes the far child first, to visit the near one first
+                let (first, second) = (index + 1, node.offset);
+                let (near, far) = if ray.direction()[node.axis as usize] < 0.0 {
+                    (second, first)
+                } else {
+                    (first, second)
+                };
+                stack[len] = far;
+                stack[len + 1] = near;
+                len += 2;
+            }
+        }
+
+        tests
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::random::Pcg32;
+    use crate::math::Vec3;
+
+    #[test]
+    fn traverse_finds_the_closest_item() {
+        let mut rng = Pcg32::new(7);
+        let mut point = || {
+            Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) * 20.0
+                - Vec3::new(10.0, 10.0, 10.0)
+        };
+        let boxes: Vec<Aabb> = (0..500).map(|_| Aabb::from_sphere(point(), 0.5)).collect();
+        let rays: Vec<Ray> = (0..200).map(|_| Ray::new(point() * 2.0, point())).collect();
+        let bvh = Bvh::new(&boxes);
+        assert_eq!(500, bvh.len());
+
+        // the distance to the box, found by bisection, stands for the distance to the item
+        let distance = |ray: &Ray, b: &Aabb| {
+            let (mut near, mut far) = (0.0, 100.0);
+            if !b.hit(ray, 0.0, far) {
+                return None;
+            }
+            for _ in 0..24 {
+                let mid = (near + far) / 2.0;
+                if b.hit(ray, 0.0, mid) {
+                    far = mid;
+                } else {
+                    near = mid;
+                }
+            }
+            Some(far)
+        };
+        let mut total_tests = 0;
+        for ray in &rays {
+            let expected = boxes
+                .iter()
+                .filter_map(|b| distance(ray, b))
+                .reduce(f32::min);
+
+            let mut closest: Option<f32> = None;
+            total_tests += bvh.traverse(ray, f32::MAX, |i| {
+                let t = distance(ray, &boxes[i])?;
+                if closest.is_none_or(|c| t < c) {
+                    closest = Some(t);
+                }
+                closest
+            });
+            assert_eq!(expected, closest);
+        }
+        assert!(
+            total_tests < 50 * rays.len() as u64,
+            "{} tests",
+            total_tests
+        );
+    }
+}
@@ -1,16 +1,37 @@
 use nalgebra::base::Vector3;
+use nalgebra::geometry::UnitQuaternion;
 
 pub type Vec3 = Vector3<f32>;
+pub type Rotation = UnitQuaternion<f32>;
+
+pub mod aabb;
+pub mod bvh;
+pub mod random;
+pub use aabb::Aabb;
+pub use bvh::Bvh;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Ray {
     origin: Vec3,
     direction: Vec3,
+    /// The time at which the ray was emitted, within the camera shutter interval.
+    time: f32,
 }
 
 impl Ray {
     pub fn new(origin: Vec3, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    /// Returns a copy of this [Ray] emitted at the specified time.
+    pub fn with_time(self, time: f32) -> Self {
+        let mut copy = self;
+        copy.time = time;
+        copy
     }
 
     /// Returns the point along the ray at the specified distance from the origin.
@@ -24,4 +45,7 @@ impl Ray {
     pub fn direction(&self) -> Vec3 {
         self.direction
     }
+    pub fn time(&self) -> f32 {
+        self.time
+    }
 }
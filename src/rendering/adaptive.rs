@@ -0,0 +1,189 @@
+//! Adaptive sampling: more samples for the pixels that have not converged.
+use crate::rendering::{Color, FrameBuffer, Pixel, PixelSize, RenderTarget, Sample};
+use nameof::name_of_type;
+use std::fmt::{Display, Formatter};
+
+/// The minimum number of samples of adaptive sampling: the variance of a single sample is
+/// 0, so every pixel would be considered converged after its first sample.
+const MIN_ADAPTIVE_SAMPLES: u32 = 2;
+
+/// Parameters of adaptive sampling.
+///
+/// Each pixel gets at least `min_samples` samples, and at least 2. Then, samples are added until the
+/// relative standard error of the pixel falls below `threshold`, or until the pixel
+/// has `max_samples` samples.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Adaptive {
+    pub min_samples: u32,
+    pub max_samples: u32,
+    pub threshold: f32,
+}
+
+impl Adaptive {
+    pub fn new(min_samples: u32, max_samples: u32, threshold: f32) -> Self {
+        let min_samples = min_samples.max(MIN_ADAPTIVE_SAMPLES);
+        Adaptive {
+            min_samples,
+            max_samples: max_samples.max(min_samples),
+            threshold,
+        }
+    }
+
+    /// Returns true if the [Sample] needs more samples.
+    pub(crate) fn needs_more_samples(&self, sample: &Sample) -> bool {
+        if sample.samples < self.min_samples {
+            return true;
+        }
+        if sample.samples >= self.max_samples {
+            return false;
+        }
+
+        sample.relative_error() > self.threshold as f64
+    }
+}
+
+/// The number of samples taken for each pixel of a render.
+#[derive(Debug, Clone)]
+pub struct SampleCounts {
+    size: PixelSize,
+    counts: Vec<u32>,
+}
+
+impl Display for SampleCounts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (min: {}, max: {}, average: {:.1})",
+            name_of_type!(SampleCounts),
+            self.min(),
+            self.max(),
+            self.average()
+        )
+    }
+}
+
+impl SampleCounts {
+    pub fn new(size: PixelSize) -> Self {
+        SampleCounts {
+            size,
+            counts: vec![0; (size.width * size.height) as usize],
+        }
+    }
+
+    pub fn set(&mut self, pixel: Pixel, count: u32) {
+        self.counts[(pixel.x + pixel.y * self.size.width) as usize] = count;
+    }
+
+    pub fn get(&self, pixel: Pixel) -> u32 {
+        self.counts[(pixel.x + pixel.y * self.size.width) as usize]
+    }
+
+    pub fn min(&self) -> u32 {
+        self.counts.iter().copied().min().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> u32 {
+        self.counts.iter().copied().max().unwrap_or_default()
+    }
+
+    pub fn average(&self) -> f64 {
+        if self.counts.is_empty() {
+            return 0.0;
+        }
+        self.counts.iter().map(|c| *c as f64).sum::<f64>() / self.counts.len() as f64
+    }
+
+    /// Renders the sample counts as a heatmap, from blue (fewest samples) to red (most samples).
+    pub fn heatmap(&self) -> FrameBuffer {
+        let mut fb = FrameBuffer::new(self.size.width, self.size.height);
+        let min = self.min() as f32;
+        let range = (self.max() as f32 - min).max(1.0);
+
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let pixel = Pixel::new(x, y);
+                let t = (self.get(pixel) as f32 - min) / range;
+                fb.set(pixel, heat_color(t));
+            }
+        }
+
+        fb
+    }
+}
+
+/// Maps a value in [0, 1] to a blue - cyan - green - yellow - red color ramp.
+pub(crate) fn heat_color(t: f32) -> Color {
+    const STOPS: [(f32, f32, f32); 5] = [
+        (0.0, 0.0, 1.0),
+        (0.0, 1.0, 1.0),
+        (0.0, 1.0, 0.0),
+        (1.0, 1.0, 0.0),
+        (1.0, 0.0, 0.0),
+    ];
+
+    let scaled = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let i = (scaled as usize).min(STOPS.len() - 2);
+    let f = scaled - i as f32;
+    let (a, b) = (STOPS[i], STOPS[i + 1]);
+    let lerp = |x: f32, y: f32| ((x + (y - x) * f) * 255.0).round() as u8;
+
+    Color::new(lerp(a.0, b.0), lerp(a.1, b.1), lerp(a.2, b.2))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rendering::hdr::linear;
+    use crate::rendering::{RenderOpts, BLACK, WHITE};
+    use crate::scene::Scene;
+
+    #[test]
+    fn welford_variance() {
+        let mut sample = Sample::default();
+        sample += linear(WHITE);
+        sample += linear(BLACK);
+        sample += linear(WHITE);
+        sample += linear(BLACK);
+
+        // luminances 1, 0, 1, 0: mean 0.5, sample variance 1/3
+        assert!((sample.variance() - 1.0 / 3.0).abs() < 1e-6);
+        assert!((sample.standard_error() - (1.0f64 / 12.0).sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn needs_more_samples() {
+        let adaptive = Adaptive::new(2, 8, 0.05);
+        let mut flat = Sample::default();
+        let mut noisy = Sample::default();
+
+        flat += linear(WHITE);
+        noisy += linear(WHITE);
+        assert!(adaptive.needs_more_samples(&flat));
+
+        flat += linear(WHITE);
+        noisy += linear(BLACK);
+        assert!(!adaptive.needs_more_samples(&flat));
+        assert!(adaptive.needs_more_samples(&noisy));
+
+        for _ in 0..6 {
+            noisy += linear(WHITE);
+        }
+        assert!(!adaptive.needs_more_samples(&noisy));
+    }
+
+    #[test]
+    fn noisy_pixels_get_more_samples() {
+        // a single sample has no variance: it must not stop the sampling
+        let adaptive = Adaptive::new(1, 16, 0.01);
+        assert_eq!(2, adaptive.min_samples);
+
+        let scene = Scene::new();
+        let opts = RenderOpts::new().with_adaptive(Some(adaptive));
+        let mut target = FrameBuffer::new(32, 18);
+        let counts = scene.render(&mut target, &opts, &|_| {});
+
+        // the flat background converges, the edges of the spheres do not
+        assert_eq!(2, counts.min());
+        assert!(counts.max() > 2, "{}", counts);
+    }
+}
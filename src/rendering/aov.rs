@@ -0,0 +1,460 @@
+//! Arbitrary output variables (AOVs): auxiliary images rendered along the beauty image,
+//! e.g for compositing or denoising.
+//!
+//! The renderer has no lighting model (surfaces show their diffuse color), so the whole
+//! beauty image is considered direct lighting, and the indirect AOV is black.
+use crate::math::random::hash;
+use crate::math::Vec3;
+use crate::rendering::hdr::linear_to_srgb;
+use crate::rendering::{Color, FrameBuffer, HdrBuffer, Pixel, PixelRect, PixelSize, RenderTarget};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// An arbitrary output variable.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Aov {
+    /// The distance to the camera along its view axis (or to its center for panoramic
+    /// projections). Infinite for the background.
+    Depth,
+    /// The world-space shading normal.
+    Normal,
+    /// The shading normal in camera space (X right, Y up, Z toward the camera).
+    CameraNormal,
+    /// The linear diffuse color of the surface.
+    Albedo,
+    /// The world-space position of the surface.
+    Position,
+    /// The texture coordinates of the surface.
+    Uv,
+    /// The identifier of the entity, as a cryptomatte-style hash.
+    ObjectId,
+    /// The identifier of the material, as a cryptomatte-style hash.
+    MaterialId,
+    /// The light that reaches the camera directly from the surface (or the background).
+    Direct,
+    /// The light that reaches the camera after bouncing around the scene.
+    Indirect,
+}
+
+/// How the samples of a pixel are combined.
+enum Accumulation {
+    Average,
+    Min,
+    First,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 10] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::CameraNormal,
+        Aov::Albedo,
+        Aov::Position,
+        Aov::Uv,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Direct,
+        Aov::Indirect,
+    ];
+
+    /// The name of the AOV, used in file names and EXR layers.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::CameraNormal => "camera-normal",
+            Aov::Albedo => "albedo",
+            Aov::Position => "position",
+            Aov::Uv => "uv",
+            Aov::ObjectId => "object-id",
+            Aov::MaterialId => "material-id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+        }
+    }
+
+    /// The names of the channels of the AOV.
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal | Aov::CameraNormal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Albedo | Aov::Direct | Aov::Indirect => &["R", "G", "B"],
+            Aov::Uv => &["U", "V"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+        }
+    }
+
+    fn accumulation(&self) -> Accumulation {
+        match self {
+            // the closest surface, as blending depths creates surfaces that do not exist
+            Aov::Depth => Accumulation::Min,
+            // identifiers cannot be blended
+            Aov::ObjectId | Aov::MaterialId => Accumulation::First,
+            _ => Accumulation::Average,
+        }
+    }
+
+    fn bit(&self) -> u16 {
+        1 << Aov::ALL.iter().position(|a| a == self).unwrap()
+    }
+}
+
+impl Display for Aov {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        Aov::ALL
+            .iter()
+            .copied()
+            .find(|a| a.name() == s || a.name().replace('-', "_") == s)
+            .ok_or_else(|| format!("unknown AOV: {}", s))
+    }
+}
+
+/// A set of [Aov]s.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct AovSet(u16);
+
+impl AovSet {
+    pub fn insert(&mut self, aov: Aov) {
+        self.0 |= aov.bit();
+    }
+
+    pub fn contains(&self, aov: Aov) -> bool {
+        self.0 & aov.bit() != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the [Aov]s of the set, in the order of [Aov::ALL].
+    pub fn iter(&self) -> impl Iterator<Item = Aov> + '_ {
+        Aov::ALL.iter().copied().filter(move |a| self.contains(*a))
+    }
+}
+
+impl Display for AovSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<&str> = self.iter().map(|a| a.name()).collect();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+impl FromStr for AovSet {
+    type Err = String;
+
+    /// Parses a comma-separated list of AOV names, or `all`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = AovSet::default();
+        for name in s.split(',') {
+            if name.trim().eq_ignore_ascii_case("all") {
+                Aov::ALL.iter().for_each(|a| set.insert(*a));
+            } else {
+                set.insert(name.parse()?);
+            }
+        }
+        Ok(set)
+    }
+}
+
+/// Converts an identifier into a float whose bits are a hash of the identifier, as
+/// cryptomatte does. The exponent is adjusted to avoid denormals, infinities and NaNs.
+pub fn id_to_float(id: u32) -> f32 {
+    let mut bits = hash(id);
+    let exponent = (bits >> 23) & 0xff;
+    if exponent == 0 || exponent == 0xff {
+        bits ^= 1 << 23;
+    }
+    f32::from_bits(bits)
+}
+
+/// The values of all the [Aov]s for a single camera sample.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AovSample {
+    pub depth: f32,
+    pub normal: Vec3,
+    pub camera_normal: Vec3,
+    pub albedo: [f32; 3],
+    pub position: Vec3,
+    pub uv: (f32, f32),
+    pub object_id: u32,
+    pub material_id: u32,
+    pub direct: [f32; 3],
+    pub indirect: [f32; 3],
+}
+
+impl AovSample {
+    /// The values of a sample that hits the background, with the linear background color.
+    pub fn background(color: [f32; 3]) -> Self {
+        AovSample {
+            depth: f32::INFINITY,
+            normal: Vec3::zeros(),
+            camera_normal: Vec3::zeros(),
+            albedo: [0.0; 3],
+            position: Vec3::zeros(),
+            uv: (0.0, 0.0),
+            object_id: 0,
+            material_id: 0,
+            direct: color,
+            indirect: [0.0; 3],
+        }
+    }
+
+    fn values(&self, aov: Aov) -> [f32; 3] {
+        let vec = |v: Vec3| [v.x, v.y, v.z];
+        let id = |id: u32| if id == 0 { 0.0 } else { id_to_float(id) };
+        match aov {
+            Aov::Depth => [self.depth, 0.0, 0.0],
+            Aov::Normal => vec(self.normal),
+            Aov::CameraNormal => vec(self.camera_normal),
+            Aov::Albedo => self.albedo,
+            Aov::Position => vec(self.position),
+            Aov::Uv => [self.uv.0, self.uv.1, 0.0],
+            Aov::ObjectId => [id(self.object_id), 0.0, 0.0],
+            Aov::MaterialId => [id(self.material_id), 0.0, 0.0],
+            Aov::Direct => self.direct,
+            Aov::Indirect => self.indirect,
+        }
+    }
+}
+
+/// Accumulates the [AovSample]s of each pixel.
+#[derive(Debug, Clone)]
+pub struct AovBuffers {
+    size: PixelSize,
+    layers: Vec<(Aov, Vec<f32>)>,
+    counts: Vec<u32>,
+}
+
+impl AovBuffers {
+    pub fn new(size: PixelSize, aovs: AovSet) -> Self {
+        let pixel_count = (size.width * size.height) as usize;
+        let layers = aovs
+            .iter()
+            .map(|aov| (aov, vec![0.0; pixel_count * aov.channels().len()]))
+            .collect();
+
+        AovBuffers {
+            size,
+            layers,
+            counts: vec![0; pixel_count],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.iter_mut().for_each(|c| *c = 0);
+        for (_, data) in self.layers.iter_mut() {
+            data.iter_mut().for_each(|v| *v = 0.0);
+        }
+    }
+
+    /// Adds the sample to the [Pixel] it was taken for.
+    pub fn add(&mut self, pixel: Pixel, sample: &AovSample) {
+        let index = (pixel.x + pixel.y * self.size.width) as usize;
+        self.counts[index] += 1;
+        let n = self.counts[index];
+
+        for (aov, data) in self.layers.iter_mut() {
+            let channels = aov.channels().len();
+            let values = sample.values(*aov);
+            for (c, v) in values.iter().take(channels).enumerate() {
+                let current = &mut data[index * channels + c];
+                *current = match aov.accumulation() {
+                    Accumulation::Average => *current + (v - *current) / n as f32,
+                    Accumulation::Min if n > 1 => current.min(*v),
+                    Accumulation::First if n > 1 => *current,
+                    _ => *v,
+                };
+            }
+        }
+    }
+
+    /// Gets the number of samples of each pixel, and the values of each layer, e.g to save them.
+    pub fn data(&self) -> (&[u32], Vec<&[f32]>) {
+        (
+            &self.counts,
+            self.layers
+                .iter()
+                .map(|(_, data)| data.as_slice())
+                .collect(),
+        )
+    }
+
+    /// Gets the number of samples of each pixel, and the values of each layer, e.g to restore them.
+    pub fn data_mut(&mut self) -> (&mut [u32], Vec<&mut [f32]>) {
+        (
+            &mut self.counts,
+            self.layers
+                .iter_mut()
+                .map(|(_, data)| data.as_mut_slice())
+                .collect(),
+        )
+    }
+
+    /// Returns the accumulated images, in the order of [Aov::ALL].
+    pub fn images(&self) -> Vec<AovImage> {
+        self.layers
+            .iter()
+            .map(|(aov, data)| AovImage {
+                aov: *aov,
+                size: self.size,
+                data: data.clone(),
+            })
+            .collect()
+    }
+}
+
+/// The image of an [Aov], with interleaved channels.
+#[derive(Debug, Clone)]
+pub struct AovImage {
+    pub aov: Aov,
+    pub size: PixelSize,
+    pub data: Vec<f32>,
+}
+
+impl AovImage {
+    fn pixel(&self, pixel: Pixel) -> [f32; 3] {
+        let channels = self.aov.channels().len();
+        let index = (pixel.x + pixel.y * self.size.width) as usize * channels;
+        let mut rgb = [0.0; 3];
+        rgb[..channels].copy_from_slice(&self.data[index..index + channels]);
+        rgb
+    }
+
+    /// Returns a copy of the values within the [PixelRect].
+    pub fn crop(&self, rect: PixelRect) -> AovImage {
+        let channels = self.aov.channels().len();
+        let mut data = Vec::with_capacity((rect.size.width * rect.size.height) as usize * channels);
+        for y in rect.origin.y..rect.origin.y + rect.size.height {
+            let start = (rect.origin.x + y * self.size.width) as usize * channels;
+            data.extend_from_slice(&self.data[start..start + rect.size.width as usize * channels]);
+        }
+
+        AovImage {
+            aov: self.aov,
+            size: rect.size,
+            data,
+        }
+    }
+
+    /// Copies the raw values into a [HdrBuffer]: single channels are written as gray
+    /// levels, and two channels as red and green.
+    pub fn to_hdr_buffer(&self) -> HdrBuffer {
+        let mut hdr = HdrBuffer::new(self.size.width, self.size.height);
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let pixel = Pixel::new(x, y);
+                let rgb = self.pixel(pixel);
+                let rgb = if self.aov.channels().len() == 1 {
+                    [rgb[0]; 3]
+                } else {
+                    rgb
+                };
+                hdr.set(pixel, rgb);
+            }
+        }
+        hdr
+    }
+
+    /// Maps the values to displayable colors: depths are normalized (near is white),
+    /// normals are remapped from [-1, 1], positions are wrapped every unit and identifiers
+    /// get a random color.
+    pub fn visualize(&self) -> FrameBuffer {
+        let mut fb = FrameBuffer::new(self.size.width, self.size.height);
+        let max_depth = self
+            .data
+            .iter()
+            .copied()
+            .filter(|d| d.is_finite())
+            .fold(0.0f32, f32::max)
+            .max(f32::EPSILON);
+
+        let byte = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let srgb = |v: f32| byte(linear_to_srgb(v));
+
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let pixel = Pixel::new(x, y);
+                let v = self.pixel(pixel);
+                let color = match self.aov {
+                    Aov::Depth if v[0].is_finite() => {
+                        let d = byte(1.0 - v[0] / max_depth);
+                        Color::new(d, d, d)
+                    }
+                    Aov::Depth => Color::new(0, 0, 0),
+                    Aov::Normal | Aov::CameraNormal => Color::new(
+                        byte(v[0] * 0.5 + 0.5),
+                        byte(v[1] * 0.5 + 0.5),
+                        byte(v[2] * 0.5 + 0.5),
+                    ),
+                    Aov::Position => Color::new(
+                        byte(v[0].rem_euclid(1.0)),
+                        byte(v[1].rem_euclid(1.0)),
+                        byte(v[2].rem_euclid(1.0)),
+                    ),
+                    Aov::Uv => Color::new(byte(v[0]), byte(v[1]), 0),
+                    Aov::ObjectId | Aov::MaterialId if v[0] == 0.0 => Color::new(0, 0, 0),
+                    Aov::ObjectId | Aov::MaterialId => {
+                        let h = hash(v[0].to_bits());
+                        Color::new(h as u8, (h >> 8) as u8, (h >> 16) as u8)
+                    }
+                    Aov::Albedo | Aov::Direct | Aov::Indirect => {
+                        Color::new(srgb(v[0]), srgb(v[1]), srgb(v[2]))
+                    }
+                };
+                fb.set(pixel, color);
+            }
+        }
+
+        fb
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_set() {
+        let set: AovSet = "depth, object_id".parse().unwrap();
+
+        assert!(set.contains(Aov::Depth));
+        assert!(set.contains(Aov::ObjectId));
+        assert!(!set.contains(Aov::Normal));
+        assert_eq!("depth,object-id", set.to_string());
+        assert_eq!(10, "all".parse::<AovSet>().unwrap().iter().count());
+        assert!("beauty".parse::<AovSet>().is_err());
+    }
+
+    #[test]
+    fn accumulation() {
+        let mut set = AovSet::default();
+        set.insert(Aov::Depth);
+        set.insert(Aov::Albedo);
+        set.insert(Aov::ObjectId);
+        let mut buffers = AovBuffers::new(PixelSize::new(1, 1), set);
+
+        let mut near = AovSample::background([0.0; 3]);
+        near.depth = 2.0;
+        near.albedo = [1.0, 0.0, 0.0];
+        near.object_id = 7;
+        let far = AovSample::background([0.0; 3]);
+
+        buffers.add(Pixel::new(0, 0), &far);
+        buffers.add(Pixel::new(0, 0), &near);
+
+        let images = buffers.images();
+        assert_eq!(vec![2.0], images[0].data);
+        assert_eq!(vec![0.5, 0.0, 0.0], images[1].data);
+        assert_eq!(vec![0.0], images[2].data);
+        assert!(id_to_float(7).is_normal());
+    }
+}
@@ -0,0 +1,311 @@
+//! Animated image files: GIF, with a palette shared by the frames, and APNG.
+use crate::error::Error;
+use crate::rendering::backends::Backend;
+use crate::rendering::{FrameBuffer, RenderTarget};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::error::{EncodingError, ImageFormatHint};
+use image::imageops::dither;
+use image::{Delay, Frame, ImageError, RgbaImage};
+use nameof::name_of_type;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// The number of colors of the GIF palette.
+const GIF_COLORS: usize = 256;
+
+/// The sampling factor of the palette quantization, from 1 (every pixel, slowest) to 30.
+const GIF_QUANTIZATION_SAMPLING: i32 = 10;
+
+/// The maximum number of pixels, over all the frames, the GIF palette is trained on.
+const GIF_TRAINING_PIXELS: usize = 1 << 20;
+
+/// An animated image file format.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AnimationFormat {
+    /// A GIF, where the frames are dithered (Floyd-Steinberg) with a palette of 256 colors
+    /// shared by all of them.
+    Gif,
+    /// An animated PNG, with 8-bit sRGB frames.
+    Apng,
+}
+
+impl AnimationFormat {
+    /// Guesses the format from the extension of the file: `.gif` or `.apng`.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let ext = Path::new(path).extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "gif" => Some(AnimationFormat::Gif),
+            "apng" => Some(AnimationFormat::Apng),
+            _ => None,
+        }
+    }
+
+    fn image_format(&self) -> image::ImageFormat {
+        match self {
+            AnimationFormat::Gif => image::ImageFormat::Gif,
+            AnimationFormat::Apng => image::ImageFormat::Png,
+        }
+    }
+}
+
+impl Display for AnimationFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnimationFormat::Gif => write!(f, "gif"),
+            AnimationFormat::Apng => write!(f, "apng"),
+        }
+    }
+}
+
+/// Writes sequences of frames into an animated image file, which loops forever.
+#[derive(Debug)]
+pub struct AnimationBackend<'a> {
+    filename: &'a str,
+    format: AnimationFormat,
+    /// the number of frames per second
+    fps: f32,
+}
+
+impl<'a> Display for AnimationBackend<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} <{}> ({}, {} fps)",
+            name_of_type!(AnimationBackend),
+            self.filename,
+            self.format,
+            self.fps
+        )
+    }
+}
+
+impl<'a> AnimationBackend<'a> {
+    pub const DEFAULT_FPS: f32 = 24.0;
+
+    /// Creates a backend for the file, or returns `None` if its extension is not the one of
+    /// an [AnimationFormat].
+    pub fn new(filename: &'a str) -> Option<Self> {
+        Some(AnimationBackend {
+            filename,
+            format: AnimationFormat::from_path(filename)?,
+            fps: Self::DEFAULT_FPS,
+        })
+    }
+
+    pub fn with_fps(self, fps: f32) -> Self {
+        let mut s = self;
+        s.fps = fps;
+        s
+    }
+
+    /// Writes the frames, which must have the same size.
+    pub fn present_frames(&self, frames: &[FrameBuffer]) -> Result<(), Error> {
+        let size = frames.first().map(|f| f.size());
+        if size.is_none() || frames.iter().any(|f| Some(f.size()) != size) {
+            return Err(Error::InvalidOption(String::from(
+                "an animation needs frames, of the same size",
+            )));
+        }
+
+        let io = Error::io(self.filename);
+        let mut stream = BufWriter::new(fs::File::create(self.filename).map_err(&io)?);
+        match self.format {
+            AnimationFormat::Gif => write_gif(&mut stream, frames, self.fps),
+            AnimationFormat::Apng => write_apng(&mut stream, frames, self.fps),
+        }
+        .map_err(|e| {
+            ImageError::Encoding(EncodingError::new(
+                ImageFormatHint::Exact(self.format.image_format()),
+                e,
+            ))
+        })
+        .map_err(Error::encoding(self.filename))?;
+        stream.flush().map_err(&io)
+    }
+}
+
+impl<'a> Backend for AnimationBackend<'a> {
+    /// Writes a single frame.
+    fn present(&self, buf: &dyn RenderTarget) -> Result<(), Error> {
+        let size = buf.size();
+        let mut fb = FrameBuffer::try_new(size.width, size.height)?;
+        fb.blit_scaled(buf);
+        self.present_frames(&[fb])
+    }
+}
+
+type EncodeResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+fn to_rgba(fb: &FrameBuffer) -> RgbaImage {
+    let size = fb.size();
+    let rgba = fb
+        .bytes()
+        .chunks_exact(3)
+        .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+        .collect();
+    RgbaImage::from_raw(size.width, size.height, rgba).expect("the buffer matches the size")
+}
+
+/// Writes the frames as a GIF. The frames are dithered with a palette computed from all of
+/// them, so that the colors of static parts do not flicker, but long animations only train it
+/// on a subsample of their pixels, see [training_pixels].
+fn write_gif(w: &mut dyn Write, frames: &[FrameBuffer], fps: f32) -> EncodeResult {
+    let images: Vec<RgbaImage> = frames.iter().map(to_rgba).collect();
+    let pixels = training_pixels(&images, GIF_TRAINING_PIXELS);
+    let palette = color_quant::NeuQuant::new(GIF_QUANTIZATION_SAMPLING, GIF_COLORS, &pixels);
+
+    // the encoder quantizes each frame again, which keeps nearly all the colors of the palette
+    // as the dithered frames have no others
+    let mut encoder = GifEncoder::new_with_speed(w, GIF_QUANTIZATION_SAMPLING);
+    encoder.set_repeat(Repeat::Infinite)?;
+    // GIF delays are in hundredths of a second, and most viewers slow down delays below 2
+    let hundredths = ((100.0 / fps).round() as u32).max(2);
+    let delay = Delay::from_numer_denom_ms(hundredths * 10, 1);
+    for mut image in images {
+        dither(&mut image, &palette);
+        encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))?;
+    }
+
+    Ok(())
+}
+
+/// Returns the RGBA bytes of at most about `max_pixels` pixels evenly spread over the
+/// images. The stride between them is coprime with the width, so that they do not all
+/// fall in the same columns.
+fn training_pixels(images: &[RgbaImage], max_pixels: usize) -> Vec<u8> {
+    let width = images.first().map_or(1, |i| i.width() as usize);
+    let total: usize = images.iter().map(|i| i.as_raw().len() / 4).sum();
+    let mut stride = ((total + max_pixels - 1) / max_pixels.max(1)).max(1);
+    while stride > 1 && gcd(stride, width) != 1 {
+        stride += 1;
+    }
+
+    let pixels = images.iter().flat_map(|i| i.as_raw().chunks_exact(4));
+    pixels.step_by(stride).flatten().copied().collect()
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Writes the frames as an APNG: the first frame is the default image of the PNG, which
+/// viewers without APNG support display.
+fn write_apng(w: &mut dyn Write, frames: &[FrameBuffer], fps: f32) -> EncodeResult {
+    let size = frames[0].size();
+    let mut encoder = png::Encoder::new(w, size.width, size.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+    // loops forever
+    encoder.set_animated(frames.len() as u32, 0)?;
+    let (numerator, denominator) = apng_delay(fps);
+    encoder.set_frame_delay(numerator, denominator)?;
+
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        writer.write_image_data(frame.bytes())?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Returns the delay between frames as a fraction of a second, exact to the millisecond
+/// frame rate, e.g 1/25 at 25 fps and 100/2997 at 29.97 fps.
+fn apng_delay(fps: f32) -> (u16, u16) {
+    let millis = ((fps * 1000.0).round() as usize).max(1);
+    let divisor = gcd(1000, millis);
+    let (numerator, denominator) = (1000 / divisor, millis / divisor);
+    let max = u16::MAX as usize;
+    if denominator <= max {
+        (numerator as u16, denominator as u16)
+    } else {
+        (
+            ((numerator * max + denominator / 2) / denominator).max(1) as u16,
+            u16::MAX,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rendering::{Pixel, BLUE, RED};
+    use image::AnimationDecoder;
+
+    fn frames() -> Vec<FrameBuffer> {
+        [RED, BLUE]
+            .iter()
+            .map(|c| {
+                let mut fb = FrameBuffer::new(8, 4);
+                fb.clear(*c);
+                fb.set(Pixel::new(0, 0), RED);
+                fb
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gif_roundtrip() {
+        let mut gif = Vec::new();
+        write_gif(&mut gif, &frames(), 10.0).unwrap();
+
+        let decoded = image::codecs::gif::GifDecoder::new(&gif[..])
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(2, decoded.len());
+        assert_eq!((100, 1), decoded[0].delay().numer_denom_ms());
+        assert_eq!(
+            &image::Rgba([0, 0, 255, 255]),
+            decoded[1].buffer().get_pixel(4, 2)
+        );
+    }
+
+    #[test]
+    fn gif_palettes_are_trained_on_a_subsample() {
+        let images: Vec<RgbaImage> = frames().iter().map(to_rgba).collect();
+        assert_eq!(2 * 8 * 4 * 4, training_pixels(&images, 1000).len());
+
+        // every 3rd pixel, as 2 is not coprime with the width
+        let pixels = training_pixels(&images, 32);
+        assert_eq!(22 * 4, pixels.len());
+        assert_eq!(&[0, 0, 255, 255], &pixels[pixels.len() - 4..]);
+    }
+
+    #[test]
+    fn apng_roundtrip() {
+        let mut apng = Vec::new();
+        write_apng(&mut apng, &frames(), 29.97).unwrap();
+
+        // the default image is the first frame
+        let image = image::load_from_memory(&apng).unwrap().to_rgb8();
+        assert_eq!(&image::Rgb([255, 0, 0]), image.get_pixel(4, 2));
+
+        let mut reader = png::Decoder::new(&apng[..]).read_info().unwrap();
+        assert_eq!(2, reader.info().animation_control().unwrap().num_frames);
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        for _ in 0..2 {
+            reader.next_frame(&mut pixels).unwrap();
+            let control = reader.info().frame_control().unwrap();
+            assert_eq!((100, 2997), (control.delay_num, control.delay_den));
+        }
+        let offset = 3 * (2 * 8 + 4);
+        assert_eq!(&[0, 0, 255], &pixels[offset..offset + 3]);
+        assert_eq!(&[255, 0, 0], &pixels[..3]);
+    }
+
+    #[test]
+    fn apng_delays() {
+        assert_eq!((1, 25), apng_delay(25.0));
+        assert_eq!((1, 60), apng_delay(60.0));
+        assert_eq!((2, 1), apng_delay(0.5));
+        assert_eq!((655, 65535), apng_delay(99.999));
+    }
+}
@@ -0,0 +1,176 @@
+//! A minimal OpenEXR encoder: single-part, scanline, uncompressed, 32-bit float channels.
+//!
+//! Channels can be grouped into layers with the `layer.channel` naming convention
+//! (e.g `normal.X`), which compositing applications display as separate passes.
+use crate::rendering::PixelSize;
+use std::io::{self, Write};
+
+/// A channel of an OpenEXR image, with one value per pixel, row by row from the top.
+#[derive(Debug, Clone)]
+pub struct ExrChannel {
+    pub name: String,
+    pub data: Vec<f32>,
+}
+
+impl ExrChannel {
+    /// Splits interleaved values into one [ExrChannel] per name, e.g `R`, `G`, `B`.
+    /// The names are prefixed by `layer.` if a layer is specified.
+    pub fn split(layer: Option<&str>, names: &[&str], interleaved: &[f32]) -> Vec<ExrChannel> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let name = match layer {
+                    Some(layer) => format!("{}.{}", layer, name),
+                    None => String::from(*name),
+                };
+                let data = interleaved
+                    .iter()
+                    .skip(i)
+                    .step_by(names.len())
+                    .copied()
+                    .collect();
+                ExrChannel { name, data }
+            })
+            .collect()
+    }
+}
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const VERSION: u32 = 2;
+const PIXEL_TYPE_FLOAT: i32 = 2;
+
+/// Writes the channels as an OpenEXR image.
+pub fn write_exr(w: &mut dyn Write, size: PixelSize, channels: &[ExrChannel]) -> io::Result<()> {
+    let pixel_count = (size.width * size.height) as usize;
+    if let Some(c) = channels.iter().find(|c| c.data.len() != pixel_count) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "channel {} has {} values instead of {}",
+                c.name,
+                c.data.len(),
+                pixel_count
+            ),
+        ));
+    }
+
+    // channels are stored in alphabetical order
+    let mut channels: Vec<&ExrChannel> = channels.iter().collect();
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+
+    let mut chlist = Vec::new();
+    for c in channels.iter() {
+        chlist.extend_from_slice(c.name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear + reserved
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+    }
+    chlist.push(0);
+    attribute(&mut header, "channels", "chlist", &chlist);
+
+    attribute(&mut header, "compression", "compression", &[0]);
+
+    let mut window = Vec::new();
+    for v in [0, 0, size.width as i32 - 1, size.height as i32 - 1].iter() {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // one chunk per scanline: y, size, then the values of each channel
+    let line_size = size.width as usize * channels.len() * 4;
+    let chunk_size = 8 + line_size;
+    let first_chunk = header.len() + 8 * size.height as usize;
+
+    w.write_all(&header)?;
+    for y in 0..size.height as usize {
+        w.write_all(&((first_chunk + y * chunk_size) as u64).to_le_bytes())?;
+    }
+
+    let mut line = Vec::with_capacity(line_size);
+    for y in 0..size.height as usize {
+        line.clear();
+        let start = y * size.width as usize;
+        for c in channels.iter() {
+            for v in &c.data[start..start + size.width as usize] {
+                line.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+
+        w.write_all(&(y as i32).to_le_bytes())?;
+        w.write_all(&(line_size as i32).to_le_bytes())?;
+        w.write_all(&line)?;
+    }
+
+    Ok(())
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        let size = PixelSize::new(2, 3);
+        let channels = ExrChannel::split(None, &["R", "G", "B"], &[0.5; 18]);
+        let mut bytes = Vec::new();
+
+        write_exr(&mut bytes, size, &channels).unwrap();
+
+        assert_eq!(&MAGIC, &bytes[0..4]);
+
+        // the offset table points to the first chunk, right after the table
+        let chunk_size = 8 + 2 * 3 * 4;
+        let first_chunk = bytes.len() - 3 * chunk_size;
+        let table = first_chunk - 3 * 8;
+        let mut offset = [0; 8];
+        offset.copy_from_slice(&bytes[table..table + 8]);
+        assert_eq!(first_chunk as u64, u64::from_le_bytes(offset));
+
+        // the last chunk is the last scanline, and ends the file
+        let last = bytes.len() - chunk_size;
+        assert_eq!(2i32.to_le_bytes(), bytes[last..last + 4]);
+        assert_eq!(0.5f32.to_le_bytes(), bytes[bytes.len() - 4..]);
+    }
+
+    #[test]
+    fn split_names_layers() {
+        let channels = ExrChannel::split(Some("normal"), &["X", "Y"], &[1.0, 2.0, 3.0, 4.0]);
+
+        assert_eq!("normal.X", channels[0].name);
+        assert_eq!(vec![1.0, 3.0], channels[0].data);
+        assert_eq!(vec![2.0, 4.0], channels[1].data);
+    }
+}
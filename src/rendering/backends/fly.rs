@@ -0,0 +1,94 @@
+//! Fly-through camera controls for the [LiveWindow].
+use crate::math::Vec3;
+use crate::rendering::backends::LiveWindow;
+use crate::rendering::Camera;
+use minifb::Key;
+
+/// Moves a [Camera] from the keyboard and mouse input of a [LiveWindow].
+///
+/// - `W`, `A`, `S`, `D` move forward, left, backward and right,
+/// - `Q` and `E` move down and up,
+/// - dragging with the left mouse button (or the arrow keys) looks around,
+/// - holding `Shift` moves faster.
+#[derive(Debug)]
+pub struct FlyController {
+    /// The movement speed, in scene units per second.
+    pub speed: f32,
+    /// The rotation speed of mouse drags, in degrees per pixel.
+    pub sensitivity: f32,
+    last_mouse: Option<(f32, f32)>,
+}
+
+impl Default for FlyController {
+    fn default() -> Self {
+        FlyController {
+            speed: 2.0,
+            sensitivity: 0.2,
+            last_mouse: None,
+        }
+    }
+}
+
+impl FlyController {
+    /// The rotation speed of the arrow keys, in degrees per second.
+    const KEY_TURN_SPEED: f32 = 60.0;
+    /// The speed multiplier when `Shift` is held.
+    const FAST_FACTOR: f32 = 4.0;
+    /// The pitch is clamped to avoid flipping over the poles.
+    const MAX_PITCH: f32 = 89.0;
+
+    /// Applies the input of the last `dt` seconds to the [Camera], and returns true
+    /// if the camera has moved.
+    pub fn update(&mut self, window: &LiveWindow, camera: &mut Camera, dt: f32) -> bool {
+        let key = |k: Key| if window.is_key_down(k) { 1.0 } else { 0.0 };
+        let ctrl = window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl);
+        let fast = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+
+        // Ctrl is reserved for shortcuts, e.g Ctrl+S
+        let (forward, right, up) = if ctrl {
+            (0.0, 0.0, 0.0)
+        } else {
+            (
+                key(Key::W) - key(Key::S),
+                key(Key::D) - key(Key::A),
+                key(Key::E) - key(Key::Q),
+            )
+        };
+
+        let mut yaw = (key(Key::Left) - key(Key::Right)) * Self::KEY_TURN_SPEED * dt;
+        let mut pitch = (key(Key::Up) - key(Key::Down)) * Self::KEY_TURN_SPEED * dt;
+
+        let mouse = if window.is_mouse_down() {
+            window.mouse_position()
+        } else {
+            None
+        };
+        if let (Some((x0, y0)), Some((x1, y1))) = (self.last_mouse, mouse) {
+            yaw -= (x1 - x0) * self.sensitivity;
+            pitch -= (y1 - y0) * self.sensitivity;
+        }
+        self.last_mouse = mouse;
+
+        let translates = forward != 0.0 || right != 0.0 || up != 0.0;
+        let rotates = yaw != 0.0 || pitch != 0.0;
+
+        if translates {
+            let speed = if fast {
+                self.speed * Self::FAST_FACTOR
+            } else {
+                self.speed
+            };
+            let offset = camera.forward() * forward + camera.right() * right + Vec3::y() * up;
+            let position = camera.position() + offset.normalize() * speed * dt;
+            camera.transform().set_position(position);
+        }
+
+        if rotates {
+            let (current_yaw, current_pitch) = camera.orientation();
+            let pitch = (current_pitch + pitch).clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+            camera.set_orientation(current_yaw + yaw, pitch);
+        }
+
+        translates || rotates
+    }
+}
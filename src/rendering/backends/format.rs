@@ -0,0 +1,126 @@
+//! The image file formats supported by the [FileBackend](super::FileBackend).
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+/// An image file format.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ImageFormat {
+    /// 8-bit sRGB PNG.
+    #[default]
+    Png,
+    /// 8-bit sRGB JPEG, with a quality from 1 to 100.
+    Jpeg { quality: u8 },
+    /// 8-bit sRGB Windows bitmap.
+    Bmp,
+    /// 8-bit sRGB Truevision TGA.
+    Tga,
+    /// 8-bit sRGB binary portable pixmap.
+    Ppm,
+    /// Linear 32-bit float portable float map.
+    Pfm,
+    /// Linear Radiance RGBE. Negative values are clamped to zero.
+    Hdr,
+    /// Linear 32-bit float OpenEXR, with additional layers if available.
+    Exr,
+}
+
+impl ImageFormat {
+    pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+    /// Guesses the format from the extension of the file, e.g `.exr`.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let ext = Path::new(path).extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "jpg" | "jpeg" => Some(ImageFormat::Jpeg {
+                quality: Self::DEFAULT_JPEG_QUALITY,
+            }),
+            "bmp" => Some(ImageFormat::Bmp),
+            "tga" => Some(ImageFormat::Tga),
+            "ppm" | "pnm" => Some(ImageFormat::Ppm),
+            "pfm" => Some(ImageFormat::Pfm),
+            "hdr" => Some(ImageFormat::Hdr),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the format stores linear, unclamped floating-point values.
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, ImageFormat::Pfm | ImageFormat::Hdr | ImageFormat::Exr)
+    }
+}
+
+impl Display for ImageFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageFormat::Png => write!(f, "png"),
+            ImageFormat::Jpeg { quality } => write!(f, "jpeg:{}", quality),
+            ImageFormat::Bmp => write!(f, "bmp"),
+            ImageFormat::Tga => write!(f, "tga"),
+            ImageFormat::Ppm => write!(f, "ppm"),
+            ImageFormat::Pfm => write!(f, "pfm"),
+            ImageFormat::Hdr => write!(f, "hdr"),
+            ImageFormat::Exr => write!(f, "exr"),
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    /// Parses a format name, e.g `exr`. JPEG accepts an optional quality, e.g `jpeg:80`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let name = parts.next().unwrap_or_default().trim().to_lowercase();
+        let param = parts.next();
+
+        let format = match name.as_str() {
+            "jpg" | "jpeg" => {
+                let quality = match param {
+                    None => Self::DEFAULT_JPEG_QUALITY,
+                    Some(q) => match q.trim().parse::<u8>() {
+                        Ok(q) if (1..=100).contains(&q) => q,
+                        _ => return Err(format!("invalid JPEG quality: {}", q)),
+                    },
+                };
+                return Ok(ImageFormat::Jpeg { quality });
+            }
+            other => ImageFormat::from_path(&format!("image.{}", other))
+                .ok_or_else(|| format!("unknown image format: {}", s))?,
+        };
+
+        match param {
+            None => Ok(format),
+            Some(_) => Err(format!("the {} format has no parameter", format)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_path() {
+        assert_eq!(
+            Some(ImageFormat::Exr),
+            ImageFormat::from_path("out/render.EXR")
+        );
+        assert_eq!(
+            Some(ImageFormat::Jpeg { quality: 90 }),
+            ImageFormat::from_path("render.jpg")
+        );
+        assert_eq!(None, ImageFormat::from_path("render"));
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Ok(ImageFormat::Hdr), "hdr".parse());
+        assert_eq!(Ok(ImageFormat::Jpeg { quality: 75 }), "jpeg:75".parse());
+        assert!("jpeg:0".parse::<ImageFormat>().is_err());
+        assert!("png:3".parse::<ImageFormat>().is_err());
+        assert!("gif".parse::<ImageFormat>().is_err());
+    }
+}
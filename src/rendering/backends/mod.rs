@@ -1,15 +1,45 @@
-use crate::rendering::RenderTarget;
+use crate::error::Error;
+use crate::rendering::aov::AovImage;
+use crate::rendering::{Color, FrameBuffer, HdrBuffer, Pixel, RenderTarget};
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::hdr::HdrEncoder;
+use image::codecs::jpeg::JpegEncoder;
 use image::codecs::png::PngEncoder;
-use image::ColorType;
+use image::codecs::pnm::{PNMSubtype, PnmEncoder, SampleEncoding};
+use image::codecs::tga::TgaEncoder;
+use image::{ColorType, Rgb};
 use nameof::name_of_type;
 use std::fmt::{Display, Formatter};
 use std::fs;
-use minifb::{WindowOptions, Window, Key};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+pub mod animated;
+pub mod exr;
+#[cfg(feature = "window")]
+pub mod fly;
+pub mod format;
+pub mod terminal;
+#[cfg(feature = "window")]
+pub mod window;
+pub use animated::{AnimationBackend, AnimationFormat};
+pub use exr::{write_exr, ExrChannel};
+#[cfg(feature = "window")]
+pub use fly::FlyController;
+pub use format::ImageFormat;
+pub use terminal::TerminalBackend;
+#[cfg(feature = "window")]
+pub use window::{LiveWindow, WindowBackend};
 
 /// Trait for types that can present a [RenderTarget]
 pub trait Backend {
     /// Presents the [RenderTarget].
-    fn present(&self, buf: &dyn RenderTarget);
+    fn present(&self, buf: &dyn RenderTarget) -> Result<(), Error>;
+
+    /// Presents the linear [HdrBuffer]. By default, it is converted to 8-bit sRGB.
+    fn present_hdr(&self, buf: &HdrBuffer) -> Result<(), Error> {
+        self.present(&buf.to_frame_buffer())
+    }
 }
 
 /// A [Backend] that discards the [RenderTarget]
@@ -23,105 +53,196 @@ impl Display for NullBackend {
 }
 
 impl Backend for NullBackend {
-    fn present(&self, _: &dyn RenderTarget) {
+    fn present(&self, _: &dyn RenderTarget) -> Result<(), Error> {
         // do nothing
+        Ok(())
     }
 }
 
 /// A [Backend] that writes the [RenderTarget] into an image file.
+///
+/// The [ImageFormat] is guessed from the extension of the file (PNG if unknown), unless
+/// specified with [FileBackend::with_format].
 pub struct FileBackend<'a> {
     filename: &'a str,
+    format: ImageFormat,
 }
 
 impl<'a> Display for FileBackend<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} <{}>", name_of_type!(FileBackend), self.filename)
+        write!(
+            f,
+            "{} <{}> ({})",
+            name_of_type!(FileBackend),
+            self.filename,
+            self.format
+        )
     }
 }
 
-#[allow(dead_code)]
 impl<'a> FileBackend<'a> {
     pub fn new(filename: &'a str) -> Self {
-        FileBackend { filename }
+        FileBackend {
+            filename,
+            format: ImageFormat::from_path(filename).unwrap_or_default(),
+        }
     }
-}
 
-impl<'a> Backend for FileBackend<'a> {
-    fn present(&self, buf: &dyn RenderTarget) {
-        let stream = fs::File::create(self.filename).expect("could not create file");
-
-        let encoder = PngEncoder::new(stream);
-        encoder
-            .encode(
-                buf.bytes(),
-                buf.size().width,
-                buf.size().height,
-                ColorType::Rgb8,
-            )
-            .unwrap();
+    /// Overrides the format guessed from the file extension.
+    pub fn with_format(self, format: ImageFormat) -> Self {
+        let mut s = self;
+        s.format = format;
+        s
     }
-}
-
-/// A [Backend] that renders into a window.
-pub struct WindowBackend {}
 
-impl Backend for WindowBackend {
-    fn present(&self, buf: &dyn RenderTarget) {
-        let pixel_count = (buf.size().width * buf.size().height) as usize;
-
-        let mut window = Window::new(
-            "raytracer - Press 'Esc' to exit",
-            buf.size().width as usize,
-            buf.size().height as usize,
-            WindowOptions::default(),
-        )
-            .unwrap_or_else(|e| {
-                panic!("{}", e);
-            });
-
-        let mut output_buf = vec![0; pixel_count * 4];
+    /// Reads the file back, if it exists and is in an 8-bit format.
+    pub fn load(&self) -> Option<HdrBuffer> {
+        if self.format.is_hdr() || !Path::new(self.filename).exists() {
+            return None;
+        }
 
-        const R_OFFSET : usize = 0;
-        const G_OFFSET : usize = 1;
-        const B_OFFSET : usize = 2;
+        let fb = read_image(self.filename).ok()?;
+        Some(HdrBuffer::from_render_target(&fb))
+    }
 
-        let input = buf.bytes();
+    /// Writes an 8-bit format.
+    fn write_ldr(&self, buf: &dyn RenderTarget) -> Result<(), Error> {
+        let mut stream = fs::File::create(self.filename).map_err(Error::io(self.filename))?;
+        let (width, height) = (buf.size().width, buf.size().height);
+
+        match self.format {
+            ImageFormat::Png => {
+                PngEncoder::new(stream).encode(buf.bytes(), width, height, ColorType::Rgb8)
+            }
+            ImageFormat::Jpeg { quality } => JpegEncoder::new_with_quality(&mut stream, quality)
+                .encode(buf.bytes(), width, height, ColorType::Rgb8),
+            ImageFormat::Bmp => {
+                BmpEncoder::new(&mut stream).encode(buf.bytes(), width, height, ColorType::Rgb8)
+            }
+            ImageFormat::Tga => {
+                TgaEncoder::new(stream).encode(buf.bytes(), width, height, ColorType::Rgb8)
+            }
+            ImageFormat::Ppm => PnmEncoder::new(stream)
+                .with_subtype(PNMSubtype::Pixmap(SampleEncoding::Binary))
+                .encode(buf.bytes(), width, height, ColorType::Rgb8),
+            _ => {
+                return Err(Error::InvalidOption(format!(
+                    "{} is not an 8-bit format",
+                    self.format
+                )))
+            }
+        }
+        .map_err(Error::encoding(self.filename))
+    }
 
-        for i in 0..pixel_count {
-            let offset = (i * 3) as usize;
-            let r = input[offset + R_OFFSET] as u32;
-            let g = input[offset + G_OFFSET] as u32;
-            let b = input[offset + B_OFFSET] as u32;
+    /// Writes a floating-point format.
+    fn write_hdr(&self, buf: &HdrBuffer) -> Result<(), Error> {
+        let io = Error::io(self.filename);
+        let mut stream = BufWriter::new(fs::File::create(self.filename).map_err(&io)?);
+        let (width, height) = (buf.size().width, buf.size().height);
+
+        match self.format {
+            ImageFormat::Pfm => {
+                // PFM scanlines are stored from the bottom, and a negative scale means little endian
+                write!(stream, "PF\n{} {}\n-1.0\n", width, height).map_err(&io)?;
+                for row in buf.data().chunks_exact(3 * width as usize).rev() {
+                    for v in row {
+                        stream.write_all(&v.to_le_bytes()).map_err(&io)?;
+                    }
+                }
+                stream.flush().map_err(&io)
+            }
+            ImageFormat::Hdr => {
+                let pixels: Vec<Rgb<f32>> = buf
+                    .data()
+                    .chunks_exact(3)
+                    .map(|rgb| Rgb([rgb[0].max(0.0), rgb[1].max(0.0), rgb[2].max(0.0)]))
+                    .collect();
+                HdrEncoder::new(stream)
+                    .encode(&pixels, width as usize, height as usize)
+                    .map_err(Error::encoding(self.filename))
+            }
+            ImageFormat::Exr => self.write_exr_layers(&mut stream, buf, &[]),
+            _ => Err(Error::InvalidOption(format!(
+                "{} is not a floating-point format",
+                self.format
+            ))),
+        }
+    }
+}
 
-            // weird pattern 0RGB
-            output_buf[i as usize] = r << 16 | g << 8 | b << 0;
+impl<'a> FileBackend<'a> {
+    /// Writes the beauty image with the [AovImage]s as additional layers, e.g `depth.Z`.
+    /// Only OpenEXR supports layers: other formats only get the beauty image.
+    pub fn present_layers(&self, buf: &HdrBuffer, aovs: &[AovImage]) -> Result<(), Error> {
+        if self.format != ImageFormat::Exr {
+            log::warn!(
+                "{} does not support layers, AOVs are not written",
+                self.format
+            );
+            return self.present_hdr(buf);
         }
 
-        window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+        let mut stream =
+            BufWriter::new(fs::File::create(self.filename).map_err(Error::io(self.filename))?);
+        self.write_exr_layers(&mut stream, buf, aovs)
+    }
 
-        window
-            .update_with_buffer(
-                &output_buf,
-                buf.size().width as usize,
-                buf.size().height as usize,
-            )
-            .unwrap();
+    /// Writes the [AovImage] as a standalone image. Floating-point formats get the raw
+    /// values, while 8-bit formats get a visualization (see [AovImage::visualize]).
+    pub fn present_aov(&self, aov: &AovImage) -> Result<(), Error> {
+        if self.format.is_hdr() {
+            self.write_hdr(&aov.to_hdr_buffer())
+        } else {
+            self.write_ldr(&aov.visualize())
+        }
+    }
 
-        while window.is_open() && !window.is_key_down(Key::Escape) {
-            window.update();
+    fn write_exr_layers(
+        &self,
+        stream: &mut dyn Write,
+        buf: &HdrBuffer,
+        aovs: &[AovImage],
+    ) -> Result<(), Error> {
+        let mut channels = ExrChannel::split(None, &["R", "G", "B"], buf.data());
+        for aov in aovs {
+            channels.extend(ExrChannel::split(
+                Some(aov.aov.name()),
+                aov.aov.channels(),
+                &aov.data,
+            ));
         }
+        write_exr(stream, buf.size(), &channels)
+            .and_then(|_| stream.flush())
+            .map_err(Error::io(self.filename))
     }
 }
 
-#[allow(dead_code)]
-impl WindowBackend {
-    pub fn new() -> Self {
-        Self {}
+impl<'a> Backend for FileBackend<'a> {
+    fn present(&self, buf: &dyn RenderTarget) -> Result<(), Error> {
+        if self.format.is_hdr() {
+            self.write_hdr(&HdrBuffer::from_render_target(buf))
+        } else {
+            self.write_ldr(buf)
+        }
+    }
+
+    fn present_hdr(&self, buf: &HdrBuffer) -> Result<(), Error> {
+        if self.format.is_hdr() {
+            self.write_hdr(buf)
+        } else {
+            self.write_ldr(&buf.to_frame_buffer())
+        }
     }
 }
 
-impl Display for WindowBackend {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "WindowBackend")
+/// Reads an image file in any format supported by the `image` crate, as 8-bit sRGB.
+pub fn read_image(path: &str) -> Result<FrameBuffer, Error> {
+    let image = image::open(path).map_err(Error::decoding(path))?.to_rgb8();
+    let mut fb = FrameBuffer::try_new(image.width(), image.height())?;
+    for (x, y, rgb) in image.enumerate_pixels() {
+        fb.set(Pixel::new(x, y), Color::new(rgb[0], rgb[1], rgb[2]));
     }
+
+    Ok(fb)
 }
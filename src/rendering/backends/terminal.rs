@@ -0,0 +1,212 @@
+//! A [Backend] that draws into the terminal, e.g on remote machines without display.
+use crate::error::Error;
+use crate::rendering::backends::Backend;
+use crate::rendering::{Color, Pixel, RenderTarget};
+use nameof::name_of_type;
+use std::env;
+use std::fmt::{Display, Formatter, Write as _};
+use std::io::{self, Write};
+
+/// The size of the terminal when it cannot be queried, in characters.
+const DEFAULT_SIZE: (u32, u32) = (80, 24);
+
+/// The character drawn in each cell: its foreground color is the top pixel, and its
+/// background color the bottom pixel.
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+/// A [Backend] that draws the [RenderTarget] in the terminal with 24-bit ANSI colors.
+///
+/// Each character cell displays two pixels with a half block, and the image is
+/// downsampled to fit the terminal, keeping its aspect. One line is left for the prompt.
+#[derive(Debug, Default)]
+pub struct TerminalBackend {
+    /// overrides the size of the terminal, in characters
+    size: Option<(u32, u32)>,
+    /// the number of lines drawn by the last [TerminalBackend::update]
+    drawn_lines: u32,
+}
+
+impl Display for TerminalBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (columns, rows) = self.terminal_size();
+        write!(
+            f,
+            "{} ({}x{})",
+            name_of_type!(TerminalBackend),
+            columns,
+            rows
+        )
+    }
+}
+
+impl TerminalBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the size of the terminal, in columns and rows of characters.
+    pub fn with_size(self, columns: u32, rows: u32) -> Self {
+        let mut s = self;
+        s.size = Some((columns.max(1), rows.max(2)));
+        s
+    }
+
+    /// Returns the size of the terminal in columns and rows: the size set with
+    /// [TerminalBackend::with_size], or else the size of the standard output if it is a
+    /// terminal, or else the `COLUMNS` and `LINES` environment variables, or 80x24.
+    pub fn terminal_size(&self) -> (u32, u32) {
+        let from_env = |name: &str| {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse::<u32>().ok())
+                .filter(|v| *v > 0)
+        };
+        self.size.or_else(query_terminal_size).unwrap_or_else(|| {
+            (
+                from_env("COLUMNS").unwrap_or(DEFAULT_SIZE.0),
+                from_env("LINES").unwrap_or(DEFAULT_SIZE.1).max(2),
+            )
+        })
+    }
+
+    /// Returns the ANSI escape sequences that draw the [RenderTarget], one line per row of
+    /// characters.
+    pub fn draw(&self, buf: &dyn RenderTarget) -> String {
+        let (columns, rows) = self.terminal_size();
+        let (width, height) = (buf.size().width, buf.size().height);
+        let scale = (columns as f32 / width as f32)
+            .min(2.0 * (rows - 1) as f32 / height as f32)
+            .min(1.0);
+        let cells_width = ((width as f32 * scale).round() as u32).max(1);
+        let pixels_height = ((height as f32 * scale).round() as u32).max(1);
+
+        let mut output = String::new();
+        for row in 0..pixels_height.div_ceil(2) {
+            for x in 0..cells_width {
+                let top = average(buf, x, 2 * row, cells_width, pixels_height);
+                write!(output, "\x1b[38;2;{};{};{}m", top.r, top.g, top.b).unwrap();
+                if 2 * row + 1 < pixels_height {
+                    let bottom = average(buf, x, 2 * row + 1, cells_width, pixels_height);
+                    write!(output, "\x1b[48;2;{};{};{}m", bottom.r, bottom.g, bottom.b).unwrap();
+                }
+                output.push(UPPER_HALF_BLOCK);
+            }
+            output.push_str("\x1b[0m\n");
+        }
+
+        output
+    }
+
+    /// Draws the [RenderTarget] over the one drawn by the previous update, e.g to refresh
+    /// the terminal during progressive rendering.
+    pub fn update(&mut self, buf: &dyn RenderTarget) -> Result<(), Error> {
+        let mut output = String::new();
+        if self.drawn_lines > 0 {
+            // moves the cursor to the first line of the previous drawing
+            write!(output, "\x1b[{}F", self.drawn_lines).unwrap();
+        }
+        let drawing = self.draw(buf);
+        self.drawn_lines = drawing.lines().count() as u32;
+        output.push_str(&drawing);
+
+        write_stdout(&output)
+    }
+}
+
+impl Backend for TerminalBackend {
+    fn present(&self, buf: &dyn RenderTarget) -> Result<(), Error> {
+        write_stdout(&self.draw(buf))
+    }
+}
+
+fn write_stdout(output: &str) -> Result<(), Error> {
+    let mut stdout = io::stdout();
+    stdout
+        .write_all(output.as_bytes())
+        .and_then(|_| stdout.flush())
+        .map_err(Error::io("<stdout>"))
+}
+
+/// Returns the average color of the source pixels covered by the pixel of a downsampled
+/// image of `width` by `height`.
+fn average(buf: &dyn RenderTarget, x: u32, y: u32, width: u32, height: u32) -> Color {
+    let (sw, sh) = (buf.size().width, buf.size().height);
+    let (x0, x1) = (
+        x * sw / width,
+        ((x + 1) * sw / width).max(x * sw / width + 1),
+    );
+    let (y0, y1) = (
+        y * sh / height,
+        ((y + 1) * sh / height).max(y * sh / height + 1),
+    );
+
+    let mut sum = [0u32; 3];
+    for sy in y0..y1.min(sh) {
+        for sx in x0..x1.min(sw) {
+            let c = buf.get(Pixel::new(sx, sy));
+            sum[0] += c.r as u32;
+            sum[1] += c.g as u32;
+            sum[2] += c.b as u32;
+        }
+    }
+    let count = ((x1.min(sw) - x0) * (y1.min(sh) - y0)).max(1);
+    Color::new(
+        (sum[0] / count) as u8,
+        (sum[1] / count) as u8,
+        (sum[2] / count) as u8,
+    )
+}
+
+/// Returns the size of the terminal of the standard output, if it is one.
+#[cfg(unix)]
+fn query_terminal_size() -> Option<(u32, u32)> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only writes into the winsize structure
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if result != 0 || size.ws_col == 0 || size.ws_row < 2 {
+        return None;
+    }
+
+    Some((size.ws_col as u32, size.ws_row as u32))
+}
+
+#[cfg(not(unix))]
+fn query_terminal_size() -> Option<(u32, u32)> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rendering::{FrameBuffer, BLUE, RED};
+
+    #[test]
+    fn draws_half_blocks() {
+        let mut fb = FrameBuffer::new(4, 4);
+        fb.clear(RED);
+        for x in 0..4 {
+            fb.set(Pixel::new(x, 2), BLUE);
+            fb.set(Pixel::new(x, 3), BLUE);
+        }
+
+        // the image fits: one cell per pixel column, two pixels rows per line
+        let drawing = TerminalBackend::new().with_size(10, 10).draw(&fb);
+        let lines: Vec<&str> = drawing.lines().collect();
+        assert_eq!(2, lines.len());
+        assert_eq!(4, lines[0].matches(UPPER_HALF_BLOCK).count());
+        assert!(lines[0].starts_with("\x1b[38;2;255;0;0m\x1b[48;2;255;0;0m"));
+        assert!(lines[1].starts_with("\x1b[38;2;0;0;255m\x1b[48;2;0;0;255m"));
+
+        // downsampled to 2 columns, keeping the aspect
+        let drawing = TerminalBackend::new().with_size(2, 10).draw(&fb);
+        let lines: Vec<&str> = drawing.lines().collect();
+        assert_eq!(1, lines.len());
+        assert_eq!(2, lines[0].matches(UPPER_HALF_BLOCK).count());
+        assert!(lines[0].starts_with("\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m"));
+    }
+}
@@ -0,0 +1,158 @@
+//! The backends that display renders in a window, only built with the `window` feature.
+use crate::error::Error;
+use crate::rendering::backends::Backend;
+use crate::rendering::RenderTarget;
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+use nameof::name_of_type;
+use std::fmt::{Display, Formatter};
+
+/// A [Backend] that renders into a window.
+#[derive(Default)]
+pub struct WindowBackend {}
+
+impl Backend for WindowBackend {
+    fn present(&self, buf: &dyn RenderTarget) -> Result<(), Error> {
+        let pixel_count = (buf.size().width * buf.size().height) as usize;
+
+        let mut window = open_window(
+            "raytracer - Press 'Esc' to exit",
+            buf.size().width,
+            buf.size().height,
+        )?;
+
+        let mut output_buf = vec![0; pixel_count];
+        to_0rgb(buf, &mut output_buf);
+
+        window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+
+        window
+            .update_with_buffer(
+                &output_buf,
+                buf.size().width as usize,
+                buf.size().height as usize,
+            )
+            .map_err(|e| Error::NoDisplay(e.to_string()))?;
+
+        while window.is_open() && !window.is_key_down(Key::Escape) {
+            window.update();
+        }
+
+        Ok(())
+    }
+}
+
+impl WindowBackend {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Display for WindowBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WindowBackend")
+    }
+}
+
+/// Opens a window, or returns [Error::NoDisplay] if there is no display to open it on.
+fn open_window(title: &str, width: u32, height: u32) -> Result<Window, Error> {
+    // X11 and Wayland need a display server: check for one before loading their libraries
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        if std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none() {
+            return Err(Error::NoDisplay(String::from(
+                "neither DISPLAY nor WAYLAND_DISPLAY is set",
+            )));
+        }
+    }
+
+    Window::new(
+        title,
+        width as usize,
+        height as usize,
+        WindowOptions::default(),
+    )
+    .map_err(|e| Error::NoDisplay(e.to_string()))
+}
+
+/// Converts the RGB bytes of the [RenderTarget] into the 0RGB pixels expected by minifb.
+fn to_0rgb(buf: &dyn RenderTarget, output: &mut [u32]) {
+    const R_OFFSET: usize = 0;
+    const G_OFFSET: usize = 1;
+    const B_OFFSET: usize = 2;
+
+    for (output, rgb) in output.iter_mut().zip(buf.bytes().chunks_exact(3)) {
+        let r = rgb[R_OFFSET] as u32;
+        let g = rgb[G_OFFSET] as u32;
+        let b = rgb[B_OFFSET] as u32;
+
+        // weird pattern 0RGB
+        *output = r << 16 | g << 8 | b;
+    }
+}
+
+/// A window that displays a [RenderTarget] while it is being rendered, e.g for
+/// progressive rendering. Unlike [WindowBackend], it does not block: the caller
+/// refreshes it every frame with [LiveWindow::update].
+pub struct LiveWindow {
+    window: Window,
+    output_buf: Vec<u32>,
+}
+
+impl Display for LiveWindow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", name_of_type!(LiveWindow))
+    }
+}
+
+impl LiveWindow {
+    pub fn new(width: u32, height: u32) -> Result<Self, Error> {
+        let mut window = open_window("raytracer", width, height)?;
+
+        window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+
+        Ok(LiveWindow {
+            window,
+            output_buf: vec![0; (width * height) as usize],
+        })
+    }
+
+    /// Returns false once the window has been closed, or 'Esc' has been pressed.
+    pub fn is_open(&self) -> bool {
+        self.window.is_open() && !self.window.is_key_down(Key::Escape)
+    }
+
+    /// Returns true if the key has been pressed since the last update.
+    pub fn is_key_pressed(&self, key: Key) -> bool {
+        self.window.is_key_pressed(key, KeyRepeat::No)
+    }
+
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.window.is_key_down(key)
+    }
+
+    /// Returns the position of the mouse cursor, in window pixels.
+    pub fn mouse_position(&self) -> Option<(f32, f32)> {
+        self.window.get_mouse_pos(MouseMode::Pass)
+    }
+
+    pub fn is_mouse_down(&self) -> bool {
+        self.window.get_mouse_down(MouseButton::Left)
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    /// Displays the [RenderTarget] and processes the window events.
+    pub fn update(&mut self, buf: &dyn RenderTarget) -> Result<(), Error> {
+        to_0rgb(buf, &mut self.output_buf);
+
+        self.window
+            .update_with_buffer(
+                &self.output_buf,
+                buf.size().width as usize,
+                buf.size().height as usize,
+            )
+            .map_err(|e| Error::NoDisplay(e.to_string()))
+    }
+}
@@ -0,0 +1,368 @@
+//! The accumulation state of a render, that can be saved to a file and resumed.
+//!
+//! The checkpoint file is a small binary format: a header with the parameters the state
+//! depends on, followed by the [FilmPixel]s, the per-pixel [Sample]s and the AOVs, little
+//! endian.
+use crate::rendering::film::FilmPixel;
+use crate::rendering::{Film, Pixel, PixelRect, PixelSize, RenderOpts, Sample, SampleCounts};
+use crate::scene::Scene;
+use nameof::name_of_type;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: [u8; 4] = *b"RTCK";
+const VERSION: u32 = 4;
+
+/// The maximum length of the parameters in a checkpoint file, so that a corrupted length
+/// does not allocate gigabytes.
+const MAX_PARAMETERS_LEN: u32 = 4096;
+
+/// The state of a render that progresses in passes: the [Film], and the samples taken for
+/// each pixel. Samplers are deterministic, so the samples of the next pass only depend on
+/// the number of samples already taken.
+///
+/// The accumulated AOVs are part of the state, so a resumed render gets the same AOVs.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    film: Film,
+    samples: Vec<Sample>,
+    /// The number of completed passes.
+    passes: u32,
+    /// The parameters that must not change when resuming, e.g the filter or the scene.
+    parameters: String,
+}
+
+impl Display for Checkpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({} passes, {})",
+            name_of_type!(Checkpoint),
+            self.passes,
+            self.parameters
+        )
+    }
+}
+
+impl Checkpoint {
+    /// Creates the empty state of a render of the [Scene] at the specified size.
+    pub fn new(scene: &Scene, size: PixelSize, opts: &RenderOpts) -> Self {
+        let region = opts.crop.map(|c| c.rect(size));
+        Checkpoint {
+            film: Film::new(size, opts.filter)
+                .with_region(region)
+                .with_aovs(opts.aovs),
+            samples: vec![Sample::default(); (size.width * size.height) as usize],
+            passes: 0,
+            parameters: Self::parameters(scene, size, opts),
+        }
+    }
+
+    /// Describes the parameters of the render that a checkpoint depends on. The scene is
+    /// identified by its [fingerprint](Scene::fingerprint), which includes the camera pose:
+    /// the pose is also written out, to tell which one changed.
+    fn parameters(scene: &Scene, size: PixelSize, opts: &RenderOpts) -> String {
+        let region = opts
+            .crop
+            .map(|c| c.rect(size))
+            .unwrap_or_else(|| PixelRect::full(size));
+        let camera = scene.camera();
+        let p = camera.position();
+        let (yaw, pitch) = camera.orientation();
+        format!(
+            "{}*{}, region {}, filter {}, sampler {} (seed {}), AOVs [{}], camera at ({}, {}, {}) yaw {} pitch {}, scene {:08x}",
+            size.width,
+            size.height,
+            region,
+            opts.filter,
+            opts.sampler,
+            opts.seed,
+            opts.aovs,
+            p.x,
+            p.y,
+            p.z,
+            yaw,
+            pitch,
+            scene.fingerprint()
+        )
+    }
+
+    /// Returns an error if the render of the [Scene] cannot be resumed with the [RenderOpts].
+    pub fn check_compatible(
+        &self,
+        scene: &Scene,
+        size: PixelSize,
+        opts: &RenderOpts,
+    ) -> Result<(), String> {
+        let expected = Self::parameters(scene, size, opts);
+        if self.parameters == expected {
+            Ok(())
+        } else {
+            Err(format!(
+                "the checkpoint was rendered with {}, but the render uses {}",
+                self.parameters, expected
+            ))
+        }
+    }
+
+    pub fn film(&self) -> &Film {
+        &self.film
+    }
+
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    /// Gets the film and the per-pixel samples, to add a pass.
+    pub fn state_mut(&mut self) -> (&mut Film, &mut [Sample]) {
+        (&mut self.film, &mut self.samples)
+    }
+
+    /// Records the completion of a pass.
+    pub fn end_pass(&mut self) {
+        self.passes += 1;
+    }
+
+    /// Returns the number of samples taken for each pixel of the region of the film.
+    pub fn sample_counts(&self) -> SampleCounts {
+        let region = self.film.region();
+        let width = self.film.size().width;
+        let mut counts = SampleCounts::new(region.size);
+        for pixel in region.pixels() {
+            let samples = self.samples[(pixel.x + pixel.y * width) as usize].samples;
+            counts.set(
+                Pixel::new(pixel.x - region.origin.x, pixel.y - region.origin.y),
+                samples,
+            );
+        }
+        counts
+    }
+
+    /// Writes the checkpoint. The file is replaced atomically, so that a render killed
+    /// while saving keeps its previous checkpoint.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let tmp = format!("{}.tmp", path);
+        {
+            let mut w = BufWriter::new(fs::File::create(&tmp)?);
+            self.write(&mut w)?;
+            w.flush()?;
+        }
+        fs::rename(&tmp, path)
+    }
+
+    /// Reads a checkpoint written by [Checkpoint::save].
+    pub fn load(path: &str, scene: &Scene, size: PixelSize, opts: &RenderOpts) -> io::Result<Self> {
+        let mut r = BufReader::new(fs::File::open(Path::new(path))?);
+        Checkpoint::read(&mut r, scene, size, opts)
+    }
+
+    fn write(&self, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(&MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&(self.parameters.len() as u32).to_le_bytes())?;
+        w.write_all(self.parameters.as_bytes())?;
+        w.write_all(&self.passes.to_le_bytes())?;
+
+        for p in self.film.pixels() {
+            for v in [p.r, p.g, p.b, p.weight].iter().chain(p.unweighted.iter()) {
+                w.write_all(&v.to_le_bytes())?;
+            }
+            w.write_all(&p.count.to_le_bytes())?;
+        }
+        for s in self.samples.iter() {
+            for v in [s.r, s.g, s.b, s.mean, s.m2].iter() {
+                w.write_all(&v.to_le_bytes())?;
+            }
+            w.write_all(&s.samples.to_le_bytes())?;
+        }
+        if let Some(aovs) = self.film.aov_buffers() {
+            let (counts, layers) = aovs.data();
+            for count in counts {
+                w.write_all(&count.to_le_bytes())?;
+            }
+            for v in layers.into_iter().flatten() {
+                w.write_all(&v.to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads a checkpoint for a render of the [Scene] at the specified size, with the [RenderOpts].
+    fn read(
+        r: &mut dyn Read,
+        scene: &Scene,
+        size: PixelSize,
+        opts: &RenderOpts,
+    ) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid(String::from("not a checkpoint file")));
+        }
+        let version = read_u32(r)?;
+        if version != VERSION {
+            return Err(invalid(format!(
+                "unsupported checkpoint version {}",
+                version
+            )));
+        }
+
+        let len = read_u32(r)?;
+        if len > MAX_PARAMETERS_LEN {
+            return Err(invalid(format!(
+                "invalid length of the checkpoint parameters: {}",
+                len
+            )));
+        }
+        let mut parameters = vec![0; len as usize];
+        r.read_exact(&mut parameters)?;
+        let parameters = String::from_utf8(parameters).map_err(|e| invalid(e.to_string()))?;
+
+        let mut checkpoint = Checkpoint::new(scene, size, opts);
+        checkpoint.parameters = parameters;
+        checkpoint
+            .check_compatible(scene, size, opts)
+            .map_err(invalid)?;
+        checkpoint.passes = read_u32(r)?;
+
+        let count = (size.width * size.height) as usize;
+        let mut pixels = Vec::with_capacity(count);
+        for _ in 0..count {
+            pixels.push(FilmPixel {
+                r: read_f64(r)?,
+                g: read_f64(r)?,
+                b: read_f64(r)?,
+                weight: read_f64(r)?,
+                unweighted: [read_f64(r)?, read_f64(r)?, read_f64(r)?],
+                count: read_u32(r)?,
+            });
+        }
+        checkpoint.film.restore(pixels);
+
+        for s in checkpoint.samples.iter_mut() {
+            s.r = read_f64(r)?;
+            s.g = read_f64(r)?;
+            s.b = read_f64(r)?;
+            s.mean = read_f64(r)?;
+            s.m2 = read_f64(r)?;
+            s.samples = read_u32(r)?;
+        }
+        if let Some(aovs) = checkpoint.film.aov_buffers_mut() {
+            let (counts, layers) = aovs.data_mut();
+            for count in counts.iter_mut() {
+                *count = read_u32(r)?;
+            }
+            for v in layers.into_iter().flatten() {
+                *v = read_f32(r)?;
+            }
+        }
+
+        Ok(checkpoint)
+    }
+}
+
+fn read_u32(r: &mut dyn Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32(r: &mut dyn Read) -> io::Result<f32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+fn read_f64(r: &mut dyn Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::Vec3;
+    use crate::rendering::{Adaptive, AovSet, HdrBuffer};
+
+    #[test]
+    fn resumed_renders_match_uninterrupted_renders() {
+        let scene = Scene::new();
+        let size = PixelSize::new(32, 18);
+        let opts = RenderOpts::new()
+            .with_adaptive(Some(Adaptive::new(2, 8, 0.05)))
+            .with_filter("gaussian".parse().unwrap())
+            .with_aovs("depth,albedo".parse::<AovSet>().unwrap());
+
+        let mut expected = HdrBuffer::new(size.width, size.height);
+        let (expected_counts, expected_aovs) = scene.render_hdr(&mut expected, &opts, &|_| {});
+
+        // interrupted after 3 passes, then saved and resumed
+        let mut checkpoint = Checkpoint::new(&scene, size, &opts);
+        scene.render_resumable(&mut checkpoint, &opts, &|_| {}, &mut |c| c.passes() < 3);
+        assert_eq!(3, checkpoint.passes());
+
+        let mut bytes = Vec::new();
+        checkpoint.write(&mut bytes).unwrap();
+        let mut resumed = Checkpoint::read(&mut bytes.as_slice(), &scene, size, &opts).unwrap();
+        scene.render_resumable(&mut resumed, &opts, &|_| {}, &mut |_| true);
+
+        let mut actual = HdrBuffer::new(size.width, size.height);
+        resumed.film().resolve_hdr(&mut actual);
+        assert_eq!(expected.data(), actual.data());
+        assert_eq!(expected_counts.average(), resumed.sample_counts().average());
+        let actual_aovs = resumed.film().aov_images();
+        assert_eq!(2, actual_aovs.len());
+        for (expected, actual) in expected_aovs.iter().zip(actual_aovs.iter()) {
+            assert_eq!(expected.data, actual.data, "{}", expected.aov);
+        }
+    }
+
+    #[test]
+    fn incompatible_checkpoints_are_rejected() {
+        let scene = Scene::new();
+        let size = PixelSize::new(4, 4);
+        let opts = RenderOpts::new();
+        let mut bytes = Vec::new();
+        Checkpoint::new(&scene, size, &opts)
+            .write(&mut bytes)
+            .unwrap();
+
+        assert!(Checkpoint::read(&mut bytes.as_slice(), &scene, size, &opts.with_seed(3)).is_err());
+        assert!(
+            Checkpoint::read(&mut bytes.as_slice(), &scene, PixelSize::new(4, 5), &opts).is_err()
+        );
+        let with_aovs = opts.with_aovs("normal".parse().unwrap());
+        assert!(Checkpoint::read(&mut bytes.as_slice(), &scene, size, &with_aovs).is_err());
+        // more samples can be added to a finished render
+        assert!(
+            Checkpoint::read(&mut bytes.as_slice(), &scene, size, &opts.with_samples(64)).is_ok()
+        );
+
+        let mut moved = Scene::new();
+        moved.camera_mut().set_orientation(10.0, 0.0);
+        assert!(Checkpoint::read(&mut bytes.as_slice(), &moved, size, &opts).is_err());
+        let mut zoomed = Scene::new();
+        zoomed.camera_mut().set_vertical_fov(30.0);
+        assert!(Checkpoint::read(&mut bytes.as_slice(), &zoomed, size, &opts).is_err());
+        let mut edited = Scene::new();
+        let (first, _) = edited.graph().nodes().next().unwrap();
+        edited
+            .graph_mut()
+            .node_mut(first)
+            .transform_mut()
+            .set_position(Vec3::new(0.0, 3.0, 0.0));
+        edited.resolve_transforms();
+        assert!(Checkpoint::read(&mut bytes.as_slice(), &edited, size, &opts).is_err());
+
+        // a corrupted length is rejected before allocating
+        bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        let error = Checkpoint::read(&mut bytes.as_slice(), &scene, size, &opts).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+    }
+}
@@ -0,0 +1,225 @@
+//! Crop windows, that restrict a render to a region of the image.
+use crate::error::Error;
+use crate::rendering::{Pixel, PixelRect, PixelSize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A region of the image to render, in pixels or relative to the size of the image.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CropWindow {
+    /// A rectangle in pixels, from its top-left corner.
+    Pixels {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// The corners of the region, where (0, 0) is the top-left corner of the image,
+    /// and (1, 1) the bottom-right corner.
+    Normalized { x0: f32, y0: f32, x1: f32, y1: f32 },
+}
+
+impl Display for CropWindow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CropWindow::Pixels {
+                x,
+                y,
+                width,
+                height,
+            } => write!(f, "{},{},{},{}", x, y, width, height),
+            CropWindow::Normalized { x0, y0, x1, y1 } => {
+                write!(f, "normalized:{},{},{},{}", x0, y0, x1, y1)
+            }
+        }
+    }
+}
+
+impl FromStr for CropWindow {
+    type Err = String;
+
+    /// Parses a window in pixels, as `x,y,width,height`, or in normalized units, as
+    /// `normalized:x0,y0,x1,y1`, e.g `normalized:0.25,0.25,0.75,0.5`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (normalized, values) = match s.trim().strip_prefix("normalized:") {
+            Some(values) => (true, values),
+            None => (false, s),
+        };
+
+        let values: Vec<&str> = values.split(',').map(str::trim).collect();
+        if values.len() != 4 {
+            return Err(format!("expected 4 values in crop window: {}", s));
+        }
+
+        if normalized {
+            let mut v = [0.0f32; 4];
+            for (v, s) in v.iter_mut().zip(values) {
+                *v = s
+                    .parse()
+                    .map_err(|_| format!("invalid crop coordinate: {}", s))?;
+            }
+            let [x0, y0, x1, y1] = v;
+            let valid = |a: f32, b: f32| (0.0..b).contains(&a) && b <= 1.0;
+            if !valid(x0, x1) || !valid(y0, y1) {
+                return Err(format!("invalid normalized crop window: {}", s));
+            }
+            Ok(CropWindow::Normalized { x0, y0, x1, y1 })
+        } else {
+            let mut v = [0u32; 4];
+            for (v, s) in v.iter_mut().zip(values) {
+                *v = s
+                    .parse()
+                    .map_err(|_| format!("invalid crop coordinate: {}", s))?;
+            }
+            let [x, y, width, height] = v;
+            if width == 0 || height == 0 {
+                return Err(format!("empty crop window: {}", s));
+            }
+            Ok(CropWindow::Pixels {
+                x,
+                y,
+                width,
+                height,
+            })
+        }
+    }
+}
+
+impl CropWindow {
+    /// Returns an error if the window lies outside of an image of the specified size.
+    pub fn check(&self, size: PixelSize) -> Result<(), Error> {
+        match *self {
+            CropWindow::Pixels { x, y, .. } if x >= size.width || y >= size.height => {
+                Err(Error::InvalidOption(format!(
+                    "the crop window {} is outside of the image ({}*{})",
+                    self, size.width, size.height
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the pixels of an image of the specified size covered by the window. Normalized
+    /// windows include every pixel they overlap, and cover at least one pixel.
+    ///
+    /// Panics if the window lies outside of the image, see [CropWindow::check].
+    ///
+    /// ```
+    /// use raytracer::{CropWindow, Pixel, PixelSize};
+    ///
+    /// let window: CropWindow = "normalized:0.5,0,1,0.5".parse().unwrap();
+    /// let rect = window.rect(PixelSize::new(640, 480));
+    /// assert_eq!(Pixel::new(320, 0), rect.origin);
+    /// assert_eq!(PixelSize::new(320, 240), rect.size);
+    /// ```
+    pub fn rect(&self, size: PixelSize) -> PixelRect {
+        let (x0, y0, x1, y1) = match *self {
+            CropWindow::Pixels {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                if let Err(e) = self.check(size) {
+                    panic!("{}", e);
+                }
+                (
+                    x,
+                    y,
+                    x.saturating_add(width).min(size.width),
+                    y.saturating_add(height).min(size.height),
+                )
+            }
+            CropWindow::Normalized { x0, y0, x1, y1 } => {
+                let (w, h) = (size.width as f32, size.height as f32);
+                let left = ((x0 * w).floor() as u32).min(size.width - 1);
+                let top = ((y0 * h).floor() as u32).min(size.height - 1);
+                let right = ((x1 * w).ceil() as u32).clamp(left + 1, size.width);
+                let bottom = ((y1 * h).ceil() as u32).clamp(top + 1, size.height);
+                (left, top, right, bottom)
+            }
+        };
+
+        PixelRect::new(Pixel::new(x0, y0), PixelSize::new(x1 - x0, y1 - y0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Ok(CropWindow::Pixels {
+                x: 10,
+                y: 20,
+                width: 30,
+                height: 40
+            }),
+            "10, 20, 30, 40".parse()
+        );
+        assert_eq!(
+            Ok(CropWindow::Normalized {
+                x0: 0.25,
+                y0: 0.0,
+                x1: 0.5,
+                y1: 1.0
+            }),
+            "normalized:0.25,0,0.5,1".parse()
+        );
+        assert!("10,20,0,40".parse::<CropWindow>().is_err());
+        assert!("normalized:0.5,0,0.25,1".parse::<CropWindow>().is_err());
+        assert!("1,2,3".parse::<CropWindow>().is_err());
+    }
+
+    #[test]
+    fn rect() {
+        let size = PixelSize::new(100, 50);
+
+        let pixels = CropWindow::Pixels {
+            x: 90,
+            y: 10,
+            width: 20,
+            height: 5,
+        };
+        assert_eq!(
+            PixelRect::new(Pixel::new(90, 10), PixelSize::new(10, 5)),
+            pixels.rect(size)
+        );
+
+        let normalized = CropWindow::Normalized {
+            x0: 0.255,
+            y0: 0.5,
+            x1: 0.5,
+            y1: 0.5001,
+        };
+        assert_eq!(
+            PixelRect::new(Pixel::new(25, 25), PixelSize::new(25, 1)),
+            normalized.rect(size)
+        );
+        assert!(CropWindow::Pixels {
+            x: 100,
+            y: 0,
+            width: 1,
+            height: 1
+        }
+        .check(size)
+        .is_err());
+    }
+
+    #[test]
+    fn huge_windows_are_clamped() {
+        let size = PixelSize::new(100, 50);
+        let huge = CropWindow::Pixels {
+            x: 10,
+            y: 20,
+            width: u32::MAX,
+            height: u32::MAX,
+        };
+        assert_eq!(
+            PixelRect::new(Pixel::new(10, 20), PixelSize::new(90, 30)),
+            huge.rect(size)
+        );
+    }
+}
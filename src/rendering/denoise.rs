@@ -0,0 +1,292 @@
+//! Denoising of low-sample renders, guided by the albedo and normal AOVs.
+use crate::error::Error;
+use crate::rendering::aov::{Aov, AovImage};
+use crate::rendering::{HdrBuffer, Pixel};
+use std::fmt::{Display, Formatter};
+
+/// A joint (cross) bilateral filter.
+///
+/// Each pixel is replaced by a weighted average of its neighbors. The weights decrease
+/// with the distance between the pixels, and with the differences of their albedo,
+/// normal and color: noise is smoothed out, but the edges of the guides are preserved.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Denoiser {
+    /// Scales the extent of the filter and its tolerance to color differences.
+    /// `0` disables denoising, `1` is the default.
+    pub strength: f32,
+}
+
+impl Display for Denoiser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "joint bilateral denoiser (strength {})", self.strength)
+    }
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser::new(1.0)
+    }
+}
+
+/// The AOVs that guide the [Denoiser].
+pub struct DenoiseGuides<'a> {
+    albedo: &'a AovImage,
+    normal: &'a AovImage,
+}
+
+impl<'a> DenoiseGuides<'a> {
+    /// The [Aov]s required by the [Denoiser].
+    pub const AOVS: [Aov; 2] = [Aov::Albedo, Aov::Normal];
+
+    /// Finds the guides among the rendered AOVs, or returns an error if one is missing.
+    pub fn from_aovs(aovs: &'a [AovImage]) -> Result<Self, Error> {
+        let find = |aov: Aov| {
+            aovs.iter().find(|a| a.aov == aov).ok_or_else(|| {
+                Error::InvalidOption(format!("denoising requires the {} AOV", aov.name()))
+            })
+        };
+
+        Ok(DenoiseGuides {
+            albedo: find(Aov::Albedo)?,
+            normal: find(Aov::Normal)?,
+        })
+    }
+}
+
+impl Denoiser {
+    /// The standard deviation of the spatial weights at strength 1, in pixels.
+    const SIGMA_SPATIAL: f32 = 1.5;
+    /// The standard deviation of the color weights at strength 1, in linear units.
+    const SIGMA_COLOR: f32 = 0.5;
+    /// The standard deviation of the albedo weights.
+    const SIGMA_ALBEDO: f32 = 0.05;
+    /// The exponent applied to the cosine between normals.
+    const NORMAL_POWER: f32 = 64.0;
+
+    pub fn new(strength: f32) -> Self {
+        Denoiser {
+            strength: strength.max(0.0),
+        }
+    }
+
+    /// Returns the denoised image.
+    pub fn denoise(&self, image: &HdrBuffer, guides: &DenoiseGuides) -> HdrBuffer {
+        let size = image.size();
+        assert_eq!(
+            size, guides.albedo.size,
+            "the albedo guide must have the size of the image"
+        );
+        assert_eq!(
+            size, guides.normal.size,
+            "the normal guide must have the size of the image"
+        );
+
+        if self.strength <= 0.0 {
+            return image.clone();
+        }
+
+        let sigma_spatial = Self::SIGMA_SPATIAL * self.strength;
+        let sigma_color = Self::SIGMA_COLOR * self.strength;
+        let radius = (2.0 * sigma_spatial).ceil() as i64;
+
+        let spatial = 1.0 / (2.0 * sigma_spatial * sigma_spatial);
+        let color = 1.0 / (2.0 * sigma_color * sigma_color);
+        let albedo = 1.0 / (2.0 * Self::SIGMA_ALBEDO * Self::SIGMA_ALBEDO);
+
+        let guide = |img: &AovImage, x: u32, y: u32| {
+            let i = 3 * (x + y * size.width) as usize;
+            [img.data[i], img.data[i + 1], img.data[i + 2]]
+        };
+        // normals are averaged over the samples of the pixel, so they are shorter at edges
+        let normal = |x: u32, y: u32| {
+            let n = guide(guides.normal, x, y);
+            let length = dot(&n, &n).sqrt();
+            if length < 1e-3 {
+                None
+            } else {
+                Some([n[0] / length, n[1] / length, n[2] / length])
+            }
+        };
+
+        let mut output = HdrBuffer::new(size.width, size.height);
+        for y in 0..size.height {
+            for x in 0..size.width {
+                let c0 = image.get(Pixel::new(x, y));
+                let a0 = guide(guides.albedo, x, y);
+                let n0 = normal(x, y);
+
+                let mut sum = [0.0f32; 3];
+                let mut total = 0.0f32;
+
+                for dy in -radius..=radius {
+                    let ny = y as i64 + dy;
+                    if ny < 0 || ny >= size.height as i64 {
+                        continue;
+                    }
+                    for dx in -radius..=radius {
+                        let nx = x as i64 + dx;
+                        if nx < 0 || nx >= size.width as i64 {
+                            continue;
+                        }
+                        let (nx, ny) = (nx as u32, ny as u32);
+
+                        let c = image.get(Pixel::new(nx, ny));
+                        let a = guide(guides.albedo, nx, ny);
+                        let n = normal(nx, ny);
+
+                        let normal_weight = match (n0, n) {
+                            (Some(n0), Some(n)) => dot(&n0, &n).max(0.0).powf(Self::NORMAL_POWER),
+                            // the background has no normal, and only matches itself
+                            (None, None) => 1.0,
+                            _ => 0.0,
+                        };
+
+                        let weight = (-((dx * dx + dy * dy) as f32) * spatial
+                            - sqr_distance(&c0, &c) * color
+                            - sqr_distance(&a0, &a) * albedo)
+                            .exp()
+                            * normal_weight;
+
+                        for i in 0..3 {
+                            sum[i] += c[i] * weight;
+                        }
+                        total += weight;
+                    }
+                }
+
+                // the center pixel always has a weight of 1
+                output.set(
+                    Pixel::new(x, y),
+                    [sum[0] / total, sum[1] / total, sum[2] / total],
+                );
+            }
+        }
+
+        output
+    }
+}
+
+fn dot(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn sqr_distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    dot(&d, &d)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::random::Pcg32;
+    use crate::rendering::{AovSet, PixelSize, RenderOpts, SamplerKind};
+    use crate::scene::Scene;
+
+    fn rmse(a: &HdrBuffer, b: &HdrBuffer) -> f32 {
+        let n = a.data().len() as f32;
+        let sum: f32 = a
+            .data()
+            .iter()
+            .zip(b.data())
+            .map(|(x, y)| (x - y) * (x - y))
+            .sum();
+        (sum / n).sqrt()
+    }
+
+    /// Renders the default scene, and adds gaussian noise to a copy of the render.
+    fn noisy_default_scene(size: PixelSize) -> (HdrBuffer, HdrBuffer, Vec<AovImage>) {
+        let mut aovs = AovSet::default();
+        DenoiseGuides::AOVS.iter().for_each(|a| aovs.insert(*a));
+        let opts = RenderOpts::new().with_samples(4).with_aovs(aovs);
+
+        let mut reference = HdrBuffer::new(size.width, size.height);
+        let (_, images) = Scene::new().render_hdr(&mut reference, &opts, &|_| {});
+
+        let mut rng = Pcg32::new(7);
+        let mut noisy = reference.clone();
+        for y in 0..size.height {
+            for x in 0..size.width {
+                let pixel = Pixel::new(x, y);
+                let mut rgb = noisy.get(pixel);
+                for v in rgb.iter_mut() {
+                    // Box-Muller transform
+                    let (u1, u2) = (rng.next_f32().max(1e-6), rng.next_f32());
+                    let gaussian =
+                        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos();
+                    *v += 0.1 * gaussian;
+                }
+                noisy.set(pixel, rgb);
+            }
+        }
+
+        (reference, noisy, images)
+    }
+
+    #[test]
+    fn denoising_reduces_the_error_of_a_noisy_render() {
+        let (reference, noisy, aovs) = noisy_default_scene(PixelSize::new(96, 54));
+        let guides = DenoiseGuides::from_aovs(&aovs).unwrap();
+
+        let denoised = Denoiser::default().denoise(&noisy, &guides);
+        let stronger = Denoiser::new(2.0).denoise(&noisy, &guides);
+
+        let before = rmse(&noisy, &reference);
+        assert!(rmse(&denoised, &reference) < 0.5 * before);
+        assert!(rmse(&stronger, &reference) < rmse(&denoised, &reference));
+        assert_eq!(
+            noisy.data(),
+            Denoiser::new(0.0).denoise(&noisy, &guides).data()
+        );
+    }
+
+    #[test]
+    fn denoising_keeps_a_low_sample_render_close_to_a_high_sample_one() {
+        let mut aovs = AovSet::default();
+        DenoiseGuides::AOVS.iter().for_each(|a| aovs.insert(*a));
+        let opts = RenderOpts::new()
+            .with_aovs(aovs)
+            .with_sampler(SamplerKind::Independent);
+        let scene = Scene::new();
+        let render = |samples: u32| {
+            let mut hdr = HdrBuffer::new(96, 54);
+            let (_, images) = scene.render_hdr(&mut hdr, &opts.with_samples(samples), &|_| {});
+            (hdr, images)
+        };
+
+        let (reference, _) = render(32);
+        let (noisy, images) = render(2);
+        let guides = DenoiseGuides::from_aovs(&images).unwrap();
+        let denoised = Denoiser::new(2.0).denoise(&noisy, &guides);
+
+        // without lighting, the only noise is the aliasing of the edges, which the guides
+        // share: the denoiser must not blur the edges away from the converged render
+        let (before, after) = (rmse(&noisy, &reference), rmse(&denoised, &reference));
+        assert!(before > 0.02, "{}", before);
+        assert!(after <= 1.01 * before, "{} {}", before, after);
+        assert!(DenoiseGuides::from_aovs(&images[..1]).is_err());
+    }
+
+    #[test]
+    fn denoising_preserves_edges() {
+        let (reference, noisy, aovs) = noisy_default_scene(PixelSize::new(96, 54));
+        let guides = DenoiseGuides::from_aovs(&aovs).unwrap();
+
+        let denoised = Denoiser::new(2.0).denoise(&noisy, &guides);
+
+        // the center of the blue sphere, and the white sphere right next to its edge
+        let blue = Pixel::new(48, 27);
+        let white = Pixel::new(48 - 11, 27);
+        for pixel in [blue, white].iter() {
+            let expected = reference.get(*pixel);
+            let actual = denoised.get(*pixel);
+            for i in 0..3 {
+                assert!(
+                    (expected[i] - actual[i]).abs() < 0.1,
+                    "{:?} {:?}",
+                    expected,
+                    actual
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use crate::rendering::aov::{AovBuffers, AovImage, AovSample, AovSet};
+use crate::rendering::hdr::encode;
+use crate::rendering::{
+    Color, Filter, HdrBuffer, Pixel, PixelRect, PixelSize, RayStats, RenderTarget, SubPixel,
+};
+use nameof::name_of_type;
+use std::fmt::{Display, Formatter};
+
+/// The accumulated, weighted contributions of the samples to a pixel, as linear radiance.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct FilmPixel {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub weight: f64,
+    /// The unweighted sum of the contributions, for when the weights cancel out.
+    pub unweighted: [f64; 3],
+    /// The number of contributions.
+    pub count: u32,
+}
+
+impl FilmPixel {
+    /// Returns the reconstructed value, or `None` if no sample contributed. Filters with
+    /// negative lobes can leave a pixel without positive weight: it then falls back to the
+    /// unweighted average of the contributions, as a box filter would.
+    pub(crate) fn value(&self) -> Option<[f64; 3]> {
+        if self.weight > 0.0 {
+            Some([
+                self.r / self.weight,
+                self.g / self.weight,
+                self.b / self.weight,
+            ])
+        } else if self.count > 0 {
+            let count = self.count as f64;
+            Some(self.unweighted.map(|v| v / count))
+        } else {
+            None
+        }
+    }
+}
+
+/// Accumulates radiance samples into pixels, using a reconstruction [Filter].
+///
+/// The radiance is linear and unclamped: it is only quantized when resolved into a
+/// [RenderTarget].
+///
+/// Each sample contributes to all the pixels whose center is within the radius of the
+/// filter, so a sample can contribute to neighboring pixels.
+///
+/// A [Film] can be restricted to a region: only the pixels of the region are resolved.
+/// Samples taken around the region still contribute to its edges, through the filter.
+///
+/// A [Film] can also accumulate [Aov](crate::rendering::Aov)s. They are not filtered:
+/// each sample only contributes to the pixel it was taken for.
+///
+/// The [RayStats] of the samples are counted along with them.
+#[derive(Debug, Clone)]
+pub struct Film {
+    size: PixelSize,
+    filter: Filter,
+    pixels: Vec<FilmPixel>,
+    aovs: Option<AovBuffers>,
+    region: Option<PixelRect>,
+    stats: RayStats,
+}
+
+impl Display for Film {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}*{}, filter: {})",
+            name_of_type!(Film),
+            self.size.width,
+            self.size.height,
+            self.filter
+        )
+    }
+}
+
+impl Film {
+    pub fn new(size: PixelSize, filter: Filter) -> Self {
+        Film {
+            size,
+            filter,
+            pixels: vec![FilmPixel::default(); (size.width * size.height) as usize],
+            aovs: None,
+            region: None,
+            stats: RayStats::default(),
+        }
+    }
+
+    /// Restricts the [Film] to a region, or to the whole image if `None`.
+    pub fn with_region(self, region: Option<PixelRect>) -> Self {
+        let mut s = self;
+        s.region = region;
+        s
+    }
+
+    /// Returns the pixels that are resolved.
+    pub fn region(&self) -> PixelRect {
+        self.region.unwrap_or_else(|| PixelRect::full(self.size))
+    }
+
+    /// Returns the pixels to sample, so that the [region](Film::region) receives the same
+    /// contributions as in a full render: the region, and the pixels within the filter radius.
+    pub fn sampled_region(&self) -> PixelRect {
+        let margin = (self.filter.radius + 0.5).ceil() as u32;
+        match self.region {
+            Some(region) => region.expand(margin, self.size),
+            None => PixelRect::full(self.size),
+        }
+    }
+
+    /// Enables the accumulation of the [AovSet].
+    pub fn with_aovs(self, aovs: AovSet) -> Self {
+        let mut s = self;
+        s.aovs = if aovs.is_empty() {
+            None
+        } else {
+            Some(AovBuffers::new(s.size, aovs))
+        };
+        s
+    }
+
+    pub fn size(&self) -> PixelSize {
+        self.size
+    }
+
+    /// Discards all the samples, e.g to restart a progressive render.
+    pub fn clear(&mut self) {
+        for p in self.pixels.iter_mut() {
+            *p = FilmPixel::default();
+        }
+        if let Some(aovs) = &mut self.aovs {
+            aovs.clear();
+        }
+        self.stats = RayStats::default();
+    }
+
+    /// Gets the rays traced for the samples of this [Film].
+    pub fn ray_stats(&self) -> RayStats {
+        self.stats
+    }
+
+    pub(crate) fn ray_stats_mut(&mut self) -> &mut RayStats {
+        &mut self.stats
+    }
+
+    /// Gets the accumulated contributions of all the pixels, row by row.
+    pub(crate) fn pixels(&self) -> &[FilmPixel] {
+        &self.pixels
+    }
+
+    /// Replaces the accumulated contributions of all the pixels, e.g to resume a render.
+    pub(crate) fn restore(&mut self, pixels: Vec<FilmPixel>) {
+        assert_eq!(self.pixels.len(), pixels.len());
+        self.pixels = pixels;
+    }
+
+    /// Gets the accumulated contributions of the [Pixel].
+    pub(crate) fn pixel(&self, pixel: Pixel) -> FilmPixel {
+        self.pixels[self.index(pixel)]
+    }
+
+    /// Splats a radiance sample taken at the specified position. Pixel centers are at
+    /// integer coordinates.
+    pub(crate) fn add_sample(&mut self, position: SubPixel, radiance: [f32; 3]) {
+        let radius = self.filter.radius;
+        let x0 = (position.x - radius).ceil().max(0.0) as u32;
+        let y0 = (position.y - radius).ceil().max(0.0) as u32;
+        let x1 = (position.x + radius)
+            .floor()
+            .min(self.size.width as f32 - 1.0);
+        let y1 = (position.y + radius)
+            .floor()
+            .min(self.size.height as f32 - 1.0);
+        if x1 < 0.0 || y1 < 0.0 {
+            return;
+        }
+
+        for y in y0..=(y1 as u32) {
+            for x in x0..=(x1 as u32) {
+                let weight = self
+                    .filter
+                    .evaluate(x as f32 - position.x, y as f32 - position.y);
+                if weight == 0.0 {
+                    continue;
+                }
+
+                let weight = weight as f64;
+                let index = self.index(Pixel::new(x, y));
+                let p = &mut self.pixels[index];
+                p.r += radiance[0] as f64 * weight;
+                p.g += radiance[1] as f64 * weight;
+                p.b += radiance[2] as f64 * weight;
+                p.weight += weight;
+                for (sum, v) in p.unweighted.iter_mut().zip(radiance.iter()) {
+                    *sum += *v as f64;
+                }
+                p.count += 1;
+            }
+        }
+    }
+
+    /// Adds the AOV values of a sample taken for the [Pixel], if AOVs are enabled.
+    pub(crate) fn add_aov_sample(&mut self, pixel: Pixel, sample: &AovSample) {
+        if let Some(aovs) = &mut self.aovs {
+            aovs.add(pixel, sample);
+        }
+    }
+
+    /// Gets the accumulated AOVs, if AOVs are enabled.
+    pub(crate) fn aov_buffers(&self) -> Option<&AovBuffers> {
+        self.aovs.as_ref()
+    }
+
+    pub(crate) fn aov_buffers_mut(&mut self) -> Option<&mut AovBuffers> {
+        self.aovs.as_mut()
+    }
+
+    /// Returns the accumulated AOV images, if any.
+    pub fn aov_images(&self) -> Vec<AovImage> {
+        self.aovs
+            .as_ref()
+            .map(AovBuffers::images)
+            .unwrap_or_default()
+    }
+
+    /// Returns the reconstructed radiance of the [Pixel], or `None` if no sample contributed to it.
+    pub fn radiance(&self, pixel: Pixel) -> Option<[f32; 3]> {
+        self.pixel(pixel).value().map(|v| v.map(|c| c as f32))
+    }
+
+    /// Returns the reconstructed color of the [Pixel], encoded and clamped to 8-bit sRGB,
+    /// or `None` if no sample contributed to it.
+    pub fn get(&self, pixel: Pixel) -> Option<Color> {
+        self.radiance(pixel).map(encode)
+    }
+
+    /// Writes the reconstructed region into the [RenderTarget]. Pixels outside of the
+    /// region, or without any contribution, are left untouched.
+    pub fn resolve(&self, target: &mut dyn RenderTarget) {
+        for pixel in self.region().pixels() {
+            if let Some(color) = self.get(pixel) {
+                target.set(pixel, color);
+            }
+        }
+    }
+
+    /// Writes the reconstructed region into the [HdrBuffer], as linear, unclamped values.
+    /// Pixels outside of the region, or without any contribution, are left untouched.
+    pub fn resolve_hdr(&self, target: &mut HdrBuffer) {
+        for pixel in self.region().pixels() {
+            if let Some(radiance) = self.radiance(pixel) {
+                target.set(pixel, radiance);
+            }
+        }
+    }
+
+    fn index(&self, p: Pixel) -> usize {
+        (p.x + p.y * self.size.width) as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rendering::filter::FilterKind;
+    use crate::rendering::hdr::linear;
+    use crate::rendering::{BLACK, WHITE};
+
+    #[test]
+    fn box_filter_averages_samples_within_the_pixel() {
+        let mut film = Film::new(PixelSize::new(3, 3), Filter::new(FilterKind::Box));
+
+        film.add_sample(SubPixel::new(1.2, 1.2), linear(WHITE));
+        film.add_sample(SubPixel::new(0.8, 0.8), linear(BLACK));
+
+        // averaged in linear light, then encoded
+        assert_eq!(Some([0.5; 3]), film.radiance(Pixel::new(1, 1)));
+        assert_eq!(Some(Color::new(188, 188, 188)), film.get(Pixel::new(1, 1)));
+        assert_eq!(None, film.get(Pixel::new(0, 0)));
+    }
+
+    #[test]
+    fn wide_filters_contribute_to_neighbors() {
+        let mut film = Film::new(PixelSize::new(3, 3), Filter::new(FilterKind::Tent));
+
+        film.add_sample(SubPixel::new(1.4, 1.0), linear(WHITE));
+
+        assert_eq!(Some(WHITE), film.get(Pixel::new(1, 1)));
+        assert_eq!(Some(WHITE), film.get(Pixel::new(2, 1)));
+        assert_eq!(None, film.get(Pixel::new(0, 1)));
+        assert!(film.pixel(Pixel::new(1, 1)).weight > film.pixel(Pixel::new(2, 1)).weight);
+    }
+
+    #[test]
+    fn pixels_without_positive_weight_are_not_skipped() {
+        let mut film = Film::new(PixelSize::new(5, 1), Filter::new(FilterKind::Lanczos));
+
+        // only in the negative lobe of the filter of the first pixel
+        film.add_sample(SubPixel::new(1.5, 0.0), linear(WHITE));
+        assert!(film.pixel(Pixel::new(0, 0)).weight < 0.0);
+        assert_eq!(Some(WHITE), film.get(Pixel::new(0, 0)));
+
+        let mut hdr = HdrBuffer::new(5, 1);
+        film.resolve_hdr(&mut hdr);
+        assert_eq!([1.0; 3], hdr.get(Pixel::new(0, 0)));
+    }
+
+    #[test]
+    fn radiance_is_not_clamped() {
+        let mut film = Film::new(PixelSize::new(1, 1), Filter::new(FilterKind::Box));
+
+        film.add_sample(SubPixel::new(0.0, 0.0), [4.0, 0.5, -1.0]);
+
+        let mut hdr = HdrBuffer::new(1, 1);
+        film.resolve_hdr(&mut hdr);
+        assert_eq!([4.0, 0.5, -1.0], hdr.get(Pixel::new(0, 0)));
+        assert_eq!(Some(Color::new(255, 188, 0)), film.get(Pixel::new(0, 0)));
+    }
+}
@@ -0,0 +1,204 @@
+//! Pixel reconstruction filters.
+use std::f32::consts::PI;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The shapes of [Filter]s.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum FilterKind {
+    /// Every sample within the radius has the same weight.
+    #[default]
+    Box,
+    /// The weight decreases linearly with the distance to the pixel center.
+    Tent,
+    /// A truncated gaussian, with a standard deviation of a third of the radius.
+    Gaussian,
+    /// The Mitchell-Netravali cubic, with B = C = 1/3.
+    Mitchell,
+    /// A sinc windowed by a sinc as wide as the radius.
+    Lanczos,
+}
+
+impl FilterKind {
+    /// Returns the radius commonly used with this kind of filter, in pixels.
+    pub fn default_radius(&self) -> f32 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 3.0,
+        }
+    }
+}
+
+/// A separable reconstruction filter, that weights the samples around a pixel.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    /// The radius of the filter, in pixels.
+    pub radius: f32,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::new(FilterKind::default())
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self.kind {
+            FilterKind::Box => "box",
+            FilterKind::Tent => "tent",
+            FilterKind::Gaussian => "gaussian",
+            FilterKind::Mitchell => "mitchell",
+            FilterKind::Lanczos => "lanczos",
+        };
+        write!(f, "{}:{}", name, self.radius)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    /// Parses a filter of the form `name[:radius]`, e.g `gaussian:1.5` or `mitchell`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let kind = match parts
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .as_str()
+        {
+            "box" => FilterKind::Box,
+            "tent" | "triangle" => FilterKind::Tent,
+            "gaussian" => FilterKind::Gaussian,
+            "mitchell" | "mitchell-netravali" => FilterKind::Mitchell,
+            "lanczos" => FilterKind::Lanczos,
+            _ => return Err(format!("unknown filter: {}", s)),
+        };
+
+        match parts.next() {
+            None => Ok(Filter::new(kind)),
+            Some(radius) => match radius.trim().parse::<f32>() {
+                Ok(radius) if radius > 0.0 && radius <= Filter::MAX_RADIUS => {
+                    Ok(Filter::with_radius(kind, radius))
+                }
+                _ => Err(format!("invalid filter radius: {}", radius)),
+            },
+        }
+    }
+}
+
+impl Filter {
+    /// The largest radius accepted, in pixels.
+    pub const MAX_RADIUS: f32 = 32.0;
+
+    /// Creates a [Filter] with the default radius of its kind.
+    pub fn new(kind: FilterKind) -> Self {
+        Filter::with_radius(kind, kind.default_radius())
+    }
+
+    pub fn with_radius(kind: FilterKind, radius: f32) -> Self {
+        Filter { kind, radius }
+    }
+
+    /// Returns the weight of a sample at the offset (in pixels) from the pixel center.
+    /// The weight can be negative for filters with negative lobes.
+    pub fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let x = x.abs();
+        let r = self.radius;
+        if x > r {
+            return 0.0;
+        }
+
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => r - x,
+            FilterKind::Gaussian => {
+                let sigma = r / 3.0;
+                let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(r)).max(0.0)
+            }
+            FilterKind::Mitchell => mitchell(2.0 * x / r),
+            FilterKind::Lanczos => sinc(x) * sinc(x / r),
+        }
+    }
+}
+
+/// The Mitchell-Netravali cubic with B = C = 1/3, for x in [0, 2].
+fn mitchell(x: f32) -> f32 {
+    const B: f32 = 1.0 / 3.0;
+    const C: f32 = 1.0 / 3.0;
+
+    let value = if x > 1.0 {
+        (-B - 6.0 * C) * x * x * x
+            + (6.0 * B + 30.0 * C) * x * x
+            + (-12.0 * B - 48.0 * C) * x
+            + (8.0 * B + 24.0 * C)
+    } else {
+        (12.0 - 9.0 * B - 6.0 * C) * x * x * x
+            + (-18.0 + 12.0 * B + 6.0 * C) * x * x
+            + (6.0 - 2.0 * B)
+    };
+
+    value / 6.0
+}
+
+/// The normalized sinc function.
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Ok(Filter::with_radius(FilterKind::Box, 0.5)), "box".parse());
+        assert_eq!(
+            Ok(Filter::with_radius(FilterKind::Gaussian, 2.0)),
+            "Gaussian:2".parse()
+        );
+        assert!("sharp".parse::<Filter>().is_err());
+        assert!("tent:-1".parse::<Filter>().is_err());
+        assert!("gaussian:inf".parse::<Filter>().is_err());
+        assert!("gaussian:NaN".parse::<Filter>().is_err());
+        assert!("box:1000".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn filters_peak_at_center_and_vanish_outside_radius() {
+        let kinds = [
+            FilterKind::Box,
+            FilterKind::Tent,
+            FilterKind::Gaussian,
+            FilterKind::Mitchell,
+            FilterKind::Lanczos,
+        ];
+        for kind in kinds.iter() {
+            let filter = Filter::new(*kind);
+            let center = filter.evaluate(0.0, 0.0);
+
+            assert!(center > 0.0, "{}", filter);
+            assert!(filter.evaluate(0.3, 0.1) <= center, "{}", filter);
+            assert_eq!(
+                0.0,
+                filter.evaluate(filter.radius + 0.01, 0.0),
+                "{}",
+                filter
+            );
+        }
+    }
+}
@@ -1,6 +1,7 @@
+use crate::error::Error;
+use crate::rendering::{Color, Pixel, PixelSize};
 use nameof::name_of_type;
 use std::fmt::{Display, Formatter};
-use crate::rendering::{Color, Pixel, PixelSize};
 
 /// Types that can be rendered into.
 pub trait RenderTarget {
@@ -80,7 +81,12 @@ impl RenderTarget for FrameBuffer {
 }
 
 impl FrameBuffer {
-    /// Constructs a [FrameBuffer] with the specified pixel size
+    /// Constructs a [FrameBuffer] with the specified pixel size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size is empty: use [FrameBuffer::try_new] for sizes that are not known
+    /// to be valid.
     pub fn new(width: u32, height: u32) -> Self {
         assert!(width > 0);
         assert!(height > 0);
@@ -102,6 +108,46 @@ impl FrameBuffer {
         }
     }
 
+    /// Constructs a [FrameBuffer], or returns an error if the size is empty.
+    pub fn try_new(width: u32, height: u32) -> Result<Self, Error> {
+        PixelSize::new(width, height).check_not_empty()?;
+        Ok(FrameBuffer::new(width, height))
+    }
+
+    /// Copies the pixels of `src` into this [FrameBuffer], with the top-left corner at `origin`.
+    /// Pixels that fall outside of this [FrameBuffer] are ignored.
+    pub fn blit(&mut self, src: &dyn RenderTarget, origin: Pixel) {
+        let width = src
+            .size()
+            .width
+            .min(self.size.width.saturating_sub(origin.x));
+        let height = src
+            .size()
+            .height
+            .min(self.size.height.saturating_sub(origin.y));
+
+        for y in 0..height {
+            for x in 0..width {
+                let color = src.get(Pixel::new(x, y));
+                self.set(Pixel::new(origin.x + x, origin.y + y), color);
+            }
+        }
+    }
+
+    /// Scales the pixels of `src` to cover this whole [FrameBuffer], using nearest-neighbor
+    /// sampling (e.g to display a low-resolution preview).
+    pub fn blit_scaled(&mut self, src: &dyn RenderTarget) {
+        let (sw, sh) = (src.size().width, src.size().height);
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let sx = (x * sw / self.size.width).min(sw - 1);
+                let sy = (y * sh / self.size.height).min(sh - 1);
+                let color = src.get(Pixel::new(sx, sy));
+                self.set(Pixel::new(x, y), color);
+            }
+        }
+    }
+
     fn offset(&self, p: Pixel) -> usize {
         ((3 * p.x) + p.y * self.size.width * 3) as usize
     }
@@ -118,6 +164,7 @@ mod test {
         assert_eq!(10, buffer.size().width);
         assert_eq!(15, buffer.size().height);
         assert_eq!(10 * 15 * 3, buffer.pixels.len());
+        assert!(FrameBuffer::try_new(0, 15).is_err());
     }
 
     #[test]
@@ -132,4 +179,18 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn blit() {
+        let mut src = FrameBuffer::new(4, 4);
+        src.clear(RED);
+        let mut dst = FrameBuffer::new(6, 6);
+
+        dst.blit(&src, Pixel::new(4, 1));
+
+        assert_eq!(Color::default(), dst.get(Pixel::new(3, 1)));
+        assert_eq!(RED, dst.get(Pixel::new(4, 1)));
+        assert_eq!(RED, dst.get(Pixel::new(5, 4)));
+        assert_eq!(Color::default(), dst.get(Pixel::new(5, 5)));
+    }
 }
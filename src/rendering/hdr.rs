@@ -0,0 +1,220 @@
+//! Linear, unclamped floating-point images.
+use crate::error::Error;
+use crate::rendering::{Color, FrameBuffer, Pixel, PixelRect, PixelSize, RenderTarget};
+use nameof::name_of_type;
+use std::fmt::{Display, Formatter};
+
+/// Decodes an sRGB-encoded value (where 1 is white) into linear light. Values outside
+/// of [0, 1] are extended symmetrically, so that they are preserved.
+pub fn srgb_to_linear(v: f32) -> f32 {
+    let a = v.abs();
+    let linear = if a <= 0.04045 {
+        a / 12.92
+    } else {
+        ((a + 0.055) / 1.055).powf(2.4)
+    };
+    linear.copysign(v)
+}
+
+/// Encodes a linear value into sRGB (where 1 is white). Values outside of [0, 1] are
+/// extended symmetrically.
+pub fn linear_to_srgb(v: f32) -> f32 {
+    let a = v.abs();
+    let srgb = if a <= 0.003_130_8 {
+        a * 12.92
+    } else {
+        1.055 * a.powf(1.0 / 2.4) - 0.055
+    };
+    srgb.copysign(v)
+}
+
+/// Decodes an sRGB [Color] into linear values.
+pub fn linear(color: Color) -> [f32; 3] {
+    let decode = |c: u8| srgb_to_linear(c as f32 / 255.0);
+    [decode(color.r), decode(color.g), decode(color.b)]
+}
+
+/// Returns the relative luminance (Rec. 709) of linear values.
+pub fn luminance(rgb: [f32; 3]) -> f32 {
+    0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2]
+}
+
+/// Encodes linear values into a [Color], clamping the values outside of [0, 1].
+pub fn encode(rgb: [f32; 3]) -> Color {
+    let channel = |v: f32| (linear_to_srgb(v).clamp(0.0, 1.0) * 255.0).round() as u8;
+    Color::new(channel(rgb[0]), channel(rgb[1]), channel(rgb[2]))
+}
+
+/// An RGB image with linear, unclamped `f32` channels, suitable for compositing.
+///
+/// [Color]s are sRGB-encoded: they are decoded when written into a [HdrBuffer], and
+/// encoded (and clamped) when converting it to a [FrameBuffer].
+#[derive(Debug, Clone)]
+pub struct HdrBuffer {
+    size: PixelSize,
+    pixels: Vec<f32>,
+}
+
+impl Display for HdrBuffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}*{} linear RGB)",
+            name_of_type!(HdrBuffer),
+            self.size.width,
+            self.size.height
+        )
+    }
+}
+
+impl HdrBuffer {
+    /// Constructs a black [HdrBuffer] with the specified pixel size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size is empty: use [HdrBuffer::try_new] for sizes that are not known
+    /// to be valid.
+    pub fn new(width: u32, height: u32) -> Self {
+        assert!(width > 0);
+        assert!(height > 0);
+
+        HdrBuffer {
+            size: PixelSize::new(width, height),
+            pixels: vec![0.0; (width * height * 3) as usize],
+        }
+    }
+
+    /// Constructs a [HdrBuffer], or returns an error if the size is empty.
+    pub fn try_new(width: u32, height: u32) -> Result<Self, Error> {
+        PixelSize::new(width, height).check_not_empty()?;
+        Ok(HdrBuffer::new(width, height))
+    }
+
+    /// Decodes the pixels of the [RenderTarget] into a [HdrBuffer].
+    pub fn from_render_target(src: &dyn RenderTarget) -> Self {
+        let mut hdr = HdrBuffer::new(src.size().width, src.size().height);
+        for y in 0..src.size().height {
+            for x in 0..src.size().width {
+                let pixel = Pixel::new(x, y);
+                hdr.set_color(pixel, src.get(pixel));
+            }
+        }
+        hdr
+    }
+
+    pub fn size(&self) -> PixelSize {
+        self.size
+    }
+
+    /// Gets the interleaved RGB values, row by row from the top.
+    pub fn data(&self) -> &[f32] {
+        &self.pixels
+    }
+
+    pub fn get(&self, pixel: Pixel) -> [f32; 3] {
+        let i = self.offset(pixel);
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+
+    pub fn set(&mut self, pixel: Pixel, rgb: [f32; 3]) {
+        let i = self.offset(pixel);
+        self.pixels[i..i + 3].copy_from_slice(&rgb);
+    }
+
+    /// Sets the [Pixel] with the linear value of the [Color].
+    pub fn set_color(&mut self, pixel: Pixel, color: Color) {
+        self.set(pixel, linear(color));
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                self.set_color(Pixel::new(x, y), color);
+            }
+        }
+    }
+
+    /// Copies the pixels of `src` into this [HdrBuffer], with the top-left corner at `origin`.
+    /// Pixels that fall outside of this [HdrBuffer] are ignored.
+    pub fn blit(&mut self, src: &HdrBuffer, origin: Pixel) {
+        let width = src.size.width.min(self.size.width.saturating_sub(origin.x));
+        let height = src
+            .size
+            .height
+            .min(self.size.height.saturating_sub(origin.y));
+
+        for y in 0..height {
+            for x in 0..width {
+                let rgb = src.get(Pixel::new(x, y));
+                self.set(Pixel::new(origin.x + x, origin.y + y), rgb);
+            }
+        }
+    }
+
+    /// Returns a copy of the pixels within the [PixelRect], or an error if it is empty.
+    pub fn crop(&self, rect: PixelRect) -> Result<HdrBuffer, Error> {
+        let mut hdr = HdrBuffer::try_new(rect.size.width, rect.size.height)?;
+        for pixel in rect.pixels() {
+            let rgb = self.get(pixel);
+            hdr.set(
+                Pixel::new(pixel.x - rect.origin.x, pixel.y - rect.origin.y),
+                rgb,
+            );
+        }
+        Ok(hdr)
+    }
+
+    /// Encodes the pixels into 8-bit sRGB, clamping the values outside of [0, 1].
+    pub fn to_frame_buffer(&self) -> FrameBuffer {
+        let mut fb = FrameBuffer::new(self.size.width, self.size.height);
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let pixel = Pixel::new(x, y);
+                fb.set(pixel, encode(self.get(pixel)));
+            }
+        }
+
+        fb
+    }
+
+    fn offset(&self, p: Pixel) -> usize {
+        (3 * (p.x + p.y * self.size.width)) as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn srgb_roundtrip() {
+        let mut fb = FrameBuffer::new(256, 1);
+        for x in 0..256 {
+            fb.set(Pixel::new(x, 0), Color::new(x as u8, 0, 255 - x as u8));
+        }
+
+        let hdr = HdrBuffer::from_render_target(&fb);
+
+        assert_eq!(fb.bytes(), hdr.to_frame_buffer().bytes());
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+        assert_eq!(-srgb_to_linear(0.5), srgb_to_linear(-0.5));
+    }
+    #[test]
+    fn crop() {
+        let mut hdr = HdrBuffer::new(4, 3);
+        hdr.set_color(Pixel::new(2, 1), Color::new(255, 0, 0));
+
+        let cropped = hdr
+            .crop(PixelRect::new(Pixel::new(1, 1), PixelSize::new(2, 2)))
+            .unwrap();
+
+        assert_eq!(PixelSize::new(2, 2), cropped.size());
+        assert_eq!(
+            Color::new(255, 0, 0),
+            cropped.to_frame_buffer().get(Pixel::new(1, 0))
+        );
+        assert!(hdr
+            .crop(PixelRect::new(Pixel::new(1, 1), PixelSize::new(0, 2)))
+            .is_err());
+    }
+}
@@ -0,0 +1,223 @@
+//! Color lookup tables, in the Adobe/Resolve `.cube` format.
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+/// A 1D or 3D color lookup table, applied to values in its domain (usually [0, 1]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut {
+    pub title: Option<String>,
+    kind: LutKind,
+    /// The number of entries along each axis.
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    /// The RGB entries, with red varying fastest.
+    table: Vec<[f32; 3]>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum LutKind {
+    /// One curve per channel.
+    Curves,
+    /// A lattice in the RGB cube, interpolated trilinearly.
+    Cube,
+}
+
+impl Display for Lut {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            LutKind::Curves => "1D",
+            LutKind::Cube => "3D",
+        };
+        match &self.title {
+            Some(title) => write!(f, "{} LUT \"{}\" ({} entries)", kind, title, self.size),
+            None => write!(f, "{} LUT ({} entries)", kind, self.size),
+        }
+    }
+}
+
+impl Lut {
+    /// Loads a `.cube` file.
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+        Lut::parse(&text).map_err(|e| format!("invalid LUT {}: {}", path, e))
+    }
+
+    /// Parses the content of a `.cube` file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut title = None;
+        let mut kind = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = Vec::new();
+
+        let parse_rgb = |values: &[&str]| -> Result<[f32; 3], String> {
+            if values.len() != 3 {
+                return Err(format!("expected 3 values, got {}", values.len()));
+            }
+            let mut rgb = [0.0; 3];
+            for (v, s) in rgb.iter_mut().zip(values) {
+                *v = s.parse().map_err(|_| format!("invalid number: {}", s))?;
+            }
+            Ok(rgb)
+        };
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            let values: Vec<&str> = words.collect();
+            match keyword {
+                "TITLE" => title = Some(line[5..].trim().trim_matches('"').to_string()),
+                "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                    let size: usize = values
+                        .first()
+                        .and_then(|v| v.parse().ok())
+                        .filter(|s| *s >= 2)
+                        .ok_or_else(|| format!("invalid size: {}", line))?;
+                    let k = if keyword == "LUT_1D_SIZE" {
+                        LutKind::Curves
+                    } else {
+                        LutKind::Cube
+                    };
+                    kind = Some((k, size));
+                }
+                "DOMAIN_MIN" => domain_min = parse_rgb(&values)?,
+                "DOMAIN_MAX" => domain_max = parse_rgb(&values)?,
+                // other keywords, e.g LUT_1D_INPUT_RANGE, are not supported
+                k if k.starts_with(char::is_alphabetic) => {
+                    return Err(format!("unsupported keyword: {}", k))
+                }
+                _ => {
+                    let mut entry = vec![keyword];
+                    entry.extend(values);
+                    table.push(parse_rgb(&entry)?);
+                }
+            }
+        }
+
+        let (kind, size) = kind.ok_or("missing LUT_1D_SIZE or LUT_3D_SIZE")?;
+        let expected = match kind {
+            LutKind::Curves => size,
+            LutKind::Cube => size * size * size,
+        };
+        if table.len() != expected {
+            return Err(format!(
+                "expected {} entries, got {}",
+                expected,
+                table.len()
+            ));
+        }
+        if (0..3).any(|i| domain_max[i] <= domain_min[i]) {
+            return Err(String::from("empty domain"));
+        }
+
+        Ok(Lut {
+            title,
+            kind,
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    /// Looks up the color. Values outside of the domain are clamped to it.
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let last = (self.size - 1) as f32;
+        // the continuous position in the table along each axis
+        let mut pos = [0.0; 3];
+        for i in 0..3 {
+            let t = (rgb[i] - self.domain_min[i]) / (self.domain_max[i] - self.domain_min[i]);
+            pos[i] = t.clamp(0.0, 1.0) * last;
+        }
+
+        match self.kind {
+            LutKind::Curves => {
+                let mut out = [0.0; 3];
+                for i in 0..3 {
+                    let (i0, i1, t) = self.lerp_indices(pos[i]);
+                    out[i] = self.table[i0][i] * (1.0 - t) + self.table[i1][i] * t;
+                }
+                out
+            }
+            LutKind::Cube => {
+                let (r0, r1, tr) = self.lerp_indices(pos[0]);
+                let (g0, g1, tg) = self.lerp_indices(pos[1]);
+                let (b0, b1, tb) = self.lerp_indices(pos[2]);
+                let at =
+                    |r: usize, g: usize, b: usize| self.table[r + self.size * (g + self.size * b)];
+                let lerp = |a: [f32; 3], b: [f32; 3], t: f32| {
+                    [
+                        a[0] + (b[0] - a[0]) * t,
+                        a[1] + (b[1] - a[1]) * t,
+                        a[2] + (b[2] - a[2]) * t,
+                    ]
+                };
+
+                let c00 = lerp(at(r0, g0, b0), at(r1, g0, b0), tr);
+                let c10 = lerp(at(r0, g1, b0), at(r1, g1, b0), tr);
+                let c01 = lerp(at(r0, g0, b1), at(r1, g0, b1), tr);
+                let c11 = lerp(at(r0, g1, b1), at(r1, g1, b1), tr);
+                lerp(lerp(c00, c10, tg), lerp(c01, c11, tg), tb)
+            }
+        }
+    }
+
+    /// Returns the entries surrounding the position, and the interpolation factor between them.
+    fn lerp_indices(&self, pos: f32) -> (usize, usize, f32) {
+        let i0 = (pos.floor() as usize).min(self.size - 2);
+        (i0, i0 + 1, pos - i0 as f32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A 2x2x2 cube that inverts the colors.
+    const INVERT: &str = "
+        # comment
+        TITLE \"invert\"
+        LUT_3D_SIZE 2
+        1 1 1
+        0 1 1
+        1 0 1
+        0 0 1
+        1 1 0
+        0 1 0
+        1 0 0
+        0 0 0
+    ";
+
+    #[test]
+    fn parse_and_apply_3d() {
+        let lut = Lut::parse(INVERT).unwrap();
+
+        assert_eq!(Some("invert"), lut.title.as_deref());
+        let out = lut.apply([0.25, 1.0, 0.5]);
+        for (expected, actual) in [0.75, 0.0, 0.5].iter().zip(out.iter()) {
+            assert!((expected - actual).abs() < 1e-6);
+        }
+        assert_eq!([1.0, 1.0, 1.0], lut.apply([-3.0, -1.0, 0.0]));
+    }
+
+    #[test]
+    fn parse_and_apply_1d() {
+        let lut = Lut::parse("LUT_1D_SIZE 3\n0 0 0\n0.5 0.25 1\n1 1 1").unwrap();
+
+        assert_eq!([0.25, 0.125, 0.5], lut.apply([0.25, 0.25, 0.25]));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Lut::parse("LUT_3D_SIZE 2\n0 0 0").is_err());
+        assert!(Lut::parse("0 0 0\n1 1 1").is_err());
+        assert!(Lut::parse("LUT_1D_SIZE 2\n0 0\n1 1 1").is_err());
+    }
+}
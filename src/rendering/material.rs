@@ -1,24 +1,69 @@
+use crate::math::random::hash_combine;
+use crate::rendering::hdr::linear;
 use crate::rendering::Color;
 
 #[derive(Debug, Default)]
 pub struct Material {
     diffuse: Color,
+    /// The light emitted, in multiples of the diffuse color.
+    emission: f32,
 }
 
 impl Material {
     pub const fn from_diffuse(diffuse: Color) -> Self {
-        Material { diffuse }
+        Material {
+            diffuse,
+            emission: 0.0,
+        }
+    }
+
+    /// Makes the surface emit light, e.g 4 for a lamp 4 times brighter than white. The
+    /// emitted light is not clamped, so that it can exceed the range of 8-bit images.
+    pub fn with_emission(self, emission: f32) -> Self {
+        let mut s = self;
+        s.emission = emission;
+        s
+    }
+
+    /// Replaces the diffuse color, keeping the other parameters.
+    pub fn with_diffuse(self, diffuse: Color) -> Self {
+        let mut s = self;
+        s.diffuse = diffuse;
+        s
     }
 
     pub fn diffuse_color(&self) -> Color {
         self.diffuse
     }
+
+    pub fn emission(&self) -> f32 {
+        self.emission
+    }
+
+    /// Returns the linear radiance of the surface: its diffuse color, plus the light
+    /// it emits.
+    pub fn radiance(&self) -> [f32; 3] {
+        linear(self.diffuse).map(|v| v * (1.0 + self.emission))
+    }
+
+    /// Returns an identifier derived from the parameters of the material: equal
+    /// materials have the same identifier.
+    pub fn id(&self) -> u32 {
+        hash_combine(&[
+            self.diffuse.r as u32,
+            self.diffuse.g as u32,
+            self.diffuse.b as u32,
+            self.emission.to_bits(),
+        ])
+        .max(1)
+    }
 }
 
 impl Clone for Material {
     fn clone(&self) -> Self {
         Self {
-            diffuse: self.diffuse
+            diffuse: self.diffuse,
+            emission: self.emission,
         }
     }
 }
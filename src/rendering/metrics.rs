@@ -0,0 +1,275 @@
+//! Error metrics between an image and a reference, e.g to compare samplers.
+use crate::error::Error;
+use crate::rendering::adaptive::heat_color;
+use crate::rendering::hdr::srgb_to_linear;
+use crate::rendering::post::{blur, gaussian_kernel};
+use crate::rendering::{FrameBuffer, HdrBuffer, Pixel, PixelSize, RenderTarget};
+use nameof::name_of_type;
+use std::fmt::{Display, Formatter};
+
+/// The standard deviation of the window of SSIM, in pixels.
+const SSIM_SIGMA: f32 = 1.5;
+
+/// The standard deviation of the filter applied before computing color differences, as a
+/// rough model of the contrast sensitivity of the eye, in pixels.
+const FLIP_SIGMA: f32 = 1.0;
+
+/// The exponent applied to color differences, that compresses the large ones.
+const FLIP_EXPONENT: f32 = 0.7;
+
+/// The fraction of the maximum color difference, and the error it maps to: smaller
+/// differences are mapped linearly below this error, larger ones above it.
+const FLIP_KNEE: (f32, f32) = (0.4, 0.95);
+
+/// The errors between an 8-bit image and a reference of the same size.
+///
+/// ```
+/// use raytracer::{Comparison, FrameBuffer, RenderTarget, RED};
+///
+/// let reference = FrameBuffer::new(8, 8);
+/// let mut image = FrameBuffer::new(8, 8);
+/// assert_eq!(0.0, Comparison::new(&reference, &image)?.mse);
+///
+/// image.clear(RED);
+/// assert!(Comparison::new(&reference, &image)?.flip > 0.5);
+/// # Ok::<(), raytracer::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    /// The mean squared error, where channel values range from 0 to 1.
+    pub mse: f32,
+    /// The peak signal-to-noise ratio, in dB. It is infinite for identical images.
+    pub psnr: f32,
+    /// The mean structural similarity of the channels, from -1 to 1 (identical images).
+    pub ssim: f32,
+    /// The mean perceptual color error, from 0 to 1, see [Comparison::flip_errors].
+    pub flip: f32,
+    size: PixelSize,
+    flip_errors: Vec<f32>,
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (MSE {:.6}, PSNR {:.2} dB, SSIM {:.4}, FLIP {:.4})",
+            name_of_type!(Comparison),
+            self.mse,
+            self.psnr,
+            self.ssim,
+            self.flip
+        )
+    }
+}
+
+impl Comparison {
+    /// Compares the image with the reference. Returns an error if their sizes differ.
+    pub fn new(reference: &FrameBuffer, image: &FrameBuffer) -> Result<Self, Error> {
+        let (size, other) = (reference.size(), image.size());
+        if size != other {
+            return Err(Error::InvalidOption(format!(
+                "cannot compare images of different sizes: {}*{} and {}*{}",
+                size.width, size.height, other.width, other.height
+            )));
+        }
+
+        let mse = reference
+            .bytes()
+            .iter()
+            .zip(image.bytes())
+            .map(|(a, b)| (*a as f32 - *b as f32) / 255.0)
+            .map(|d| d * d)
+            .sum::<f32>()
+            / reference.bytes().len() as f32;
+        let flip_errors = flip_errors(reference, image);
+
+        Ok(Comparison {
+            mse,
+            psnr: -10.0 * mse.log10(),
+            ssim: ssim(&to_hdr(reference, |v| v), &to_hdr(image, |v| v)),
+            flip: flip_errors.iter().sum::<f32>() / flip_errors.len() as f32,
+            size,
+            flip_errors,
+        })
+    }
+
+    /// Returns the perceptual error of each pixel, from 0 to 1, in the spirit of FLIP: the
+    /// images are filtered, then the differences of their colors in L\*a\*b\* are compressed
+    /// and normalized by the difference between green and blue. Unlike FLIP, the differences
+    /// of edges and points are not taken into account.
+    pub fn flip_errors(&self) -> &[f32] {
+        &self.flip_errors
+    }
+
+    /// Returns the perceptual errors as a false-color heatmap, from blue (no error) to red.
+    pub fn heatmap(&self) -> FrameBuffer {
+        let mut fb = FrameBuffer::new(self.size.width, self.size.height);
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let e = self.flip_errors[(x + y * self.size.width) as usize];
+                fb.set(Pixel::new(x, y), heat_color(e));
+            }
+        }
+
+        fb
+    }
+}
+
+/// Converts the 8-bit channels into values from 0 to 1, and applies the function to them.
+fn to_hdr(fb: &FrameBuffer, f: impl Fn(f32) -> f32) -> HdrBuffer {
+    let size = fb.size();
+    let mut hdr = HdrBuffer::new(size.width, size.height);
+    for y in 0..size.height {
+        for x in 0..size.width {
+            let pixel = Pixel::new(x, y);
+            let c = fb.get(pixel);
+            hdr.set(pixel, [c.r, c.g, c.b].map(|v| f(v as f32 / 255.0)));
+        }
+    }
+
+    hdr
+}
+
+/// Combines the channels of two images of the same size.
+fn combine(a: &HdrBuffer, b: &HdrBuffer, f: impl Fn(f32, f32) -> f32) -> HdrBuffer {
+    let size = a.size();
+    let mut output = HdrBuffer::new(size.width, size.height);
+    for y in 0..size.height {
+        for x in 0..size.width {
+            let pixel = Pixel::new(x, y);
+            let (a, b) = (a.get(pixel), b.get(pixel));
+            output.set(pixel, [f(a[0], b[0]), f(a[1], b[1]), f(a[2], b[2])]);
+        }
+    }
+
+    output
+}
+
+fn gaussian_blur(image: &HdrBuffer, sigma: f32) -> HdrBuffer {
+    let kernel = gaussian_kernel(sigma);
+    blur(&blur(image, &kernel, true), &kernel, false)
+}
+
+/// Returns the mean SSIM of the channels, with a gaussian window.
+fn ssim(x: &HdrBuffer, y: &HdrBuffer) -> f32 {
+    const C1: f32 = 0.01 * 0.01;
+    const C2: f32 = 0.03 * 0.03;
+
+    let mean_x = gaussian_blur(x, SSIM_SIGMA);
+    let mean_y = gaussian_blur(y, SSIM_SIGMA);
+    let mean_xx = gaussian_blur(&combine(x, x, |a, b| a * b), SSIM_SIGMA);
+    let mean_yy = gaussian_blur(&combine(y, y, |a, b| a * b), SSIM_SIGMA);
+    let mean_xy = gaussian_blur(&combine(x, y, |a, b| a * b), SSIM_SIGMA);
+
+    let mut total = 0.0;
+    for i in 0..x.data().len() {
+        let (mx, my) = (mean_x.data()[i], mean_y.data()[i]);
+        let var_x = mean_xx.data()[i] - mx * mx;
+        let var_y = mean_yy.data()[i] - my * my;
+        let cov = mean_xy.data()[i] - mx * my;
+        total += ((2.0 * mx * my + C1) * (2.0 * cov + C2))
+            / ((mx * mx + my * my + C1) * (var_x + var_y + C2));
+    }
+
+    total / x.data().len() as f32
+}
+
+/// See [Comparison::flip_errors].
+fn flip_errors(reference: &FrameBuffer, image: &FrameBuffer) -> Vec<f32> {
+    let reference = gaussian_blur(&to_hdr(reference, srgb_to_linear), FLIP_SIGMA);
+    let image = gaussian_blur(&to_hdr(image, srgb_to_linear), FLIP_SIGMA);
+
+    let max = hyab(lab([0.0, 1.0, 0.0]), lab([0.0, 0.0, 1.0])).powf(FLIP_EXPONENT);
+    let (knee, knee_error) = (FLIP_KNEE.0 * max, FLIP_KNEE.1);
+
+    reference
+        .data()
+        .chunks_exact(3)
+        .zip(image.data().chunks_exact(3))
+        .map(|(a, b)| {
+            let d = hyab(lab([a[0], a[1], a[2]]), lab([b[0], b[1], b[2]])).powf(FLIP_EXPONENT);
+            let e = if d < knee {
+                d / knee * knee_error
+            } else {
+                knee_error + (d - knee) / (max - knee) * (1.0 - knee_error)
+            };
+            e.min(1.0)
+        })
+        .collect()
+}
+
+/// Converts linear sRGB into CIE L\*a\*b\*, with a D65 white point.
+fn lab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb;
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.9505;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.089;
+
+    let f = |t: f32| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            t * 841.0 / 108.0 + 4.0 / 29.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// The HyAB color difference: the lightness difference, plus the chromatic distance.
+fn hyab(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (a[0] - b[0]).abs() + ((a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rendering::{Color, BLACK, WHITE};
+
+    fn checkerboard(size: u32, a: Color, b: Color) -> FrameBuffer {
+        let mut fb = FrameBuffer::new(size, size);
+        for y in 0..size {
+            for x in 0..size {
+                fb.set(Pixel::new(x, y), if (x + y) % 2 == 0 { a } else { b });
+            }
+        }
+        fb
+    }
+
+    #[test]
+    fn identical_images() {
+        let image = checkerboard(16, BLACK, WHITE);
+        let c = Comparison::new(&image, &image).unwrap();
+
+        assert_eq!(0.0, c.mse);
+        assert_eq!(f32::INFINITY, c.psnr);
+        assert!((c.ssim - 1.0).abs() < 1e-4);
+        assert_eq!(0.0, c.flip);
+    }
+
+    #[test]
+    fn metrics_order_errors() {
+        let reference = checkerboard(16, BLACK, WHITE);
+        let gray = Color::new(128, 128, 128);
+        let slightly_off = checkerboard(16, Color::new(10, 10, 10), WHITE);
+        let flat = checkerboard(16, gray, gray);
+
+        let small = Comparison::new(&reference, &slightly_off).unwrap();
+        let large = Comparison::new(&reference, &flat).unwrap();
+        assert!(small.mse < large.mse);
+        assert!(small.psnr > large.psnr);
+        assert!(small.ssim > large.ssim);
+        assert!(small.flip < large.flip);
+
+        let white = checkerboard(16, WHITE, WHITE);
+        let black = checkerboard(16, BLACK, BLACK);
+        let c = Comparison::new(&black, &white).unwrap();
+        assert!((c.mse - 1.0).abs() < 1e-6);
+        assert!(c.flip > 0.95);
+    }
+
+    #[test]
+    fn different_sizes() {
+        assert!(Comparison::new(&FrameBuffer::new(4, 4), &FrameBuffer::new(4, 5)).is_err());
+    }
+}
@@ -1,16 +1,41 @@
 use std::fmt::{Display, Formatter};
 
+use crate::error::Error;
 use nameof::name_of_type;
 
+pub use adaptive::{Adaptive, SampleCounts};
+pub use aov::{Aov, AovImage, AovSet};
+pub use checkpoint::Checkpoint;
+pub use crop::CropWindow;
+pub use film::Film;
+pub use filter::Filter;
 pub use framebuffer::{FrameBuffer, RenderTarget};
-pub use material::{Material};
+pub use hdr::HdrBuffer;
+pub use material::Material;
+pub use post::{PostEffect, PostStack};
+pub use sampler::SamplerKind;
+pub use stats::{RayKind, RayStats};
 
 pub use crate::scene::camera::Camera;
+use crate::scene::Eye;
 use std::ops::{Add, AddAssign};
 
+pub mod adaptive;
+pub mod aov;
 pub mod backends;
+pub mod checkpoint;
+pub mod crop;
+pub mod denoise;
+pub mod film;
+pub mod filter;
 pub mod framebuffer;
+pub mod hdr;
+pub mod lut;
 pub mod material;
+pub mod metrics;
+pub mod post;
+pub mod sampler;
+pub mod stats;
 
 pub static BLACK: Color = Color::new(0, 0, 0);
 pub static WHITE: Color = Color::new(255, 255, 255);
@@ -24,37 +49,113 @@ pub static DARK_GRAY: Color = Color::new(50, 50, 50);
 /// Contains parameters for the raytracing pass.
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct RenderOpts {
-    pub samples: Sampling,
+    /// The number of samples per pixel. Ignored if adaptive sampling is enabled.
+    pub samples: u32,
+    /// Enables adaptive sampling.
+    pub adaptive: Option<Adaptive>,
+    /// The sampler used to generate the per-sample values.
+    pub sampler: SamplerKind,
+    /// The seed of the sampler. Renders with the same seed are identical.
+    pub seed: u32,
+    /// The reconstruction [Filter] applied to the samples.
+    pub filter: Filter,
+    /// The eye to render for stereoscopic cameras, or `None` for a monoscopic image.
+    pub eye: Option<Eye>,
+    /// The [AOVs](Aov) to render along the beauty image.
+    pub aovs: AovSet,
+    /// Restricts the render to a region of the image.
+    pub crop: Option<CropWindow>,
+}
+
+impl Default for RenderOpts {
+    fn default() -> Self {
+        RenderOpts::new()
+    }
 }
 
 impl RenderOpts {
     pub fn new() -> Self {
-        RenderOpts { samples: Sampling::Disabled }
+        RenderOpts {
+            samples: 1,
+            adaptive: None,
+            sampler: SamplerKind::default(),
+            seed: 0,
+            filter: Filter::default(),
+            eye: None,
+            aovs: AovSet::default(),
+            crop: None,
+        }
     }
 
-    pub fn with_samples(self, samples: Sampling) -> Self {
+    pub fn with_samples(self, samples: u32) -> Self {
         let mut s = self;
         s.samples = samples;
         s
     }
-}
 
-/// Multisampling values.
-#[allow(dead_code)]
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub enum Sampling {
-    /// Disabled: only 1 sample per pixel is computed
-    Disabled,
-    /// 2x2 samples per pixel
-    Samples4,
-    /// 4x4 samples per pixel
-    Samples16,
+    pub fn with_adaptive(self, adaptive: Option<Adaptive>) -> Self {
+        let mut s = self;
+        s.adaptive = adaptive;
+        s
+    }
+
+    /// Returns the maximum number of samples a pixel can receive.
+    pub fn max_samples(&self) -> u32 {
+        match self.adaptive {
+            Some(adaptive) => adaptive.max_samples,
+            None => self.samples.max(1),
+        }
+    }
+
+    /// Returns true if the pixel [Sample] should receive more samples.
+    pub(crate) fn needs_more_samples(&self, sample: &Sample) -> bool {
+        match self.adaptive {
+            Some(adaptive) => adaptive.needs_more_samples(sample),
+            None => sample.samples < self.max_samples(),
+        }
+    }
+
+    pub fn with_sampler(self, sampler: SamplerKind) -> Self {
+        let mut s = self;
+        s.sampler = sampler;
+        s
+    }
+
+    pub fn with_seed(self, seed: u32) -> Self {
+        let mut s = self;
+        s.seed = seed;
+        s
+    }
+
+    pub fn with_filter(self, filter: Filter) -> Self {
+        let mut s = self;
+        s.filter = filter;
+        s
+    }
+
+    pub fn with_eye(self, eye: Option<Eye>) -> Self {
+        let mut s = self;
+        s.eye = eye;
+        s
+    }
+
+    pub fn with_aovs(self, aovs: AovSet) -> Self {
+        let mut s = self;
+        s.aovs = aovs;
+        s
+    }
+
+    pub fn with_crop(self, crop: Option<CropWindow>) -> Self {
+        let mut s = self;
+        s.crop = crop;
+        s
+    }
 }
 
 /// Defines a size in pixels with a pair of integers.
 #[derive(Debug, Copy, Clone, Default, PartialEq)]
 pub struct PixelSize {
-    pub width : u32,
+    pub width: u32,
     pub height: u32,
 }
 
@@ -62,6 +163,75 @@ impl PixelSize {
     pub fn new(width: u32, height: u32) -> Self {
         Self { width, height }
     }
+
+    /// Returns an error if the size has no pixels, e.g because of an extreme aspect ratio.
+    pub fn check_not_empty(&self) -> Result<(), Error> {
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidOption(format!(
+                "empty image size: {}*{}",
+                self.width, self.height
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A rectangle of pixels, from its top-left corner.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct PixelRect {
+    pub origin: Pixel,
+    pub size: PixelSize,
+}
+
+impl Display for PixelRect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}*{} at ({}, {})",
+            self.size.width, self.size.height, self.origin.x, self.origin.y
+        )
+    }
+}
+
+impl PixelRect {
+    pub fn new(origin: Pixel, size: PixelSize) -> Self {
+        Self { origin, size }
+    }
+
+    /// Returns the rectangle that covers an image of the specified size.
+    pub fn full(size: PixelSize) -> Self {
+        Self::new(Pixel::default(), size)
+    }
+
+    /// Returns the rectangle grown by `margin` pixels on each side, but restricted to an
+    /// image of the specified size.
+    pub fn expand(&self, margin: u32, size: PixelSize) -> Self {
+        let x0 = self.origin.x.saturating_sub(margin);
+        let y0 = self.origin.y.saturating_sub(margin);
+        let x1 = (self.origin.x + self.size.width)
+            .saturating_add(margin)
+            .min(size.width);
+        let y1 = (self.origin.y + self.size.height)
+            .saturating_add(margin)
+            .min(size.height);
+
+        Self::new(Pixel::new(x0, y0), PixelSize::new(x1 - x0, y1 - y0))
+    }
+
+    pub fn contains(&self, pixel: Pixel) -> bool {
+        pixel.x >= self.origin.x
+            && pixel.y >= self.origin.y
+            && pixel.x < self.origin.x + self.size.width
+            && pixel.y < self.origin.y + self.size.height
+    }
+
+    /// Iterates over the pixels, row by row.
+    pub fn pixels(&self) -> impl Iterator<Item = Pixel> {
+        let r = *self;
+        (r.origin.y..r.origin.y + r.size.height).flat_map(move |y| {
+            (r.origin.x..r.origin.x + r.size.width).map(move |x| Pixel::new(x, y))
+        })
+    }
 }
 
 /// A pixel coordinate as a pair of integers.
@@ -103,44 +273,80 @@ impl From<Pixel> for SubPixel {
     }
 }
 
-/// A high-dynamic range (HDR) color sample, suitable for additive multisampling.
+/// A high-dynamic range (HDR) color sample, suitable for additive multisampling. The
+/// added values are linear radiance.
+///
+/// Also tracks the running mean and variance of the luminance of the added values
+/// (using Welford's algorithm), to estimate how converged the pixel is.
 #[derive(Debug, Default, Copy, Clone)]
 pub struct Sample {
     pub r: f64,
     pub g: f64,
     pub b: f64,
     pub samples: u32,
+    /// The running mean of the luminance, 1 for white.
+    mean: f64,
+    /// The sum of squared differences from the mean of the luminance.
+    m2: f64,
 }
 
-impl AddAssign<Color> for Sample {
-    fn add_assign(&mut self, rhs: Color) {
-        self.r += rhs.r as f64;
-        self.g += rhs.g as f64;
-        self.b += rhs.b as f64;
+impl Sample {
+    /// Returns the sample variance of the luminance.
+    pub fn variance(&self) -> f64 {
+        if self.samples < 2 {
+            0.0
+        } else {
+            self.m2 / (self.samples - 1) as f64
+        }
+    }
+
+    /// Returns the estimated standard error of the mean luminance.
+    pub fn standard_error(&self) -> f64 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            (self.variance() / self.samples as f64).sqrt()
+        }
+    }
+
+    /// Returns the standard error relative to the mean luminance. Dark pixels are
+    /// compared against a minimum luminance, so that they can converge as well.
+    pub fn relative_error(&self) -> f64 {
+        const MIN_LUMINANCE: f64 = 0.01;
+
+        self.standard_error() / self.mean.max(MIN_LUMINANCE)
+    }
+}
+
+impl AddAssign<[f32; 3]> for Sample {
+    fn add_assign(&mut self, rhs: [f32; 3]) {
+        self.r += rhs[0] as f64;
+        self.g += rhs[1] as f64;
+        self.b += rhs[2] as f64;
         self.samples += 1;
+
+        let luminance = hdr::luminance(rhs) as f64;
+        let delta = luminance - self.mean;
+        self.mean += delta / self.samples as f64;
+        self.m2 += delta * (luminance - self.mean);
     }
 }
 
-impl Add<Color> for Sample {
+impl Add<[f32; 3]> for Sample {
     type Output = Sample;
 
-    fn add(self, rhs: Color) -> Self::Output {
-        Self::Output {
-            r: self.r + rhs.r as f64,
-            g: self.g + rhs.g as f64,
-            b: self.b + rhs.b as f64,
-            samples: self.samples + 1,
-        }
+    fn add(self, rhs: [f32; 3]) -> Self::Output {
+        let mut sum = self;
+        sum += rhs;
+        sum
     }
 }
 
 impl From<Sample> for Color {
     fn from(hdr: Sample) -> Self {
-        let r = hdr.r / hdr.samples as f64;
-        let g = hdr.g / hdr.samples as f64;
-        let b = hdr.b / hdr.samples as f64;
+        let mean = |v: f64| (v / hdr.samples as f64) as f32;
 
-        Self::new(r as u8, g as u8, b as u8)
+        hdr::encode([mean(hdr.r), mean(hdr.g), mean(hdr.b)])
     }
 }
 
@@ -169,4 +375,9 @@ impl Color {
     pub const fn new(r: u8, g: u8, b: u8) -> Self {
         Color { r, g, b }
     }
+
+    /// Returns the relative luminance (Rec. 709), in [0, 1].
+    pub fn luminance(&self) -> f32 {
+        (0.2126 * self.r as f32 + 0.7152 * self.g as f32 + 0.0722 * self.b as f32) / 255.0
+    }
 }
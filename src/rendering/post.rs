@@ -0,0 +1,614 @@
+//! Post-processing effects, applied in order to the final image before it is presented.
+use crate::math::random::{hash_combine, to_unit_float};
+use crate::rendering::hdr::{linear_to_srgb, srgb_to_linear};
+use crate::rendering::lut::Lut;
+use crate::rendering::{HdrBuffer, Pixel};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Adds a glow around the highlights brighter than the threshold.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Bloom {
+    /// The linear value above which the pixels glow.
+    pub threshold: f32,
+    /// The amount of glow added to the image.
+    pub intensity: f32,
+    /// The extent of the glow, relative to the height of the image.
+    pub radius: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Bloom {
+            threshold: 1.0,
+            intensity: 0.5,
+            radius: 0.02,
+        }
+    }
+}
+
+/// Darkens the image towards its corners.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Vignette {
+    /// The darkening of the corners, from 0 (none) to 1 (black).
+    pub strength: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Vignette { strength: 0.4 }
+    }
+}
+
+/// Adds monochromatic film grain.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Grain {
+    /// The relative amplitude of the grain.
+    pub amount: f32,
+    /// The seed of the grain pattern.
+    pub seed: u32,
+}
+
+impl Default for Grain {
+    fn default() -> Self {
+        Grain {
+            amount: 0.05,
+            seed: 0,
+        }
+    }
+}
+
+/// Shifts the red and blue channels radially, like a lens with lateral chromatic aberration.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ChromaticAberration {
+    /// The shift at the corners, relative to the distance from the center.
+    pub strength: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        ChromaticAberration { strength: 0.005 }
+    }
+}
+
+/// Corrects the colors for the temperature of the illuminant, like the white balance of a camera:
+/// lower temperatures make the image cooler.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WhiteBalance {
+    /// The temperature of the illuminant, in Kelvin. 6500 is neutral.
+    pub temperature: f32,
+    /// The shift from green (negative) to magenta (positive), from -1 to 1.
+    pub tint: f32,
+}
+
+impl Default for WhiteBalance {
+    fn default() -> Self {
+        WhiteBalance {
+            temperature: WhiteBalance::NEUTRAL,
+            tint: 0.0,
+        }
+    }
+}
+
+/// Grades the colors with a lookup table, applied to the sRGB-encoded values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorGrading {
+    /// The `.cube` file the [Lut] was loaded from.
+    pub path: String,
+    pub lut: Lut,
+}
+
+impl ColorGrading {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        Ok(ColorGrading {
+            path: String::from(path),
+            lut: Lut::from_file(path)?,
+        })
+    }
+}
+
+/// A post-processing effect.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostEffect {
+    Bloom(Bloom),
+    Vignette(Vignette),
+    Grain(Grain),
+    ChromaticAberration(ChromaticAberration),
+    WhiteBalance(WhiteBalance),
+    ColorGrading(ColorGrading),
+}
+
+impl Display for PostEffect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostEffect::Bloom(e) => write!(f, "bloom:{}", e.threshold),
+            PostEffect::Vignette(e) => write!(f, "vignette:{}", e.strength),
+            PostEffect::Grain(e) => write!(f, "grain:{}", e.amount),
+            PostEffect::ChromaticAberration(e) => write!(f, "chromatic-aberration:{}", e.strength),
+            PostEffect::WhiteBalance(e) => write!(f, "white-balance:{}", e.temperature),
+            PostEffect::ColorGrading(e) => write!(f, "lut:{}", e.path),
+        }
+    }
+}
+
+impl FromStr for PostEffect {
+    type Err = String;
+
+    /// Parses an effect of the form `name[:param]`, e.g `bloom:0.8` or `lut:grade.cube`.
+    /// The parameter is the threshold of the bloom, the strength of the vignette and of the
+    /// chromatic aberration, the amount of grain, the white balance temperature, and the
+    /// path of the `.cube` file, which is required.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let name = parts.next().unwrap_or_default().trim().to_lowercase();
+        let param = parts.next().map(str::trim);
+
+        let number = |min: f32| -> Result<Option<f32>, String> {
+            match param {
+                None => Ok(None),
+                Some(p) => match p.parse::<f32>() {
+                    Ok(v) if v >= min && v.is_finite() => Ok(Some(v)),
+                    _ => Err(format!("invalid parameter for {}: {}", name, p)),
+                },
+            }
+        };
+
+        match name.as_str() {
+            "bloom" => {
+                let mut e = Bloom::default();
+                e.threshold = number(0.0)?.unwrap_or(e.threshold);
+                Ok(PostEffect::Bloom(e))
+            }
+            "vignette" => {
+                let mut e = Vignette::default();
+                e.strength = number(0.0)?.unwrap_or(e.strength);
+                Ok(PostEffect::Vignette(e))
+            }
+            "grain" => {
+                let mut e = Grain::default();
+                e.amount = number(0.0)?.unwrap_or(e.amount);
+                Ok(PostEffect::Grain(e))
+            }
+            "chromatic-aberration" | "ca" => {
+                let mut e = ChromaticAberration::default();
+                e.strength = number(0.0)?.unwrap_or(e.strength);
+                Ok(PostEffect::ChromaticAberration(e))
+            }
+            "white-balance" | "wb" => {
+                let mut e = WhiteBalance::default();
+                e.temperature = number(WhiteBalance::MIN_TEMPERATURE)?.unwrap_or(e.temperature);
+                Ok(PostEffect::WhiteBalance(e))
+            }
+            "lut" => match param {
+                Some(path) if !path.is_empty() => {
+                    Ok(PostEffect::ColorGrading(ColorGrading::from_file(path)?))
+                }
+                _ => Err(String::from(
+                    "the lut effect requires the path of a .cube file",
+                )),
+            },
+            _ => Err(format!("unknown post effect: {}", s)),
+        }
+    }
+}
+
+impl PostEffect {
+    /// Returns an error if a parameter is out of its range, e.g a negative intensity.
+    pub fn check(&self) -> Result<(), String> {
+        let at_least = |name: &str, v: f32, min: f32| {
+            if v >= min && v.is_finite() {
+                Ok(())
+            } else {
+                Err(format!("invalid {}: {}", name, v))
+            }
+        };
+
+        match self {
+            PostEffect::Bloom(e) => {
+                at_least("bloom threshold", e.threshold, 0.0)?;
+                at_least("bloom intensity", e.intensity, 0.0)?;
+                at_least("bloom radius", e.radius, 0.0)
+            }
+            PostEffect::Vignette(e) => at_least("vignette strength", e.strength, 0.0),
+            PostEffect::Grain(e) => at_least("grain amount", e.amount, 0.0),
+            PostEffect::ChromaticAberration(e) => {
+                at_least("chromatic aberration strength", e.strength, 0.0)
+            }
+            PostEffect::WhiteBalance(e) => {
+                at_least(
+                    "white balance temperature",
+                    e.temperature,
+                    WhiteBalance::MIN_TEMPERATURE,
+                )?;
+                if (-1.0..=1.0).contains(&e.tint) {
+                    Ok(())
+                } else {
+                    Err(format!("invalid white balance tint: {}", e.tint))
+                }
+            }
+            PostEffect::ColorGrading(_) => Ok(()),
+        }
+    }
+
+    pub fn apply(&self, image: &mut HdrBuffer) {
+        match self {
+            PostEffect::Bloom(e) => e.apply(image),
+            PostEffect::Vignette(e) => e.apply(image),
+            PostEffect::Grain(e) => e.apply(image),
+            PostEffect::ChromaticAberration(e) => e.apply(image),
+            PostEffect::WhiteBalance(e) => e.apply(image),
+            PostEffect::ColorGrading(e) => e.apply(image),
+        }
+    }
+}
+
+/// An ordered list of [PostEffect]s.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PostStack {
+    pub effects: Vec<PostEffect>,
+    /// Skips all the effects.
+    pub bypass: bool,
+}
+
+impl Display for PostStack {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.effects.is_empty() {
+            return write!(f, "none");
+        }
+        let names: Vec<String> = self.effects.iter().map(PostEffect::to_string).collect();
+        write!(f, "{}", names.join(","))?;
+        if self.bypass {
+            write!(f, " (bypassed)")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PostStack {
+    type Err = String;
+
+    /// Parses a comma-separated list of [PostEffect]s, e.g `bloom,vignette:0.3`, or `none`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("none") {
+            return Ok(PostStack::default());
+        }
+
+        let effects = s.split(',').map(str::parse).collect::<Result<_, _>>()?;
+        Ok(PostStack::new(effects))
+    }
+}
+
+impl PostStack {
+    pub fn new(effects: Vec<PostEffect>) -> Self {
+        PostStack {
+            effects,
+            bypass: false,
+        }
+    }
+
+    pub fn with_bypass(self, bypass: bool) -> Self {
+        let mut s = self;
+        s.bypass = bypass;
+        s
+    }
+
+    /// Returns true if applying the stack leaves the image unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.bypass || self.effects.is_empty()
+    }
+
+    /// Applies the effects in order, unless bypassed.
+    pub fn apply(&self, image: &mut HdrBuffer) {
+        if self.bypass {
+            return;
+        }
+        for effect in self.effects.iter() {
+            effect.apply(image);
+        }
+    }
+}
+
+impl Bloom {
+    pub fn apply(&self, image: &mut HdrBuffer) {
+        let size = image.size();
+        let sigma = (self.radius * size.height as f32).max(0.5);
+        let kernel = gaussian_kernel(sigma);
+
+        let mut bright = HdrBuffer::new(size.width, size.height);
+        for_each_pixel(image, |pixel, rgb| {
+            bright.set(pixel, rgb.map(|v| (v - self.threshold).max(0.0)));
+        });
+
+        let glow = blur(&blur(&bright, &kernel, true), &kernel, false);
+        for_each_pixel(&glow, |pixel, g| {
+            let rgb = image.get(pixel);
+            image.set(pixel, [0, 1, 2].map(|i| rgb[i] + self.intensity * g[i]));
+        });
+    }
+}
+
+impl Vignette {
+    pub fn apply(&self, image: &mut HdrBuffer) {
+        let geometry = Radial::new(image);
+        let copy = image.clone();
+        for_each_pixel(&copy, |pixel, rgb| {
+            let r = geometry.distance(pixel);
+            let factor = (1.0 - self.strength * r * r).max(0.0);
+            image.set(pixel, rgb.map(|v| v * factor));
+        });
+    }
+}
+
+impl Grain {
+    pub fn apply(&self, image: &mut HdrBuffer) {
+        let copy = image.clone();
+        for_each_pixel(&copy, |pixel, rgb| {
+            // triangular noise in [-1, 1]
+            let u1 = to_unit_float(hash_combine(&[pixel.x, pixel.y, self.seed, 0]));
+            let u2 = to_unit_float(hash_combine(&[pixel.x, pixel.y, self.seed, 1]));
+            let factor = 1.0 + self.amount * (u1 + u2 - 1.0);
+            image.set(pixel, rgb.map(|v| v * factor));
+        });
+    }
+}
+
+impl ChromaticAberration {
+    pub fn apply(&self, image: &mut HdrBuffer) {
+        let geometry = Radial::new(image);
+        let copy = image.clone();
+        for_each_pixel(&copy, |pixel, rgb| {
+            let (dx, dy) = (pixel.x as f32 - geometry.cx, pixel.y as f32 - geometry.cy);
+            let shifted =
+                |scale: f32| sample(&copy, geometry.cx + dx * scale, geometry.cy + dy * scale);
+            let red = shifted(1.0 - self.strength)[0];
+            let blue = shifted(1.0 + self.strength)[2];
+            image.set(pixel, [red, rgb[1], blue]);
+        });
+    }
+}
+
+impl WhiteBalance {
+    /// The temperature that leaves the colors unchanged, in Kelvin.
+    pub const NEUTRAL: f32 = 6500.0;
+    pub const MIN_TEMPERATURE: f32 = 1000.0;
+    pub const MAX_TEMPERATURE: f32 = 40000.0;
+
+    /// Returns the per-channel gains, normalized to preserve the luminance.
+    pub fn gains(&self) -> [f32; 3] {
+        let neutral = blackbody(Self::NEUTRAL);
+        let illuminant = blackbody(self.temperature);
+        let mut gains = [0, 1, 2].map(|i| neutral[i] / illuminant[i].max(1e-4));
+        gains[1] *= 1.0 - 0.25 * self.tint.clamp(-1.0, 1.0);
+
+        let luminance = 0.2126 * gains[0] + 0.7152 * gains[1] + 0.0722 * gains[2];
+        gains.map(|g| g / luminance)
+    }
+
+    pub fn apply(&self, image: &mut HdrBuffer) {
+        let gains = self.gains();
+        let copy = image.clone();
+        for_each_pixel(&copy, |pixel, rgb| {
+            image.set(pixel, [0, 1, 2].map(|i| rgb[i] * gains[i]));
+        });
+    }
+}
+
+impl ColorGrading {
+    /// Grades the image. The values are clamped to the domain of the [Lut].
+    pub fn apply(&self, image: &mut HdrBuffer) {
+        let copy = image.clone();
+        for_each_pixel(&copy, |pixel, rgb| {
+            let graded = self.lut.apply(rgb.map(linear_to_srgb));
+            image.set(pixel, graded.map(srgb_to_linear));
+        });
+    }
+}
+
+fn for_each_pixel(image: &HdrBuffer, mut f: impl FnMut(Pixel, [f32; 3])) {
+    let size = image.size();
+    for y in 0..size.height {
+        for x in 0..size.width {
+            let pixel = Pixel::new(x, y);
+            f(pixel, image.get(pixel));
+        }
+    }
+}
+
+/// The center of an image, and the distance from the center to the corners.
+struct Radial {
+    cx: f32,
+    cy: f32,
+    corner: f32,
+}
+
+impl Radial {
+    fn new(image: &HdrBuffer) -> Self {
+        let size = image.size();
+        let (cx, cy) = (
+            (size.width as f32 - 1.0) / 2.0,
+            (size.height as f32 - 1.0) / 2.0,
+        );
+        Radial {
+            cx,
+            cy,
+            corner: (cx * cx + cy * cy).sqrt().max(1.0),
+        }
+    }
+
+    /// Returns the distance of the pixel from the center, where the corners are at 1.
+    fn distance(&self, pixel: Pixel) -> f32 {
+        let (dx, dy) = (pixel.x as f32 - self.cx, pixel.y as f32 - self.cy);
+        (dx * dx + dy * dy).sqrt() / self.corner
+    }
+}
+
+/// Samples the image at a continuous position with bilinear interpolation.
+/// Positions outside of the image are clamped to its edges.
+fn sample(image: &HdrBuffer, x: f32, y: f32) -> [f32; 3] {
+    let size = image.size();
+    let x = x.clamp(0.0, (size.width - 1) as f32);
+    let y = y.clamp(0.0, (size.height - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(size.width - 1), (y0 + 1).min(size.height - 1));
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+    let lerp = |a: [f32; 3], b: [f32; 3], t: f32| [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t);
+    let top = lerp(
+        image.get(Pixel::new(x0, y0)),
+        image.get(Pixel::new(x1, y0)),
+        tx,
+    );
+    let bottom = lerp(
+        image.get(Pixel::new(x0, y1)),
+        image.get(Pixel::new(x1, y1)),
+        tx,
+    );
+    lerp(top, bottom, ty)
+}
+
+/// Returns the normalized weights of a gaussian, from the center to 3 standard deviations.
+pub(crate) fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let radius = (3.0 * sigma).ceil() as i32;
+    let weights: Vec<f32> = (0..=radius)
+        .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total = weights[0] + 2.0 * weights[1..].iter().sum::<f32>();
+    weights.iter().map(|w| w / total).collect()
+}
+
+/// Blurs the image horizontally or vertically with a symmetric kernel. The edges are extended.
+pub(crate) fn blur(image: &HdrBuffer, kernel: &[f32], horizontal: bool) -> HdrBuffer {
+    let size = image.size();
+    let mut output = HdrBuffer::new(size.width, size.height);
+    let clamp = |v: i64, max: u32| v.clamp(0, max as i64 - 1) as u32;
+
+    for_each_pixel(image, |pixel, _| {
+        let mut sum = [0.0f32; 3];
+        for (i, w) in kernel.iter().enumerate() {
+            let offsets: &[i64] = if i == 0 {
+                &[0]
+            } else {
+                &[-(i as i64), i as i64]
+            };
+            for offset in offsets {
+                let p = if horizontal {
+                    Pixel::new(clamp(pixel.x as i64 + offset, size.width), pixel.y)
+                } else {
+                    Pixel::new(pixel.x, clamp(pixel.y as i64 + offset, size.height))
+                };
+                let rgb = image.get(p);
+                for c in 0..3 {
+                    sum[c] += w * rgb[c];
+                }
+            }
+        }
+        output.set(pixel, sum);
+    });
+
+    output
+}
+
+/// Returns the linear RGB color of a black body at the temperature (in Kelvin), normalized
+/// so that its largest component is 1 (after Tanner Helland's approximation).
+fn blackbody(kelvin: f32) -> [f32; 3] {
+    let t = kelvin.clamp(WhiteBalance::MIN_TEMPERATURE, WhiteBalance::MAX_TEMPERATURE) / 100.0;
+
+    let r = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_73 * (t - 60.0).powf(-0.133_204_76)
+    };
+    let g = if t <= 66.0 {
+        99.470_8 * t.ln() - 161.119_57
+    } else {
+        288.122_17 * (t - 60.0).powf(-0.075_514_85)
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (t - 10.0).ln() - 305.044_8
+    };
+
+    let rgb = [r, g, b].map(|v| srgb_to_linear(v.clamp(0.0, 255.0) / 255.0));
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+    rgb.map(|v| v / max)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn uniform(width: u32, height: u32, rgb: [f32; 3]) -> HdrBuffer {
+        let mut image = HdrBuffer::new(width, height);
+        for_each_pixel(&image.clone(), |pixel, _| image.set(pixel, rgb));
+        image
+    }
+
+    #[test]
+    fn parse_stack() {
+        let stack: PostStack = "bloom:0.8, vignette,wb:3200".parse().unwrap();
+
+        assert_eq!(
+            "bloom:0.8,vignette:0.4,white-balance:3200",
+            stack.to_string()
+        );
+        assert!("none".parse::<PostStack>().unwrap().is_empty());
+        assert!("vignette:-1".parse::<PostStack>().is_err());
+        assert!("lut".parse::<PostStack>().is_err());
+        assert!("sharpen".parse::<PostStack>().is_err());
+    }
+
+    #[test]
+    fn bypass_leaves_the_image_unchanged() {
+        let mut image = uniform(8, 8, [0.5, 0.5, 0.5]);
+        let stack: PostStack = "vignette:1,grain:0.5".parse().unwrap();
+
+        stack.clone().with_bypass(true).apply(&mut image);
+        assert_eq!(uniform(8, 8, [0.5, 0.5, 0.5]).data(), image.data());
+
+        stack.apply(&mut image);
+        assert_ne!(uniform(8, 8, [0.5, 0.5, 0.5]).data(), image.data());
+    }
+
+    #[test]
+    fn bloom_spreads_highlights_only() {
+        let mut dim = uniform(32, 32, [0.5, 0.5, 0.5]);
+        let mut bright = dim.clone();
+        bright.set(Pixel::new(16, 16), [8.0, 8.0, 8.0]);
+
+        let bloom = Bloom::default();
+        bloom.apply(&mut dim);
+        bloom.apply(&mut bright);
+
+        assert_eq!(uniform(32, 32, [0.5, 0.5, 0.5]).data(), dim.data());
+        assert!(bright.get(Pixel::new(16, 17))[0] > 0.5);
+        assert!(bright.get(Pixel::new(16, 16))[0] > 8.0);
+    }
+
+    #[test]
+    fn vignette_darkens_the_corners() {
+        let mut image = uniform(33, 33, [1.0, 1.0, 1.0]);
+        Vignette { strength: 0.5 }.apply(&mut image);
+
+        assert_eq!([1.0, 1.0, 1.0], image.get(Pixel::new(16, 16)));
+        assert!((image.get(Pixel::new(0, 0))[0] - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn white_balance() {
+        let neutral = WhiteBalance::default().gains();
+        for g in neutral.iter() {
+            assert!((g - 1.0).abs() < 1e-2, "{:?}", neutral);
+        }
+
+        // correcting for a tungsten illuminant makes the image bluer
+        let tungsten = WhiteBalance {
+            temperature: 3200.0,
+            tint: 0.0,
+        }
+        .gains();
+        assert!(tungsten[2] > 1.0 && tungsten[0] < 1.0, "{:?}", tungsten);
+    }
+}
@@ -0,0 +1,481 @@
+//! Sample generators for the per-pixel sample dimensions (sub-pixel position, time...).
+//!
+//! All samplers are deterministic: the samples of a pixel only depend on the pixel
+//! coordinates, the sample index and the seed.
+use crate::math::random::{hash, hash_combine, to_unit_float, Pcg32};
+use crate::rendering::Pixel;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Generates the sample values of each pixel.
+///
+/// Each call to [Sampler::start_sample] begins a new sample, after which each call to
+/// [Sampler::get_1d] or [Sampler::get_2d] returns the values of the next dimension(s).
+pub trait Sampler {
+    /// Starts the sample `index` of the [Pixel].
+    fn start_sample(&mut self, pixel: Pixel, index: u32);
+
+    /// Returns the value of the next dimension, in [0, 1).
+    fn get_1d(&mut self) -> f32;
+
+    /// Returns the values of the next two dimensions, in [0, 1).
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+/// The available samplers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SamplerKind {
+    /// Independent uniform random samples.
+    Independent,
+    /// Jittered samples, one per stratum.
+    #[default]
+    Stratified,
+    /// The Halton low-discrepancy sequence, randomized per pixel.
+    Halton,
+    /// The Sobol low-discrepancy sequence, with Owen scrambling.
+    Sobol,
+}
+
+impl SamplerKind {
+    /// Creates a [Sampler] that generates `samples_per_pixel` samples for each pixel.
+    pub(crate) fn create(&self, samples_per_pixel: u32, seed: u32) -> Box<dyn Sampler> {
+        let state = SamplerState::new(seed);
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler {
+                state,
+                rng: Pcg32::new(0),
+            }),
+            SamplerKind::Stratified => Box::new(StratifiedSampler {
+                state,
+                samples_per_pixel: samples_per_pixel.max(1),
+            }),
+            SamplerKind::Halton => Box::new(HaltonSampler { state }),
+            SamplerKind::Sobol => Box::new(SobolSampler { state }),
+        }
+    }
+}
+
+impl Display for SamplerKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SamplerKind::Independent => write!(f, "independent"),
+            SamplerKind::Stratified => write!(f, "stratified"),
+            SamplerKind::Halton => write!(f, "halton"),
+            SamplerKind::Sobol => write!(f, "sobol"),
+        }
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "independent" | "random" => Ok(SamplerKind::Independent),
+            "stratified" | "jittered" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(format!("unknown sampler: {}", s)),
+        }
+    }
+}
+
+/// The state shared by all samplers: the current pixel, sample and dimension.
+#[derive(Debug, Copy, Clone)]
+struct SamplerState {
+    seed: u32,
+    pixel_seed: u32,
+    index: u32,
+    dimension: u32,
+}
+
+impl SamplerState {
+    fn new(seed: u32) -> Self {
+        SamplerState {
+            seed,
+            pixel_seed: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn start_sample(&mut self, pixel: Pixel, index: u32) {
+        self.pixel_seed = hash_combine(&[pixel.x, pixel.y, self.seed]);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    /// Returns the current dimension, and advances to the next one.
+    fn next_dimension(&mut self) -> u32 {
+        let dim = self.dimension;
+        self.dimension += 1;
+        dim
+    }
+
+    /// Returns a hash of the current pixel and the specified dimension.
+    fn dimension_seed(&self, dimension: u32) -> u32 {
+        hash(self.pixel_seed ^ hash(dimension))
+    }
+}
+
+/// A [Sampler] that returns independent uniform random values.
+#[derive(Debug)]
+pub struct IndependentSampler {
+    state: SamplerState,
+    rng: Pcg32,
+}
+
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, pixel: Pixel, index: u32) {
+        self.state.start_sample(pixel, index);
+        let seed = hash_combine(&[self.state.pixel_seed, index]);
+        self.rng = Pcg32::new(seed as u64);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.rng.next_f32()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.rng.next_f32(), self.rng.next_f32())
+    }
+}
+
+/// A [Sampler] that divides each dimension into strata, and returns a jittered
+/// value in a different stratum for each sample. The order of the strata is shuffled
+/// per pixel and dimension, so that dimensions are not correlated.
+#[derive(Debug)]
+pub struct StratifiedSampler {
+    state: SamplerState,
+    samples_per_pixel: u32,
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, pixel: Pixel, index: u32) {
+        self.state.start_sample(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dim = self.state.next_dimension();
+        let seed = self.state.dimension_seed(dim);
+        let count = self.samples_per_pixel;
+        let stratum = permute(self.state.index % count, count, seed);
+        let jitter = to_unit_float(hash(seed ^ self.state.index));
+
+        (stratum as f32 + jitter) / count as f32
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let dim = self.state.next_dimension();
+        let seed = self.state.dimension_seed(dim);
+        self.state.next_dimension();
+
+        // the strata form a grid that covers at least samples_per_pixel cells, and no more than
+        // u32::MAX (65535 * 65537)
+        let nx = ((self.samples_per_pixel as f32).sqrt() as u32).min(u16::MAX as u32);
+        let ny = self.samples_per_pixel.div_ceil(nx);
+        let count = nx * ny;
+        let stratum = permute(self.state.index % count, count, seed);
+        let jx = to_unit_float(hash(seed ^ self.state.index));
+        let jy = to_unit_float(hash(hash(seed) ^ self.state.index));
+
+        (
+            ((stratum % nx) as f32 + jx) / nx as f32,
+            ((stratum / nx) as f32 + jy) / ny as f32,
+        )
+    }
+}
+
+/// The bases of the first dimensions of the Halton sequence.
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// A [Sampler] based on the Halton sequence. Each pixel uses the same points, with
+/// a random toroidal shift per pixel and dimension (Cranley-Patterson rotation).
+///
+/// The dimensions past the table of [PRIMES] are independent random values, rather than
+/// reusing a base, which would correlate them with an earlier dimension.
+#[derive(Debug)]
+pub struct HaltonSampler {
+    state: SamplerState,
+}
+
+impl HaltonSampler {
+    fn sample_dimension(&mut self) -> f32 {
+        let dim = self.state.next_dimension();
+        let seed = self.state.dimension_seed(dim);
+        let base = match PRIMES.get(dim as usize) {
+            Some(base) => *base,
+            None => return to_unit_float(hash(seed ^ hash(self.state.index))),
+        };
+        let shift = to_unit_float(seed);
+        let value = radical_inverse(base, self.state.index) + shift;
+
+        (value - value.floor()).min(ONE_MINUS_EPSILON)
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, pixel: Pixel, index: u32) {
+        self.state.start_sample(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.sample_dimension()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.sample_dimension(), self.sample_dimension())
+    }
+}
+
+/// A [Sampler] based on the first two dimensions of the Sobol sequence, with hash-based
+/// Owen scrambling. Each call to [Sampler::get_1d] or [Sampler::get_2d] uses a
+/// differently shuffled and scrambled copy of the 2D sequence ("Practical Hash-based
+/// Owen Scrambling", Burley 2020).
+#[derive(Debug)]
+pub struct SobolSampler {
+    state: SamplerState,
+}
+
+impl SobolSampler {
+    fn sample_pair(&mut self) -> (f32, f32) {
+        let dim = self.state.next_dimension();
+        let seed = self.state.dimension_seed(dim);
+        let index = nested_uniform_scramble(self.state.index, seed);
+
+        let x = nested_uniform_scramble(sobol_dimension_0(index), hash(seed ^ 1));
+        let y = nested_uniform_scramble(sobol_dimension_1(index), hash(seed ^ 2));
+
+        (to_unit_float(x), to_unit_float(y))
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, pixel: Pixel, index: u32) {
+        self.state.start_sample(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.sample_pair().0
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        self.sample_pair()
+    }
+}
+
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+/// Returns the digits of `index` in the specified base, mirrored around the radix point.
+fn radical_inverse(base: u32, index: u32) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_n = 1.0;
+    let mut reversed: u64 = 0;
+    let mut index = index;
+
+    while index > 0 {
+        let next = index / base;
+        let digit = index - next * base;
+        reversed = reversed * base as u64 + digit as u64;
+        inv_base_n *= inv_base;
+        index = next;
+    }
+
+    ((reversed as f64 * inv_base_n) as f32).min(ONE_MINUS_EPSILON)
+}
+
+/// The first dimension of the Sobol sequence (the base-2 van der Corput sequence).
+fn sobol_dimension_0(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+/// The second dimension of the Sobol sequence.
+fn sobol_dimension_1(index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1u32 << 31;
+    let mut index = index;
+
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+
+    result
+}
+
+/// A hash that only propagates bits upwards, from Laine and Karras.
+fn laine_karras_permutation(x: u32, seed: u32) -> u32 {
+    let mut x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+/// Owen-scrambles the bits of `x`: each bit is flipped based on a hash of the more
+/// significant bits.
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Returns the element at `index` of a random permutation of `[0, count)`
+/// ("Correlated Multi-Jittered Sampling", Kensler 2013).
+fn permute(index: u32, count: u32, seed: u32) -> u32 {
+    if count <= 1 {
+        return 0;
+    }
+
+    let mut w = count - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    let p = seed;
+    let mut i = index;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < count {
+            break;
+        }
+    }
+
+    ((i as u64 + p as u64) % count as u64) as u32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ALL: [SamplerKind; 4] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ];
+
+    fn generate(kind: SamplerKind, pixel: Pixel, count: u32) -> Vec<(f32, f32, f32)> {
+        let mut sampler = kind.create(count, 7);
+        (0..count)
+            .map(|i| {
+                sampler.start_sample(pixel, i);
+                let (x, y) = sampler.get_2d();
+                (x, y, sampler.get_1d())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn samples_are_deterministic_and_in_range() {
+        for kind in ALL.iter() {
+            let a = generate(*kind, Pixel::new(3, 5), 16);
+            let b = generate(*kind, Pixel::new(3, 5), 16);
+            let c = generate(*kind, Pixel::new(4, 5), 16);
+
+            assert_eq!(a, b, "{}", kind);
+            assert_ne!(a, c, "{}", kind);
+            for (x, y, t) in a {
+                assert!((0.0..1.0).contains(&x), "{}", kind);
+                assert!((0.0..1.0).contains(&y), "{}", kind);
+                assert!((0.0..1.0).contains(&t), "{}", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn samples_are_in_range_with_the_largest_sample_count() {
+        for kind in ALL.iter() {
+            let mut sampler = kind.create(u32::MAX, 7);
+            for index in [0, u32::MAX / 2, u32::MAX - 1].iter() {
+                sampler.start_sample(Pixel::new(3, 5), *index);
+                let (x, y) = sampler.get_2d();
+                for value in [x, y, sampler.get_1d()].iter() {
+                    assert!((0.0..1.0).contains(value), "{}", kind);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn halton_dimensions_are_not_correlated() {
+        let mut sampler = SamplerKind::Halton.create(64, 7);
+        // the offsets between a dimension and the first one, for each sample
+        let mut offsets_of = |dimension: usize| -> Vec<f32> {
+            (0..64)
+                .map(|i| {
+                    sampler.start_sample(Pixel::new(3, 5), i);
+                    let values: Vec<f32> = (0..=dimension).map(|_| sampler.get_1d()).collect();
+                    (values[dimension] - values[0]).rem_euclid(1.0)
+                })
+                .collect()
+        };
+
+        for dimension in [16, 64, 100] {
+            let offsets = offsets_of(dimension);
+            let spread = offsets
+                .iter()
+                .fold(0f32, |m, o| m.max((o - offsets[0]).abs()));
+            assert!(
+                spread > 0.1,
+                "dimension {} is a shifted copy of dimension 0",
+                dimension
+            );
+        }
+    }
+
+    #[test]
+    fn stratified_and_sobol_cover_every_stratum() {
+        for kind in [SamplerKind::Stratified, SamplerKind::Sobol].iter() {
+            let samples = generate(*kind, Pixel::new(1, 2), 16);
+
+            let mut cells: Vec<u32> = samples
+                .iter()
+                .map(|(x, y, _)| (x * 4.0) as u32 + 4 * (y * 4.0) as u32)
+                .collect();
+            cells.sort_unstable();
+            cells.dedup();
+            assert_eq!(16, cells.len(), "{}", kind);
+
+            let mut times: Vec<u32> = samples.iter().map(|(_, _, t)| (t * 16.0) as u32).collect();
+            times.sort_unstable();
+            times.dedup();
+            assert_eq!(16, times.len(), "{}", kind);
+        }
+    }
+
+    #[test]
+    fn permute_is_a_permutation() {
+        for count in [1, 5, 16, 33].iter() {
+            let mut values: Vec<u32> = (0..*count).map(|i| permute(i, *count, 1234)).collect();
+            values.sort_unstable();
+            assert_eq!((0..*count).collect::<Vec<u32>>(), values);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Ray counters, e.g to benchmark the renderer.
+use nameof::name_of_type;
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, AddAssign};
+
+/// The purpose of a traced ray.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RayKind {
+    /// A ray from the camera.
+    Primary,
+    /// A ray towards a light, to test its visibility.
+    Shadow,
+    /// A ray scattered by a surface, e.g a reflection.
+    Secondary,
+}
+
+/// Counts the rays traced through a scene, and the intersection tests they required.
+///
+/// Surfaces are shaded without tracing further rays, so the shadow and secondary counters
+/// stay at zero until the renderer traces such rays. They are kept so that the reports
+/// of the benchmarks have the same fields across versions.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct RayStats {
+    pub primary_rays: u64,
+    pub shadow_rays: u64,
+    pub secondary_rays: u64,
+    /// The number of bounding boxes tested.
+    pub bounds_tests: u64,
+    /// The number of primitives tested, after their bounding box was hit.
+    pub intersection_tests: u64,
+}
+
+impl Display for RayStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({} rays: {} primary, {} shadow, {} secondary, {:.2} intersection tests per ray)",
+            name_of_type!(RayStats),
+            self.rays(),
+            self.primary_rays,
+            self.shadow_rays,
+            self.secondary_rays,
+            self.intersection_tests_per_ray()
+        )
+    }
+}
+
+impl RayStats {
+    /// Counts a ray.
+    pub fn add_ray(&mut self, kind: RayKind) {
+        match kind {
+            RayKind::Primary => self.primary_rays += 1,
+            RayKind::Shadow => self.shadow_rays += 1,
+            RayKind::Secondary => self.secondary_rays += 1,
+        }
+    }
+
+    /// The total number of rays.
+    pub fn rays(&self) -> u64 {
+        self.primary_rays + self.shadow_rays + self.secondary_rays
+    }
+
+    pub fn bounds_tests_per_ray(&self) -> f64 {
+        self.bounds_tests as f64 / self.rays().max(1) as f64
+    }
+
+    pub fn intersection_tests_per_ray(&self) -> f64 {
+        self.intersection_tests as f64 / self.rays().max(1) as f64
+    }
+}
+
+impl Add for RayStats {
+    type Output = RayStats;
+
+    fn add(self, rhs: RayStats) -> RayStats {
+        RayStats {
+            primary_rays: self.primary_rays + rhs.primary_rays,
+            shadow_rays: self.shadow_rays + rhs.shadow_rays,
+            secondary_rays: self.secondary_rays + rhs.secondary_rays,
+            bounds_tests: self.bounds_tests + rhs.bounds_tests,
+            intersection_tests: self.intersection_tests + rhs.intersection_tests,
+        }
+    }
+}
+
+impl AddAssign for RayStats {
+    fn add_assign(&mut self, rhs: RayStats) {
+        *self = *self + rhs;
+    }
+}
@@ -0,0 +1,478 @@
+//! Keyframe animation of the entities and the camera of a [Scene](crate::scene::Scene).
+use crate::error::Error;
+use crate::math::{Rotation, Vec3};
+use crate::rendering::Color;
+use crate::scene::{Camera, Entity, Node, NodeId, Projection, SceneGraph, Transform};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The smallest animated field of view of a fisheye, in degrees.
+const MIN_FOV: f32 = 1.0;
+
+/// The smallest animated height of an orthographic projection, in world units.
+const MIN_ORTHO_HEIGHT: f32 = 1e-3;
+
+/// How a value changes from a [Keyframe] to the next one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// A cubic Bézier curve with automatic handles: the slope at a keyframe is the slope
+    /// between its neighbours, and is zero at the first and last keyframes, and next to an
+    /// equal keyframe, so that the curve stays flat between them.
+    Bezier,
+    /// The value is held until the next keyframe.
+    Step,
+}
+
+impl Display for Interpolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Interpolation::Linear => write!(f, "linear"),
+            Interpolation::Bezier => write!(f, "bezier"),
+            Interpolation::Step => write!(f, "step"),
+        }
+    }
+}
+
+impl FromStr for Interpolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "linear" => Ok(Interpolation::Linear),
+            "bezier" => Ok(Interpolation::Bezier),
+            "step" | "constant" => Ok(Interpolation::Step),
+            _ => Err(format!("unknown interpolation: {}", s)),
+        }
+    }
+}
+
+/// A value that can be interpolated between keyframes.
+pub trait Animatable: Copy + PartialEq {
+    /// Returns the value at `t` from `a` (at 0) to `b` (at 1).
+    fn lerp(a: Self, b: Self, t: f32) -> Self;
+
+    /// Returns this value changed by `t` times the change from `from` to `to`.
+    fn offset(self, from: Self, to: Self, t: f32) -> Self;
+}
+
+impl Animatable for f32 {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        a + (b - a) * t
+    }
+
+    fn offset(self, from: Self, to: Self, t: f32) -> Self {
+        self + (to - from) * t
+    }
+}
+
+impl Animatable for Vec3 {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        a.lerp(&b, t)
+    }
+
+    fn offset(self, from: Self, to: Self, t: f32) -> Self {
+        self + (to - from) * t
+    }
+}
+
+/// Rotations are interpolated spherically, the shortest way, as during the shutter of a
+/// moving [Transform].
+impl Animatable for Rotation {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        a.try_slerp(&b, t, f32::EPSILON).unwrap_or(a)
+    }
+
+    fn offset(self, from: Self, to: Self, t: f32) -> Self {
+        (to * from.inverse()).powf(t) * self
+    }
+}
+
+/// The value of an animated property at a frame. The interpolation applies from this
+/// keyframe to the next one.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Keyframe<T> {
+    pub frame: f32,
+    pub value: T,
+    pub interpolation: Interpolation,
+}
+
+impl<T> Keyframe<T> {
+    pub fn new(frame: f32, value: T, interpolation: Interpolation) -> Self {
+        Keyframe {
+            frame,
+            value,
+            interpolation,
+        }
+    }
+}
+
+/// The keyframes of an animated property. Before the first keyframe and after the last
+/// one, the property keeps their value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T: Animatable> Track<T> {
+    /// Creates a track from keyframes in any order, or returns an error if there are none.
+    pub fn new(keyframes: Vec<Keyframe<T>>) -> Result<Self, Error> {
+        if keyframes.is_empty() {
+            return Err(Error::Scene(String::from(
+                "a track needs at least one keyframe",
+            )));
+        }
+        let mut keyframes = keyframes;
+        keyframes.sort_by(|a, b| a.frame.total_cmp(&b.frame));
+        Ok(Track { keyframes })
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+        &self.keyframes
+    }
+
+    /// Returns the frames of the first and last keyframes.
+    pub fn range(&self) -> (f32, f32) {
+        (
+            self.keyframes[0].frame,
+            self.keyframes[self.keyframes.len() - 1].frame,
+        )
+    }
+
+    /// Returns the interpolated value at the frame.
+    pub fn sample(&self, frame: f32) -> T {
+        let keys = &self.keyframes;
+        let next = keys.partition_point(|k| k.frame <= frame);
+        if next == 0 {
+            return keys[0].value;
+        }
+        if next == keys.len() {
+            return keys[next - 1].value;
+        }
+
+        let (i, a, b) = (next - 1, &keys[next - 1], &keys[next]);
+        let duration = b.frame - a.frame;
+        let t = (frame - a.frame) / duration;
+        match a.interpolation {
+            Interpolation::Step => a.value,
+            Interpolation::Linear => T::lerp(a.value, b.value, t),
+            Interpolation::Bezier => {
+                // de Casteljau's algorithm, which only interpolates between control points
+                let (h0, h1) = (self.handle(i, duration), self.handle(i + 1, -duration));
+                let (p0, p1, p2) = (
+                    T::lerp(a.value, h0, t),
+                    T::lerp(h0, h1, t),
+                    T::lerp(h1, b.value, t),
+                );
+                let (q0, q1) = (T::lerp(p0, p1, t), T::lerp(p1, p2, t));
+                T::lerp(q0, q1, t)
+            }
+        }
+    }
+
+    /// Returns the Bézier handle of the keyframe for a segment of `duration` frames, negative
+    /// for the segment before it: a third of the segment away, along the slope of the
+    /// keyframe, which is the one between its neighbours.
+    fn handle(&self, i: usize, duration: f32) -> T {
+        let keys = &self.keyframes;
+        let value = keys[i].value;
+        if i == 0 || i == keys.len() - 1 {
+            return value;
+        }
+
+        let (prev, next) = (&keys[i - 1], &keys[i + 1]);
+        if value == prev.value || value == next.value {
+            return value;
+        }
+        let t = duration / (3.0 * (next.frame - prev.frame));
+        value.offset(prev.value, next.value, t)
+    }
+}
+
+/// The animated properties of an [Entity].
+#[derive(Debug, Clone, Default)]
+pub struct EntityAnimation {
+    pub position: Option<Track<Vec3>>,
+    pub rotation: Option<Track<Rotation>>,
+    /// The diffuse color, with channels from 0 to 255.
+    pub color: Option<Track<Vec3>>,
+    /// The light emitted, in multiples of the color. See
+    /// [Material::with_emission](crate::Material::with_emission).
+    pub emission: Option<Track<f32>>,
+}
+
+/// The animated properties of a [Camera].
+#[derive(Debug, Clone, Default)]
+pub struct CameraAnimation {
+    pub position: Option<Track<Vec3>>,
+    /// The yaw, in degrees. See [Camera::set_orientation].
+    pub yaw: Option<Track<f32>>,
+    /// The pitch, in degrees.
+    pub pitch: Option<Track<f32>>,
+    /// The background color, with channels from 0 to 255.
+    pub background: Option<Track<Vec3>>,
+    /// The field of view, in degrees: the vertical one of perspective projections, or the
+    /// one of fisheyes.
+    pub fov: Option<Track<f32>>,
+    /// The height of the view volume of orthographic projections, in world units.
+    pub height: Option<Track<f32>>,
+}
+
+impl EntityAnimation {
+    /// Sets the animated properties of the [Node] and its [Entity] at the frame. The position
+    /// and rotation are relative to the parent node. A position or rotation that changes
+    /// before the next frame becomes a motion, blurred by the shutter of the camera.
+    pub fn apply(&self, node: &mut Node, entity: Option<&mut Entity>, frame: f32) {
+        if let Some(position) = &self.position {
+            set_motion(node.transform_mut(), position, frame);
+        }
+        if let Some(rotation) = &self.rotation {
+            set_spin(node.transform_mut(), rotation, frame);
+        }
+        let entity = match entity {
+            Some(entity) if self.color.is_some() || self.emission.is_some() => entity,
+            _ => return,
+        };
+        let mut material = entity.material().clone();
+        if let Some(color) = &self.color {
+            material = material.with_diffuse(to_color(color.sample(frame)));
+        }
+        if let Some(emission) = &self.emission {
+            // Bézier curves can overshoot the keyframes
+            material = material.with_emission(emission.sample(frame).max(0.0));
+        }
+        entity.set_material(material);
+    }
+
+    /// Returns the frame ranges of the tracks.
+    fn ranges(&self) -> Vec<(f32, f32)> {
+        let vectors = [&self.position, &self.color];
+        vectors
+            .iter()
+            .filter_map(|t| t.as_ref().map(Track::range))
+            .chain(self.rotation.as_ref().map(Track::range))
+            .chain(self.emission.as_ref().map(Track::range))
+            .collect()
+    }
+}
+
+impl CameraAnimation {
+    /// Sets the animated properties of the [Camera] at the frame. A position that changes
+    /// before the next frame becomes a motion, as with [EntityAnimation::apply].
+    pub fn apply(&self, camera: &mut Camera, frame: f32) {
+        if let Some(position) = &self.position {
+            set_motion(camera.transform(), position, frame);
+        }
+        if self.yaw.is_some() || self.pitch.is_some() {
+            let (yaw, pitch) = camera.orientation();
+            let yaw = self.yaw.as_ref().map_or(yaw, |t| t.sample(frame));
+            let pitch = self.pitch.as_ref().map_or(pitch, |t| t.sample(frame));
+            camera.set_orientation(yaw, pitch);
+        }
+        if let Some(background) = &self.background {
+            camera.set_clear_color(to_color(background.sample(frame)));
+        }
+
+        // the tracks that do not apply to the projection are ignored, and the Bézier curves
+        // can overshoot the keyframes, out of the range of the parameters
+        let fov = self.fov.as_ref().map(|t| t.sample(frame));
+        let height = self.height.as_ref().map(|t| t.sample(frame));
+        match (camera.projection(), fov, height) {
+            (Projection::Perspective, Some(fov), _) => camera.set_vertical_fov(fov),
+            (Projection::Fisheye { .. }, Some(fov), _) => {
+                camera.set_projection(Projection::Fisheye {
+                    fov: fov.clamp(MIN_FOV, 360.0),
+                })
+            }
+            (Projection::Orthographic { .. }, _, Some(height)) => {
+                camera.set_projection(Projection::Orthographic {
+                    height: height.max(MIN_ORTHO_HEIGHT),
+                })
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the frame ranges of the tracks.
+    fn ranges(&self) -> Vec<(f32, f32)> {
+        let vectors = [&self.position, &self.background];
+        let scalars = [&self.yaw, &self.pitch, &self.fov, &self.height];
+        vectors
+            .iter()
+            .filter_map(|t| t.as_ref().map(Track::range))
+            .chain(scalars.iter().filter_map(|t| t.as_ref().map(Track::range)))
+            .collect()
+    }
+}
+
+/// The animations of a scene. Each frame is computed from the keyframes only, so the
+/// frames of a sequence can be rendered in any order, e.g split across machines.
+#[derive(Debug, Clone, Default)]
+pub struct Animation {
+    pub camera: CameraAnimation,
+    /// The animations of the nodes of the scene graph, and of their entities.
+    pub entities: Vec<(NodeId, EntityAnimation)>,
+}
+
+impl Animation {
+    /// Returns the first and last frames with a keyframe, or `None` if nothing is animated.
+    pub fn range(&self) -> Option<(f32, f32)> {
+        let entities = self.entities.iter().flat_map(|(_, a)| a.ranges());
+        self.camera
+            .ranges()
+            .into_iter()
+            .chain(entities)
+            .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+    }
+
+    /// Sets the animated properties of the nodes, their entities and the camera at the
+    /// frame. The transforms of the entities must then be resolved from the nodes.
+    pub fn apply(
+        &self,
+        graph: &mut SceneGraph,
+        entities: &mut [Entity],
+        camera: &mut Camera,
+        frame: f32,
+    ) {
+        for (id, animation) in &self.entities {
+            let node = graph.node_mut(*id);
+            let entity = node.entity().and_then(|i| entities.get_mut(i));
+            animation.apply(node, entity, frame);
+        }
+        self.camera.apply(camera, frame);
+    }
+}
+
+/// Sets the position of the [Transform] at the frame, and its end position at the next one.
+fn set_motion(transform: &mut Transform, track: &Track<Vec3>, frame: f32) {
+    let (start, end) = (track.sample(frame), track.sample(frame + 1.0));
+    transform.set_position(start);
+    transform.set_end_position(if end != start { Some(end) } else { None });
+}
+
+/// Sets the rotation of the [Transform] at the frame, and its end rotation at the next one.
+fn set_spin(transform: &mut Transform, track: &Track<Rotation>, frame: f32) {
+    let (start, end) = (track.sample(frame), track.sample(frame + 1.0));
+    transform.set_rotation(start);
+    transform.set_end_rotation(if end != start { Some(end) } else { None });
+}
+
+fn to_color(v: Vec3) -> Color {
+    let channel = |c: f32| c.round().clamp(0.0, 255.0) as u8;
+    Color::new(channel(v.x), channel(v.y), channel(v.z))
+}
+
+/// An inclusive range of frames to render, e.g `1..24`, or a single frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FrameRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl Display for FrameRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+impl FromStr for FrameRange {
+    type Err = String;
+
+    /// Parses a range of the form `start..end`, where both frames are rendered, or a frame.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<u32>()
+                .map_err(|_| format!("invalid frame: {}", v))
+        };
+        let (start, end) = match s.split_once("..") {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => (parse(s)?, parse(s)?),
+        };
+        if end < start {
+            return Err(format!("the frame range {} is empty", s));
+        }
+
+        Ok(FrameRange { start, end })
+    }
+}
+
+impl FrameRange {
+    pub fn new(start: u32, end: u32) -> Self {
+        FrameRange { start, end }
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = u32> {
+        self.start..=self.end
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interpolations() {
+        let keys = |interpolation| {
+            Track::new(vec![
+                Keyframe::new(10.0, 4.0, interpolation),
+                Keyframe::new(0.0, 0.0, interpolation),
+                Keyframe::new(20.0, 4.0, interpolation),
+            ])
+            .unwrap()
+        };
+
+        let linear = keys(Interpolation::Linear);
+        assert_eq!(0.0, linear.sample(-5.0));
+        assert_eq!(1.0, linear.sample(2.5));
+        assert_eq!(4.0, linear.sample(15.0));
+        assert_eq!(4.0, linear.sample(25.0));
+
+        let step = keys(Interpolation::Step);
+        assert_eq!(0.0, step.sample(9.9));
+        assert_eq!(4.0, step.sample(10.0));
+
+        // eases out of the first keyframe, and stays flat between the equal keyframes
+        let bezier = keys(Interpolation::Bezier);
+        assert!(bezier.sample(1.0) < linear.sample(1.0));
+        assert!((bezier.sample(5.0) - 2.0).abs() < 0.5);
+        assert!((bezier.sample(10.0) - 4.0).abs() < 1e-6);
+        assert_eq!(4.0, bezier.sample(15.0));
+        assert!(Track::<f32>::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn rotations_are_interpolated_spherically() {
+        let around_y =
+            |degrees: f32| Rotation::from_axis_angle(&Vec3::y_axis(), degrees.to_radians());
+        let keys = |interpolation| {
+            Track::new(vec![
+                Keyframe::new(0.0, around_y(0.0), interpolation),
+                Keyframe::new(10.0, around_y(90.0), interpolation),
+                Keyframe::new(20.0, around_y(270.0), interpolation),
+            ])
+            .unwrap()
+        };
+
+        let linear = keys(Interpolation::Linear);
+        assert!(linear.sample(5.0).angle_to(&around_y(45.0)) < 1e-4);
+        // the shortest way, through 180 degrees
+        assert!(linear.sample(15.0).angle_to(&around_y(180.0)) < 1e-4);
+
+        let bezier = keys(Interpolation::Bezier);
+        assert!(bezier.sample(10.0).angle_to(&around_y(90.0)) < 1e-4);
+        let eased = bezier.sample(1.0);
+        assert!(eased.angle() < linear.sample(1.0).angle());
+        assert!((eased * Vec3::y()).dot(&Vec3::y()) > 1.0 - 1e-4);
+    }
+
+    #[test]
+    fn parse_frame_ranges() {
+        assert_eq!(Ok(FrameRange { start: 1, end: 24 }), "1..24".parse());
+        assert_eq!(Ok(FrameRange { start: 7, end: 7 }), "7".parse());
+        assert_eq!(3, "1..3".parse::<FrameRange>().unwrap().frames().count());
+        assert!("5..1".parse::<FrameRange>().is_err());
+        assert!("a..b".parse::<FrameRange>().is_err());
+    }
+}
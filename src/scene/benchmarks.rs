@@ -0,0 +1,232 @@
+//! The standard scenes rendered by the `bench` command.
+use crate::math::{Rotation, Vec3};
+use crate::rendering::{Color, Material, GRAY};
+use crate::scene::primitives::{Mesh, Sphere};
+use crate::scene::{Camera, Entity, Primitive, Scene, Transform};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The number of spheres along each side of the grid of [Benchmark::Spheres].
+const GRID_SIZE: u32 = 24;
+
+/// The number of instances along each side of the grid of [Benchmark::Instances].
+const INSTANCE_GRID_SIZE: u32 = 12;
+
+/// The number of segments around the meshes of [Benchmark::Instances], which have half as
+/// many rings: 2 * 48 * 24 triangles.
+const MESH_SEGMENTS: u32 = 48;
+
+/// A standard scene, to measure the performance of the renderer.
+///
+/// There is no glass caustics scene yet, as there are no refractive materials to build
+/// it from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Benchmark {
+    /// The built-in scene, see [Scene::new].
+    Default,
+    /// A large grid of small spheres, that stresses the intersection of the scene.
+    Spheres,
+    /// A grid of moving spheres, seen with a long shutter.
+    MotionBlur,
+    /// A grid of rotated instances of a single triangle mesh, that stresses the
+    /// two-level acceleration structure.
+    Instances,
+}
+
+impl Benchmark {
+    pub const ALL: [Benchmark; 4] = [
+        Benchmark::Default,
+        Benchmark::Spheres,
+        Benchmark::MotionBlur,
+        Benchmark::Instances,
+    ];
+
+    /// Builds the scene of the benchmark.
+    pub fn build(&self) -> Scene {
+        match self {
+            Benchmark::Default => Scene::new(),
+            Benchmark::Spheres => grid(false),
+            Benchmark::MotionBlur => grid(true),
+            Benchmark::Instances => instances(),
+        }
+    }
+}
+
+impl Display for Benchmark {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Benchmark::Default => write!(f, "default"),
+            Benchmark::Spheres => write!(f, "spheres"),
+            Benchmark::MotionBlur => write!(f, "motion-blur"),
+            Benchmark::Instances => write!(f, "instances"),
+        }
+    }
+}
+
+impl FromStr for Benchmark {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "default" => Ok(Benchmark::Default),
+            "spheres" => Ok(Benchmark::Spheres),
+            "motion-blur" | "motion" => Ok(Benchmark::MotionBlur),
+            "instances" | "mesh" => Ok(Benchmark::Instances),
+            _ => Err(format!("unknown benchmark scene: {}", s)),
+        }
+    }
+}
+
+/// A grid of small spheres of various colors on the ground, seen from above.
+fn grid(moving: bool) -> Scene {
+    let spacing = 0.5;
+    let offset = (GRID_SIZE - 1) as f32 * spacing / 2.0;
+    let sphere: Arc<dyn Primitive> = Arc::new(Sphere::new(0.2));
+    let mut entities = Vec::new();
+    for i in 0..GRID_SIZE {
+        for j in 0..GRID_SIZE {
+            let position = Vec3::new(
+                i as f32 * spacing - offset,
+                0.2,
+                j as f32 * spacing - offset,
+            );
+            let mut transform = Transform::default().with_position(position);
+            if moving {
+                transform.set_end_position(Some(
+                    position + Vec3::new(0.0, 0.2 * ((i + j) % 3) as f32, 0.0),
+                ));
+            }
+            let color = Color::new(
+                (i * 255 / GRID_SIZE) as u8,
+                (j * 255 / GRID_SIZE) as u8,
+                160,
+            );
+            entities.push(Entity::new(
+                transform,
+                Material::from_diffuse(color),
+                sphere.clone(),
+            ));
+        }
+    }
+    let ground = Transform::default().with_position(Vec3::new(0.0, -100.0, 0.0));
+    entities.push(Entity::new(
+        ground,
+        Material::from_diffuse(GRAY),
+        Arc::new(Sphere::new(100.0)),
+    ));
+
+    let mut camera = Camera::new();
+    camera.transform().set_position(Vec3::new(0.0, 6.0, 10.0));
+    camera.set_orientation(0.0, -30.0);
+    if moving {
+        camera
+            .set_shutter(0.0, 1.0)
+            .expect("the shutter spans the frame");
+    }
+
+    Scene::from_parts(entities, camera)
+}
+
+/// A grid of instances of a finely tessellated, squashed sphere, each rotated differently.
+fn instances() -> Scene {
+    let spacing = 1.0;
+    let offset = (INSTANCE_GRID_SIZE - 1) as f32 * spacing / 2.0;
+    let mesh: Arc<dyn Primitive> = Arc::new(ellipsoid(Vec3::new(0.45, 0.25, 0.3), MESH_SEGMENTS));
+    let mut entities = Vec::new();
+    for i in 0..INSTANCE_GRID_SIZE {
+        for j in 0..INSTANCE_GRID_SIZE {
+            let position = Vec3::new(
+                i as f32 * spacing - offset,
+                0.3,
+                j as f32 * spacing - offset,
+            );
+            let mut transform = Transform::default().with_position(position);
+            let angle = (i * INSTANCE_GRID_SIZE + j) as f32 * 0.7;
+            transform.set_rotation(Rotation::from_euler_angles(0.0, angle, 0.3 * angle.sin()));
+            let color = Color::new(
+                200,
+                (i * 255 / INSTANCE_GRID_SIZE) as u8,
+                (j * 255 / INSTANCE_GRID_SIZE) as u8,
+            );
+            entities.push(Entity::new(
+                transform,
+                Material::from_diffuse(color),
+                mesh.clone(),
+            ));
+        }
+    }
+    let ground = Transform::default().with_position(Vec3::new(0.0, -100.0, 0.0));
+    entities.push(Entity::new(
+        ground,
+        Material::from_diffuse(GRAY),
+        Arc::new(Sphere::new(100.0)),
+    ));
+
+    let mut camera = Camera::new();
+    camera.transform().set_position(Vec3::new(0.0, 5.0, 9.0));
+    camera.set_orientation(0.0, -30.0);
+
+    Scene::from_parts(entities, camera)
+}
+
+/// Tessellates an ellipsoid with the specified radii into a [Mesh], with `segments`
+/// segments around its vertical axis and half as many rings.
+fn ellipsoid(radii: Vec3, segments: u32) -> Mesh {
+    let rings = segments / 2;
+    let mut vertices = Vec::new();
+    for ring in 0..=rings {
+        let theta = std::f32::consts::PI * ring as f32 / rings as f32;
+        for segment in 0..segments {
+            let phi = 2.0 * std::f32::consts::PI * segment as f32 / segments as f32;
+            let direction = Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            );
+            vertices.push(direction.component_mul(&radii));
+        }
+    }
+
+    let index = |ring: u32, segment: u32| ring * segments + segment % segments;
+    let mut triangles = Vec::new();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let (a, b) = (index(ring, segment), index(ring, segment + 1));
+            let (c, d) = (index(ring + 1, segment), index(ring + 1, segment + 1));
+            triangles.push([a, c, b]);
+            triangles.push([b, c, d]);
+        }
+    }
+
+    Mesh::new(vertices, triangles).expect("the indices are within the vertices")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_names() {
+        for benchmark in Benchmark::ALL.iter() {
+            assert_eq!(Ok(*benchmark), benchmark.to_string().parse());
+        }
+        assert!("glass".parse::<Benchmark>().is_err());
+    }
+
+    #[test]
+    fn instances_share_their_mesh() {
+        let scene = Benchmark::Instances.build();
+        let entities = scene.entities();
+
+        let count = (INSTANCE_GRID_SIZE * INSTANCE_GRID_SIZE) as usize;
+        assert_eq!(count + 1, entities.len());
+        assert_eq!(
+            (2 * MESH_SEGMENTS * MESH_SEGMENTS / 2) as usize,
+            entities[0].primitive().triangle_count()
+        );
+        assert!(entities[1..count]
+            .iter()
+            .all(|e| Arc::ptr_eq(e.shared_primitive(), entities[0].shared_primitive())));
+    }
+}
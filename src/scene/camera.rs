@@ -1,13 +1,25 @@
-use crate::math::{Ray, Vec3};
-use crate::rendering::{Color, Sample, Pixel, PixelSize, RenderOpts, RenderTarget, Sampling, SubPixel, BLACK};
-use crate::scene::{Scene, Transform, Hittable};
+use crate::error::Error;
+use crate::math::{Ray, Rotation, Vec3};
+use crate::rendering::aov::{AovImage, AovSample};
+use crate::rendering::hdr::linear;
+use crate::rendering::sampler::Sampler;
+use crate::rendering::{
+    Checkpoint, Color, Film, HdrBuffer, Pixel, PixelRect, PixelSize, RayKind, RayStats, RenderOpts,
+    RenderTarget, Sample, SampleCounts, SubPixel, BLACK,
+};
+use crate::scene::{Eye, Hit, Projection, Scene, Stereo, Transform};
 use nameof::name_of_type;
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
 
 #[derive(Debug)]
 pub struct Camera {
     transform: Transform,
     clear_color: Color,
+    projection: Projection,
+    stereo: Stereo,
+    /// The times at which the shutter opens and closes. The frame spans from 0 to 1.
+    shutter: (f32, f32),
     focal_length: f32,
     aspect: f32,
     width: f32,
@@ -18,13 +30,20 @@ impl Display for Camera {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{} (background: {})",
+            "{} ({}, background: {})",
             name_of_type!(Camera),
+            self.projection,
             self.clear_color
         )
     }
 }
 
+impl Default for Camera {
+    fn default() -> Self {
+        Camera::new()
+    }
+}
+
 impl Camera {
     pub fn new() -> Self {
         let aspect = 16.0 / 9.0;
@@ -34,6 +53,9 @@ impl Camera {
         Camera {
             transform: Transform::default(),
             clear_color: BLACK,
+            projection: Projection::default(),
+            stereo: Stereo::default(),
+            shutter: (0.0, 0.0),
             focal_length: 1f32,
             aspect,
             width,
@@ -48,180 +70,425 @@ impl Camera {
         new
     }
 
+    pub fn set_clear_color(&mut self, color: Color) {
+        self.clear_color = color;
+    }
+
+    /// Returns the color of the pixels that do not see any entity.
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
+    }
+
+    /// Sets the [Projection]
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    /// Gets the [Projection]
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Sets the [Stereo] rig parameters, used when rendering an [Eye].
+    pub fn set_stereo(&mut self, stereo: Stereo) {
+        self.stereo = stereo;
+    }
+
+    /// Gets the [Stereo] rig parameters
+    pub fn stereo(&self) -> Stereo {
+        self.stereo
+    }
+
+    /// Sets the shutter open and close times. Objects moving during this interval are blurred.
+    /// Returns [Error::Scene] unless the shutter opens and closes within the frame (from 0
+    /// to 1), in this order.
+    pub fn set_shutter(&mut self, open: f32, close: f32) -> Result<(), Error> {
+        let in_frame = |t: f32| (0.0..=1.0).contains(&t);
+        if !(in_frame(open) && in_frame(close) && open <= close) {
+            return Err(Error::Scene(format!(
+                "the camera shutter must satisfy 0 <= open <= close <= 1, not [{}, {}]",
+                open, close
+            )));
+        }
+
+        self.shutter = (open, close);
+        Ok(())
+    }
+
+    /// Gets the shutter open and close times.
+    pub fn shutter(&self) -> (f32, f32) {
+        self.shutter
+    }
+
     /// Gets the [Transform] associated with this [Camera]
     pub fn transform(&mut self) -> &mut Transform {
         &mut self.transform
     }
 
-    /// Gets the aspect ratio
+    /// Gets the position of the camera at the start of the frame.
+    pub fn position(&self) -> Vec3 {
+        self.transform.position()
+    }
+
+    /// Orients the camera with a yaw (around the Y axis, counterclockwise seen from above)
+    /// and a pitch (around the X axis, positive upward), in degrees. The camera looks
+    /// down -Z when both are zero.
+    pub fn set_orientation(&mut self, yaw: f32, pitch: f32) {
+        self.transform
+            .set_rotation(orientation_rotation(yaw, pitch));
+    }
+
+    /// Sets the yaw and pitch of the camera at the end of the frame, in degrees, or `None`
+    /// for a camera that does not turn. See [Camera::set_orientation].
+    pub fn set_end_orientation(&mut self, orientation: Option<(f32, f32)>) {
+        self.transform
+            .set_end_rotation(orientation.map(|(yaw, pitch)| orientation_rotation(yaw, pitch)));
+    }
+
+    /// Gets the yaw and pitch of the camera, in degrees. See [Camera::set_orientation].
+    pub fn orientation(&self) -> (f32, f32) {
+        let forward = self.forward();
+        let yaw = (-forward.x).atan2(-forward.z);
+        let pitch = forward.y.clamp(-1.0, 1.0).asin();
+
+        (yaw.to_degrees(), pitch.to_degrees())
+    }
+
+    /// Gets the direction the camera is looking at.
+    pub fn forward(&self) -> Vec3 {
+        self.transform
+            .transform_vector(Vec3::new(0.0, 0.0, -1.0), 0.0)
+    }
+
+    /// Gets the direction to the right of the camera.
+    pub fn right(&self) -> Vec3 {
+        self.transform
+            .transform_vector(Vec3::new(1.0, 0.0, 0.0), 0.0)
+    }
+
+    /// Gets the vertical field of view of the perspective projection, in degrees.
+    pub fn vertical_fov(&self) -> f32 {
+        (2.0 * (self.height / 2.0).atan2(self.focal_length)).to_degrees()
+    }
+
+    /// Sets the vertical field of view of the perspective projection, in degrees, clamped to
+    /// [1, 179].
+    pub fn set_vertical_fov(&mut self, fov: f32) {
+        let fov = fov.clamp(1.0, 179.0).to_radians();
+        self.height = 2.0 * self.focal_length * (fov / 2.0).tan();
+        self.width = self.aspect * self.height;
+    }
+
+    /// Gets the horizontal field of view of the perspective projection, in degrees.
+    pub fn horizontal_fov(&self) -> f32 {
+        (2.0 * (self.height * self.aspect / 2.0).atan2(self.focal_length)).to_degrees()
+    }
+
+    /// Gets the aspect ratio of the image, taking into account the requirements
+    /// of the [Projection] (e.g 2:1 for equirectangular panoramas).
     pub fn aspect(&self) -> f32 {
-        self.aspect
+        self.projection.preferred_aspect().unwrap_or(self.aspect)
     }
 
+    /// Renders the [Scene] into the [RenderTarget], and returns the number of
+    /// samples taken for each pixel.
+    ///
+    /// If the [RenderOpts] have a crop window, only the pixels of the window are traced
+    /// and updated: the rest of the target is left untouched.
     pub fn render(
         &self,
         scene: &Scene,
         target: &mut dyn RenderTarget,
         opts: &RenderOpts,
         progress_func: &dyn Fn(f32),
-    ) {
-        target.clear(self.clear_color);
+    ) -> SampleCounts {
+        if opts.crop.is_none() {
+            target.clear(self.clear_color);
+        }
+
+        let mut film = Film::new(target.size(), opts.filter)
+            .with_region(opts.crop.map(|c| c.rect(target.size())));
+        let counts = self.render_film(scene, &mut film, opts, progress_func);
+
+        film.resolve(target);
+
+        counts
+    }
+
+    /// Renders the [Scene] into the linear [HdrBuffer], and returns the number of
+    /// samples taken for each pixel, along with the images of the requested AOVs.
+    ///
+    /// As with [Camera::render], a crop window restricts the pixels that are updated.
+    /// The AOV images cover the whole image, but are empty outside of the window.
+    pub fn render_hdr(
+        &self,
+        scene: &Scene,
+        target: &mut HdrBuffer,
+        opts: &RenderOpts,
+        progress_func: &dyn Fn(f32),
+    ) -> (SampleCounts, Vec<AovImage>) {
+        if opts.crop.is_none() {
+            target.clear(self.clear_color);
+        }
+
+        let mut film = Film::new(target.size(), opts.filter)
+            .with_region(opts.crop.map(|c| c.rect(target.size())))
+            .with_aovs(opts.aovs);
+        let counts = self.render_film(scene, &mut film, opts, progress_func);
+
+        film.resolve_hdr(target);
+
+        (counts, film.aov_images())
+    }
 
-        log::info!("rendering...");
+    /// Renders the [Scene] by adding samples to the [Film], and returns the number of
+    /// samples taken for each pixel of its [region](Film::region).
+    pub fn render_film(
+        &self,
+        scene: &Scene,
+        film: &mut Film,
+        opts: &RenderOpts,
+        progress_func: &dyn Fn(f32),
+    ) -> SampleCounts {
+        log::info!("rendering into {}...", film);
         let mut progress: f32 = 0f32;
 
-        let render_func = match opts.samples {
-                Sampling::Disabled => Self::render_pixel_1_sample,
-                Sampling::Samples4 => Self::render_pixel_4_samples,
-                Sampling::Samples16 => Self::render_pixel_16_samples,
-            };
+        let mut sampler = opts.sampler.create(opts.max_samples(), opts.seed);
+        let region = film.region();
+        let sampled = film.sampled_region();
+        let mut counts = SampleCounts::new(region.size);
 
-        for y in 0..target.size().height {
-            self.render_scanline(y, &scene, target, render_func);
+        for y in sampled.origin.y..sampled.origin.y + sampled.size.height {
+            self.render_scanline(y, scene, film, opts, sampler.as_mut(), &mut counts);
 
-            progress += 1f32 / (target.size().height as f32);
+            progress += 1f32 / (sampled.size.height as f32);
             progress_func(progress);
         }
+
+        counts
     }
 
-    /// Renders a single scanline
-    fn render_scanline(
+    /// Adds the sample `index` of every pixel to the [Film]. Progressive rendering
+    /// runs successive passes (0, 1, 2...) into the same [Film].
+    ///
+    /// Only the pixels that contribute to the [region](Film::region) of the [Film] are sampled.
+    pub(crate) fn render_pass(
         &self,
-        row: u32,
         scene: &Scene,
-        target: &mut dyn RenderTarget,
-        render_func: fn(&Camera, Pixel, &Scene, PixelSize, &mut Sample)
+        film: &mut Film,
+        opts: &RenderOpts,
+        sampler: &mut dyn Sampler,
+        index: u32,
     ) {
-        for col in 0..target.size().width {
-            self.render_pixel(Pixel::new(col, row), scene, target, render_func);
-        }
+        let rows = 0..film.sampled_region().size.height;
+        self.render_pass_rows(scene, film, opts, sampler, index, rows)
     }
 
-    /// Render a single pixel
-    fn render_pixel(
+    /// Adds the sample `index` of the pixels of some rows of the sampled region of the
+    /// [Film], relative to its top, e.g to split a pass of [Camera::render_pass] across
+    /// frames. Rendering every row once is the same as rendering the whole pass.
+    pub(crate) fn render_pass_rows(
         &self,
-        pixel: Pixel,
         scene: &Scene,
-        target: &mut dyn RenderTarget,
-        render_func: fn(&Camera, Pixel, &Scene, PixelSize, &mut Sample)
+        film: &mut Film,
+        opts: &RenderOpts,
+        sampler: &mut dyn Sampler,
+        index: u32,
+        rows: Range<u32>,
     ) {
-        let mut hdr = Sample::default();
-
-        render_func(&self, pixel, scene, target.size(), &mut hdr);
-
-        let color = Color::from(hdr);
-
-        target.set(pixel, color);
+        let sampled = film.sampled_region();
+        let rows = rows.start.min(sampled.size.height)..rows.end.min(sampled.size.height);
+        let band = PixelRect::new(
+            Pixel::new(sampled.origin.x, sampled.origin.y + rows.start),
+            PixelSize::new(sampled.size.width, rows.end.saturating_sub(rows.start)),
+        );
+        for pixel in band.pixels() {
+            sampler.start_sample(pixel, index);
+            let (position, radiance, aov) = self.sample(
+                pixel,
+                scene,
+                film.size(),
+                opts,
+                sampler,
+                film.ray_stats_mut(),
+            );
+            film.add_sample(position, radiance);
+            if let Some(aov) = aov {
+                film.add_aov_sample(pixel, &aov);
+            }
+        }
     }
 
-    /// Render a single pixel
-    fn render_pixel_1_sample(
+    /// Renders the [Scene] pass by pass into the [Checkpoint], until every pixel of its
+    /// region has enough samples. Each pass adds one sample to the pixels that need more,
+    /// so the result is the same as [Camera::render_film], even with adaptive sampling.
+    ///
+    /// The render starts from the passes already in the [Checkpoint], and `on_pass` is
+    /// called after each pass, e.g to save the [Checkpoint]. Returning false stops the render.
+    pub(crate) fn render_resumable(
         &self,
-        pixel: Pixel,
         scene: &Scene,
-        size: PixelSize,
-        hdr: &mut Sample,
+        checkpoint: &mut Checkpoint,
+        opts: &RenderOpts,
+        progress_func: &dyn Fn(f32),
+        on_pass: &mut dyn FnMut(&Checkpoint) -> bool,
     ) {
-        let center = SubPixel::from(pixel);
+        let mut sampler = opts.sampler.create(opts.max_samples(), opts.seed);
+        let max_passes = opts.max_samples();
+
+        while checkpoint.passes() < max_passes {
+            let (film, samples) = checkpoint.state_mut();
+            let width = film.size().width;
+            for pixel in film.sampled_region().pixels() {
+                let hdr = &mut samples[(pixel.x + pixel.y * width) as usize];
+                if !opts.needs_more_samples(hdr) {
+                    continue;
+                }
+
+                sampler.start_sample(pixel, hdr.samples);
+                let (position, radiance, aov) = self.sample(
+                    pixel,
+                    scene,
+                    film.size(),
+                    opts,
+                    sampler.as_mut(),
+                    film.ray_stats_mut(),
+                );
+                *hdr += radiance;
+                film.add_sample(position, radiance);
+                if let Some(aov) = aov {
+                    film.add_aov_sample(pixel, &aov);
+                }
+            }
+
+            checkpoint.end_pass();
+            progress_func(checkpoint.passes() as f32 / max_passes as f32);
+            if !on_pass(checkpoint) {
+                break;
+            }
+        }
+    }
 
-        self.sample(center, scene, size, hdr);
+    /// Renders a single scanline of the sampled region of the [Film]. The sample counts
+    /// are relative to its region.
+    fn render_scanline(
+        &self,
+        row: u32,
+        scene: &Scene,
+        film: &mut Film,
+        opts: &RenderOpts,
+        sampler: &mut dyn Sampler,
+        counts: &mut SampleCounts,
+    ) {
+        let region = film.region();
+        let sampled = film.sampled_region();
+        for col in sampled.origin.x..sampled.origin.x + sampled.size.width {
+            let pixel = Pixel::new(col, row);
+            let samples = self.render_pixel(pixel, scene, film, opts, sampler);
+            if region.contains(pixel) {
+                counts.set(
+                    Pixel::new(col - region.origin.x, row - region.origin.y),
+                    samples,
+                );
+            }
+        }
     }
 
-    /// Render a single pixel with 4 samples (2*2)
-    fn render_pixel_4_samples(
+    /// Render a single pixel, and returns the number of samples taken.
+    ///
+    /// The samples are splatted into the [Film], but the convergence of the pixel
+    /// is only estimated from its own samples.
+    fn render_pixel(
         &self,
         pixel: Pixel,
         scene: &Scene,
-        size: PixelSize,
-        hdr: &mut Sample,
-    ) {
-        // The pixel is divided into multiple samples in the following pattern
-        // +--------+
-        // | ul  ur |
-        // | ll  lr |
-        // +--------+
-        let center = SubPixel::from(pixel);
-        const SUB_OFFSET: f32 = 0.25;
-        let ur = center.with_offset(SUB_OFFSET, SUB_OFFSET);
-        let ll = center.with_offset(-SUB_OFFSET, -SUB_OFFSET);
-        let lr = center.with_offset(SUB_OFFSET, -SUB_OFFSET);
-        let ul = center.with_offset(-SUB_OFFSET, SUB_OFFSET);
-
-        self.sample(ur, scene, size, hdr);
-        self.sample(ul, scene, size, hdr);
-        self.sample(lr, scene, size, hdr);
-        self.sample(ll, scene, size, hdr);
-    }
-
-    /// Render a single pixel with 16 samples (4*4)
-    fn render_pixel_16_samples(
+        film: &mut Film,
+        opts: &RenderOpts,
+        sampler: &mut dyn Sampler,
+    ) -> u32 {
+        let mut hdr = Sample::default();
+
+        while opts.needs_more_samples(&hdr) {
+            sampler.start_sample(pixel, hdr.samples);
+            let (position, radiance, aov) = self.sample(
+                pixel,
+                scene,
+                film.size(),
+                opts,
+                sampler,
+                film.ray_stats_mut(),
+            );
+
+            hdr += radiance;
+            film.add_sample(position, radiance);
+            if let Some(aov) = aov {
+                film.add_aov_sample(pixel, &aov);
+            }
+        }
+
+        hdr.samples
+    }
+
+    /// Traces the current sample of the [Sampler] for the pixel, and returns the
+    /// position of the sample along with its linear radiance, and its AOV values if requested.
+    /// The traced rays are counted into the [RayStats].
+    fn sample(
         &self,
         pixel: Pixel,
         scene: &Scene,
         size: PixelSize,
-        hdr: &mut Sample,
-    ) {
-        // The pixel is divided into multiple samples in the following pattern
-        // +-----------------+
-        // | r00 r10 r20 r30 |
-        // | r01 r11 r21 r31 |
-        // | r02 r12 r22 r32 |
-        // | r03 r13 r23 r33 |
-        // +-----------------+
-        let center = SubPixel::from(pixel);
-        const SUB_OFFSET: f32 = 0.125;
-        let r00 = center.with_offset(-SUB_OFFSET*2.0, SUB_OFFSET*2.0);
-        let r10 = center.with_offset(-SUB_OFFSET*1.0, SUB_OFFSET*2.0);
-        let r20 = center.with_offset(SUB_OFFSET*1.0, SUB_OFFSET*2.0);
-        let r30 = center.with_offset(SUB_OFFSET*2.0, SUB_OFFSET*2.0);
-
-        let r01 = center.with_offset(-SUB_OFFSET*2.0, SUB_OFFSET*1.0);
-        let r11 = center.with_offset(-SUB_OFFSET*1.0, SUB_OFFSET*1.0);
-        let r21 = center.with_offset(SUB_OFFSET*1.0, SUB_OFFSET*1.0);
-        let r31 = center.with_offset(SUB_OFFSET*2.0, SUB_OFFSET*1.0);
-
-        let r02 = center.with_offset(-SUB_OFFSET*2.0, -SUB_OFFSET*1.0);
-        let r12 = center.with_offset(-SUB_OFFSET*1.0, -SUB_OFFSET*1.0);
-        let r22 = center.with_offset(SUB_OFFSET*1.0, -SUB_OFFSET*1.0);
-        let r32 = center.with_offset(SUB_OFFSET*2.0, -SUB_OFFSET*1.0);
-
-        let r03 = center.with_offset(-SUB_OFFSET*2.0, -SUB_OFFSET*2.0);
-        let r13 = center.with_offset(-SUB_OFFSET*1.0, -SUB_OFFSET*2.0);
-        let r23 = center.with_offset(SUB_OFFSET*1.0, -SUB_OFFSET*2.0);
-        let r33 = center.with_offset(SUB_OFFSET*2.0, -SUB_OFFSET*2.0);
-
-        self.sample(r00, scene, size, hdr);
-        self.sample(r10, scene, size, hdr);
-        self.sample(r20, scene, size, hdr);
-        self.sample(r30, scene, size, hdr);
-
-        self.sample(r01, scene, size, hdr);
-        self.sample(r11, scene, size, hdr);
-        self.sample(r21, scene, size, hdr);
-        self.sample(r31, scene, size, hdr);
-
-        self.sample(r02, scene, size, hdr);
-        self.sample(r12, scene, size, hdr);
-        self.sample(r22, scene, size, hdr);
-        self.sample(r32, scene, size, hdr);
-
-        self.sample(r03, scene, size, hdr);
-        self.sample(r13, scene, size, hdr);
-        self.sample(r23, scene, size, hdr);
-        self.sample(r33, scene, size, hdr);
-    }
-
-    fn sample(&self, subpix: SubPixel, scene: &Scene, size: PixelSize, hdr: &mut Sample) {
+        opts: &RenderOpts,
+        sampler: &mut dyn Sampler,
+        stats: &mut RayStats,
+    ) -> (SubPixel, [f32; 3], Option<AovSample>) {
+        let (dx, dy) = sampler.get_2d();
+        let subpix = SubPixel::from(pixel).with_offset(dx - 0.5, dy - 0.5);
         let uv = self.uv(subpix, size);
-        let ray = self.pixel_to_ray(uv);
-
-        *hdr += self.raytrace(scene, &ray);
+        let time = self.shutter_time(sampler.get_1d());
+
+        let ray = self.eye_ray(uv, time, opts.eye);
+        let hit = ray.and_then(|ray| scene.trace(&ray, RayKind::Primary, stats));
+        let radiance = match &hit {
+            Some(hit) => hit.material().radiance(),
+            None => linear(self.clear_color),
+        };
+
+        let aov = if opts.aovs.is_empty() {
+            None
+        } else {
+            Some(match (&ray, &hit) {
+                (Some(ray), Some(hit)) => self.aov_sample(ray, hit, radiance),
+                _ => AovSample::background(linear(self.clear_color)),
+            })
+        };
+
+        (subpix, radiance, aov)
     }
 
-    fn raytrace(&self, scene: &Scene, ray: &Ray) -> Color {
-        match &scene.hit(ray) {
-            Some(hit) => hit.material().diffuse_color(),
-            None => self.clear_color
+    /// Returns the AOV values of the surface hit by a primary ray.
+    fn aov_sample(&self, ray: &Ray, hit: &Hit, radiance: [f32; 3]) -> AovSample {
+        let offset = hit.position() - ray.origin();
+        let depth = match self.projection {
+            Projection::Perspective | Projection::Orthographic { .. } => {
+                offset.dot(&self.forward())
+            }
+            Projection::Fisheye { .. } | Projection::Equirectangular => offset.magnitude(),
+        };
+
+        AovSample {
+            depth,
+            normal: hit.normal(),
+            camera_normal: self
+                .transform
+                .inverse_transform_vector(hit.normal(), ray.time()),
+            albedo: linear(hit.material().diffuse_color()),
+            position: hit.position(),
+            uv: hit.uv(),
+            object_id: hit.object_id(),
+            material_id: hit.material().id(),
+            // without a lighting model, all the light is direct
+            direct: radiance,
+            indirect: [0.0; 3],
         }
     }
 
@@ -230,16 +497,199 @@ impl Camera {
         let v = pixel.y / (size.height - 1) as f32;
 
         // the viewport is Y-up, but the framebuffer is Y-down
-        (u, 1.0-v)
+        (u, 1.0 - v)
+    }
+
+    /// Maps a sample value in [0, 1) to a time within the shutter interval.
+    fn shutter_time(&self, u: f32) -> f32 {
+        let (open, close) = self.shutter;
+
+        open + (close - open) * u
     }
 
-    fn pixel_to_ray(&self, uv: (f32, f32)) -> Ray {
-        let horiz = Vec3::new(self.width, 0f32, 0f32);
-        let vert = Vec3::new(0f32, self.height, 0f32);
-        let fwd = Vec3::new(0.0, 0.0, self.focal_length);
-        let origin = self.transform.position();
-        let ll = origin - horiz / 2.0 - vert / 2.0 - fwd;
+    /// Returns the primary ray for the viewport coordinates, as seen from the specified [Eye],
+    /// or from the center of the camera if `eye` is `None`.
+    fn eye_ray(&self, uv: (f32, f32), time: f32, eye: Option<Eye>) -> Option<Ray> {
+        let ray = self.pixel_to_ray(uv)?;
+
+        let ray = match eye {
+            None => ray,
+            Some(eye) => {
+                let panoramic = matches!(
+                    self.projection,
+                    Projection::Fisheye { .. } | Projection::Equirectangular
+                );
+                self.stereo.eye_ray(ray, eye, panoramic)
+            }
+        };
+
+        let origin = self.transform.transform_point(ray.origin(), time);
+        let direction = self.transform.transform_vector(ray.direction(), time);
+        Some(Ray::new(origin, direction).with_time(time))
+    }
+
+    /// Returns the camera-space primary ray for the viewport coordinates, using the current
+    /// [Projection]. Returns `None` if the coordinates are not covered by the projection.
+    fn pixel_to_ray(&self, uv: (f32, f32)) -> Option<Ray> {
+        let origin = Vec3::zeros();
+
+        match self.projection {
+            Projection::Perspective => {
+                let horiz = Vec3::new(self.width, 0f32, 0f32);
+                let vert = Vec3::new(0f32, self.height, 0f32);
+                let fwd = Vec3::new(0.0, 0.0, self.focal_length);
+                let ll = origin - horiz / 2.0 - vert / 2.0 - fwd;
+
+                Some(Ray::new(origin, ll + horiz * uv.0 + vert * uv.1 - origin))
+            }
+            Projection::Orthographic { height } => {
+                let horiz = Vec3::new(height * self.aspect(), 0f32, 0f32);
+                let vert = Vec3::new(0f32, height, 0f32);
+                let ll = origin - horiz / 2.0 - vert / 2.0;
+
+                Some(Ray::new(
+                    ll + horiz * uv.0 + vert * uv.1,
+                    Vec3::new(0.0, 0.0, -1.0),
+                ))
+            }
+            Projection::Fisheye { .. } | Projection::Equirectangular => self
+                .projection
+                .spherical_direction(uv, self.aspect())
+                .map(|dir| Ray::new(origin, dir)),
+        }
+    }
+}
+
+/// Returns the rotation of a camera with the yaw and pitch, in degrees.
+fn orientation_rotation(yaw: f32, pitch: f32) -> Rotation {
+    let yaw = Rotation::from_axis_angle(&Vec3::y_axis(), yaw.to_radians());
+    let pitch = Rotation::from_axis_angle(&Vec3::x_axis(), pitch.to_radians());
+    yaw * pitch
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rendering::{CropWindow, FrameBuffer, SamplerKind};
+
+    #[test]
+    fn progressive_passes_converge_to_the_same_image() {
+        let scene = Scene::new();
+        let opts = RenderOpts::new()
+            .with_samples(4)
+            .with_sampler(SamplerKind::Sobol);
+
+        let mut expected = FrameBuffer::new(32, 18);
+        scene.render(&mut expected, &opts, &|_| {});
+
+        let mut actual = FrameBuffer::new(32, 18);
+        let mut film = Film::new(actual.size(), opts.filter);
+        let mut sampler = opts.sampler.create(opts.samples, opts.seed);
+        for pass in 0..opts.samples {
+            scene.render_pass(&mut film, &opts, sampler.as_mut(), pass);
+        }
+        film.resolve(&mut actual);
+
+        assert_eq!(expected.bytes(), actual.bytes());
+    }
+
+    #[test]
+    fn passes_can_be_split_into_rows() {
+        let scene = Scene::new();
+        let opts = RenderOpts::new()
+            .with_samples(2)
+            .with_crop(Some(CropWindow::Pixels {
+                x: 4,
+                y: 3,
+                width: 20,
+                height: 9,
+            }));
+        let size = PixelSize::new(32, 18);
+
+        let mut expected = FrameBuffer::new(size.width, size.height);
+        let mut film = Film::new(size, opts.filter).with_region(opts.crop.map(|c| c.rect(size)));
+        let mut sampler = opts.sampler.create(opts.samples, opts.seed);
+        for pass in 0..opts.samples {
+            scene.render_pass(&mut film, &opts, sampler.as_mut(), pass);
+        }
+        film.resolve(&mut expected);
+
+        let mut actual = FrameBuffer::new(size.width, size.height);
+        let mut film = Film::new(size, opts.filter).with_region(opts.crop.map(|c| c.rect(size)));
+        for pass in 0..opts.samples {
+            for rows in [0..4, 4..5, 5..100] {
+                scene.render_pass_rows(&mut film, &opts, sampler.as_mut(), pass, rows);
+            }
+        }
+        film.resolve(&mut actual);
+
+        assert_eq!(expected.bytes(), actual.bytes());
+    }
+
+    #[test]
+    fn ray_stats_count_the_samples() {
+        let scene = Scene::new();
+        let opts = RenderOpts::new().with_samples(3);
+
+        let mut film = Film::new(PixelSize::new(8, 4), opts.filter);
+        scene
+            .camera()
+            .render_film(&scene, &mut film, &opts, &|_| {});
+
+        let stats = film.ray_stats();
+        assert_eq!(8 * 4 * 3, stats.primary_rays);
+        assert_eq!(stats.primary_rays, stats.rays());
+        // the hierarchy of the 5 entities is tested for every ray
+        assert!(stats.bounds_tests >= stats.rays());
+        assert!(stats.intersection_tests <= 5 * stats.rays());
+    }
+
+    #[test]
+    fn cropped_renders_match_the_full_render() {
+        let scene = Scene::new();
+        let opts = RenderOpts::new()
+            .with_samples(2)
+            .with_filter("gaussian".parse().unwrap());
+        let crop = CropWindow::Pixels {
+            x: 10,
+            y: 4,
+            width: 8,
+            height: 6,
+        };
+        let rect = crop.rect(PixelSize::new(32, 18));
+
+        let mut full = FrameBuffer::new(32, 18);
+        scene.render(&mut full, &opts, &|_| {});
+
+        let mut cropped = FrameBuffer::new(32, 18);
+        cropped.clear(BLACK);
+        let counts = scene.render(&mut cropped, &opts.with_crop(Some(crop)), &|_| {});
+
+        for y in 0..18 {
+            for x in 0..32 {
+                let pixel = Pixel::new(x, y);
+                let expected = if rect.contains(pixel) {
+                    full.get(pixel)
+                } else {
+                    BLACK
+                };
+                assert_eq!(expected, cropped.get(pixel), "{:?}", pixel);
+            }
+        }
+        assert_eq!(2, counts.min());
+    }
+
+    #[test]
+    fn orientation_roundtrip() {
+        let mut camera = Camera::new();
+        assert!((camera.forward() - Vec3::new(0.0, 0.0, -1.0)).magnitude() < 1e-6);
+
+        camera.set_orientation(90.0, 0.0);
+        assert!((camera.forward() - Vec3::new(-1.0, 0.0, 0.0)).magnitude() < 1e-6);
 
-        Ray::new(origin, ll + horiz * uv.0 + vert * uv.1 - origin)
+        camera.set_orientation(-30.0, 20.0);
+        let (yaw, pitch) = camera.orientation();
+        assert!((yaw + 30.0).abs() < 1e-3);
+        assert!((pitch - 20.0).abs() < 1e-3);
     }
 }
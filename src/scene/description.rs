@@ -0,0 +1,1029 @@
+//! The scene description format, used to load [Scene]s from TOML files.
+//!
+//! ```toml
+//! [camera]
+//! position = [0.0, 1.0, 4.0]
+//! yaw = 0.0
+//! pitch = -10.0
+//! background = [50, 50, 50]
+//! projection = "fisheye:180"
+//! stereo = { ipd = 0.064, convergence = 4.0 }
+//! shutter = [0.0, 1.0]
+//!
+//! [camera.animation]
+//! yaw = [{ frame = 1, value = 0.0 }, { frame = 48, value = 90.0, interpolation = "bezier" }]
+//! fov = [{ frame = 1, value = 50.0 }, { frame = 48, value = 30.0 }]
+//!
+//! # a primitive shared by the entities that instance it
+//! [prototypes.tree]
+//! color = [40, 120, 40]
+//! primitive = { type = "mesh", vertices = [[-0.5, 0.0, 0.0], [0.5, 0.0, 0.0], [0.0, 2.0, 0.0]], triangles = [[0, 1, 2]] }
+//!
+//! [[entities]]
+//! position = [0.0, 1.0, 0.0]
+//! end_position = [0.5, 1.0, 0.0]
+//! end_rotation = [0.0, 30.0, 0.0]
+//! color = [255, 255, 255]
+//! primitive = { type = "sphere", radius = 1.0 }
+//!
+//! # a lamp, brighter than white
+//! [[entities]]
+//! position = [0.0, 4.0, 0.0]
+//! color = [255, 220, 180]
+//! emission = 4.0
+//! primitive = { type = "sphere", radius = 0.2 }
+//!
+//! [entities.animation]
+//! position = [
+//!     { frame = 1, value = [0.0, 1.0, 0.0], interpolation = "bezier" },
+//!     { frame = 24, value = [0.0, 3.0, 0.0] },
+//! ]
+//! rotation = [{ frame = 1, value = [0.0, 0.0, 0.0] }, { frame = 24, value = [0.0, 180.0, 0.0] }]
+//! color = [{ frame = 1, value = [255, 0, 0], interpolation = "step" }, { frame = 12, value = [0, 0, 255] }]
+//! emission = [{ frame = 1, value = 4.0 }, { frame = 24, value = 0.0 }]
+//!
+//! [[entities]]
+//! instance = "tree"
+//! position = [-3.0, 0.0, -2.0]
+//! rotation = [0.0, 45.0, 0.0]
+//! color = [60, 140, 30]
+//!
+//! # a group of entities, placed relative to their parent
+//! [[entities]]
+//! name = "robot"
+//! position = [2.0, 0.0, 0.0]
+//!
+//! [[entities.children]]
+//! name = "head"
+//! position = [0.0, 1.5, 0.0]
+//! primitive = { type = "sphere", radius = 0.3 }
+//!
+//! [post]
+//! bypass = false
+//!
+//! [[post.effects]]
+//! type = "bloom"
+//! threshold = 0.8
+//!
+//! [[post.effects]]
+//! type = "lut"
+//! path = "grades/film.cube"
+//! ```
+use crate::error::Error;
+use crate::math::Rotation;
+use crate::math::Vec3;
+use crate::rendering::post::{
+    Bloom, ChromaticAberration, ColorGrading, Grain, PostEffect, Vignette, WhiteBalance,
+};
+use crate::rendering::{Color, Material, PostStack};
+use crate::scene::animation::{
+    Animatable, Animation, CameraAnimation, EntityAnimation, Keyframe, Track,
+};
+use crate::scene::primitives::{Mesh, Sphere};
+use crate::scene::{Camera, NodeId, Primitive, Projection, Scene, Stereo, Transform};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// The primitives of the prototypes and their default color, by name.
+type Prototypes<'a> = BTreeMap<&'a str, (Arc<dyn Primitive>, [u8; 3])>;
+
+/// The root of a scene file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub camera: CameraDescription,
+    /// The primitives shared by the entities, by name.
+    #[serde(default)]
+    pub prototypes: BTreeMap<String, PrototypeDescription>,
+    #[serde(default)]
+    pub entities: Vec<EntityDescription>,
+    #[serde(default)]
+    pub post: PostDescription,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    pub position: Option<[f32; 3]>,
+    /// The position at the end of the frame, for moving cameras.
+    pub end_position: Option<[f32; 3]>,
+    /// The rotation around the vertical axis, in degrees.
+    pub yaw: Option<f32>,
+    /// The rotation above or below the horizon, in degrees.
+    pub pitch: Option<f32>,
+    /// The yaw and pitch at the end of the frame, for turning cameras.
+    pub end_rotation: Option<[f32; 2]>,
+    /// The shutter open and close times, within the frame (from 0 to 1).
+    pub shutter: Option<[f32; 2]>,
+    pub background: Option<[u8; 3]>,
+    /// A projection in the same syntax as the command line, e.g `orthographic:5`.
+    pub projection: Option<String>,
+    pub stereo: Option<StereoDescription>,
+    pub animation: Option<CameraAnimationDescription>,
+}
+
+/// The stereoscopic rig of the camera, used when rendering stereo pairs.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StereoDescription {
+    #[serde(default = "default_ipd")]
+    pub ipd: f32,
+    pub convergence: Option<f32>,
+}
+
+/// A primitive shared by the entities that instance it, and their default color.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrototypeDescription {
+    #[serde(default = "default_color")]
+    pub color: [u8; 3],
+    pub primitive: PrimitiveDescription,
+}
+
+/// A node of the scene graph. Its transform is relative to its parent, and it places an
+/// entity if it has a primitive or instances a prototype, or else only groups its children,
+/// and has no color or emission.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntityDescription {
+    /// The name of the node, to find it by its path, e.g `robot/arm`.
+    pub name: Option<String>,
+    #[serde(default)]
+    pub position: [f32; 3],
+    /// The position at the end of the frame, for moving entities.
+    pub end_position: Option<[f32; 3]>,
+    /// The rotations around the X, Y and Z axes, in degrees.
+    pub rotation: Option<[f32; 3]>,
+    /// The rotations at the end of the frame, for spinning entities.
+    pub end_rotation: Option<[f32; 3]>,
+    /// The color, which overrides the one of the prototype. White by default.
+    pub color: Option<[u8; 3]>,
+    /// The light emitted, in multiples of the color, e.g 4 for a lamp. 0 by default.
+    pub emission: Option<f32>,
+    pub primitive: Option<PrimitiveDescription>,
+    /// The name of the prototype to instance, instead of a primitive.
+    pub instance: Option<String>,
+    pub animation: Option<EntityAnimationDescription>,
+    #[serde(default)]
+    pub children: Vec<EntityDescription>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PrimitiveDescription {
+    Sphere {
+        radius: f32,
+    },
+    /// Triangles, as indices of the vertices.
+    Mesh {
+        vertices: Vec<[f32; 3]>,
+        triangles: Vec<[u32; 3]>,
+    },
+}
+
+/// A keyframe of an animated property. The interpolation until the next keyframe is
+/// `linear` (the default), `bezier` or `step`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyframeDescription<T> {
+    pub frame: f32,
+    pub value: T,
+    pub interpolation: Option<String>,
+}
+
+/// The keyframes of the animated properties of an entity.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntityAnimationDescription {
+    #[serde(default)]
+    pub position: Vec<KeyframeDescription<[f32; 3]>>,
+    /// The rotations around the X, Y and Z axes, in degrees.
+    #[serde(default)]
+    pub rotation: Vec<KeyframeDescription<[f32; 3]>>,
+    #[serde(default)]
+    pub color: Vec<KeyframeDescription<[u8; 3]>>,
+    #[serde(default)]
+    pub emission: Vec<KeyframeDescription<f32>>,
+}
+
+/// The keyframes of the animated properties of the camera.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraAnimationDescription {
+    #[serde(default)]
+    pub position: Vec<KeyframeDescription<[f32; 3]>>,
+    #[serde(default)]
+    pub yaw: Vec<KeyframeDescription<f32>>,
+    #[serde(default)]
+    pub pitch: Vec<KeyframeDescription<f32>>,
+    #[serde(default)]
+    pub background: Vec<KeyframeDescription<[u8; 3]>>,
+    /// The vertical field of view of perspective projections, or the one of fisheyes, in
+    /// degrees.
+    #[serde(default)]
+    pub fov: Vec<KeyframeDescription<f32>>,
+    /// The height of orthographic projections.
+    #[serde(default)]
+    pub height: Vec<KeyframeDescription<f32>>,
+}
+
+/// The post-processing effects, applied in order.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostDescription {
+    #[serde(default)]
+    pub bypass: bool,
+    #[serde(default)]
+    pub effects: Vec<EffectDescription>,
+}
+
+/// A post-processing effect. The missing parameters take their default value.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum EffectDescription {
+    Bloom {
+        threshold: Option<f32>,
+        intensity: Option<f32>,
+        radius: Option<f32>,
+    },
+    Vignette {
+        strength: Option<f32>,
+    },
+    Grain {
+        amount: Option<f32>,
+        seed: Option<u32>,
+    },
+    ChromaticAberration {
+        strength: Option<f32>,
+    },
+    WhiteBalance {
+        temperature: Option<f32>,
+        tint: Option<f32>,
+    },
+    /// A `.cube` file, relative to the working directory.
+    Lut {
+        path: String,
+    },
+}
+
+fn default_color() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+fn default_ipd() -> f32 {
+    Stereo::DEFAULT_IPD
+}
+
+fn vec3(v: [f32; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+fn color(c: [u8; 3]) -> Color {
+    Color::new(c[0], c[1], c[2])
+}
+
+/// Returns the rotation around the X, Y and Z axes, in degrees.
+fn euler_rotation([x, y, z]: [f32; 3]) -> Rotation {
+    Rotation::from_euler_angles(x.to_radians(), y.to_radians(), z.to_radians())
+}
+
+fn color_vec3(c: [u8; 3]) -> Vec3 {
+    Vec3::new(c[0] as f32, c[1] as f32, c[2] as f32)
+}
+
+/// Formats the position and orientation of the [Camera] as the `[camera]` table of a
+/// scene file.
+pub fn camera_pose(camera: &Camera) -> String {
+    let p = camera.position();
+    let (yaw, pitch) = camera.orientation();
+
+    format!(
+        "[camera]\nposition = [{:.3}, {:.3}, {:.3}]\nyaw = {:.2}\npitch = {:.2}",
+        p.x, p.y, p.z, yaw, pitch
+    )
+}
+
+impl SceneDescription {
+    /// Parses a scene description from a TOML string.
+    /// Returns [Error::Scene] if the TOML is invalid or does not describe a scene.
+    ///
+    /// ```
+    /// use raytracer::SceneDescription;
+    ///
+    /// let description = SceneDescription::parse(
+    ///     r#"
+    ///     [[entities]]
+    ///     position = [0.0, 0.0, -3.0]
+    ///     primitive = { type = "sphere", radius = 1.0 }
+    ///     color = [255, 0, 0]
+    ///     "#,
+    /// )?;
+    /// assert_eq!(1, description.entities.len());
+    /// let scene = description.build()?;
+    /// # Ok::<(), raytracer::Error>(())
+    /// ```
+    pub fn parse(toml: &str) -> Result<Self, Error> {
+        toml::from_str(toml).map_err(|e| Error::Scene(e.to_string()))
+    }
+
+    /// Builds the [Scene] described by this description. Returns [Error::Scene] if a value
+    /// is invalid, e.g the camera projection, or a referenced file cannot be loaded.
+    ///
+    /// The animated properties of an animated scene are set at its first keyframe.
+    pub fn build(&self) -> Result<Scene, Error> {
+        let mut scene = Scene::from_parts(Vec::new(), self.camera.build()?);
+        scene.set_post(self.post.build()?);
+
+        let mut prototypes = Prototypes::new();
+        for (name, prototype) in &self.prototypes {
+            prototypes.insert(name, (prototype.primitive.build()?, prototype.color));
+        }
+
+        let mut animation = Animation::default();
+        if let Some(camera) = &self.camera.animation {
+            animation.camera = camera.build()?;
+        }
+        for entity in &self.entities {
+            entity.build(&mut scene, None, &prototypes, &mut animation)?;
+        }
+        if let Some((first, _)) = animation.range() {
+            scene.set_animation(animation);
+            scene.set_frame(first.floor().max(0.0) as u32);
+        }
+        scene.resolve_transforms();
+
+        Ok(scene)
+    }
+}
+
+impl EntityAnimationDescription {
+    fn build(&self) -> Result<EntityAnimation, Error> {
+        check_keyframes(&self.emission, "emission", |e| e >= 0.0 && e.is_finite())?;
+
+        Ok(EntityAnimation {
+            position: track(&self.position, |v| vec3(*v))?,
+            rotation: track(&self.rotation, |r| euler_rotation(*r))?,
+            color: track(&self.color, |c| color_vec3(*c))?,
+            emission: track(&self.emission, |e| *e)?,
+        })
+    }
+}
+
+impl CameraAnimationDescription {
+    fn build(&self) -> Result<CameraAnimation, Error> {
+        let is_positive = |v: f32| v > 0.0 && v.is_finite();
+        check_keyframes(&self.fov, "camera field of view", is_positive)?;
+        check_keyframes(&self.height, "camera height", is_positive)?;
+
+        Ok(CameraAnimation {
+            position: track(&self.position, |v| vec3(*v))?,
+            yaw: track(&self.yaw, |v| *v)?,
+            pitch: track(&self.pitch, |v| *v)?,
+            background: track(&self.background, |c| color_vec3(*c))?,
+            fov: track(&self.fov, |v| *v)?,
+            height: track(&self.height, |v| *v)?,
+        })
+    }
+}
+
+/// Returns an error if the value of a keyframe is not valid for the property.
+fn check_keyframes(
+    keyframes: &[KeyframeDescription<f32>],
+    property: &str,
+    is_valid: impl Fn(f32) -> bool,
+) -> Result<(), Error> {
+    match keyframes.iter().find(|k| !is_valid(k.value)) {
+        Some(k) => Err(Error::Scene(format!(
+            "invalid {} at frame {}: {}",
+            property, k.frame, k.value
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Builds the [Track] of the keyframes, converting their values, or `None` if there are none.
+fn track<T, U: Animatable>(
+    keyframes: &[KeyframeDescription<T>],
+    value: impl Fn(&T) -> U,
+) -> Result<Option<Track<U>>, Error> {
+    if keyframes.is_empty() {
+        return Ok(None);
+    }
+
+    let keyframes = keyframes
+        .iter()
+        .map(|k| {
+            let interpolation = match &k.interpolation {
+                Some(i) => i.parse().map_err(Error::Scene)?,
+                None => Default::default(),
+            };
+            Ok(Keyframe::new(k.frame, value(&k.value), interpolation))
+        })
+        .collect::<Result<_, Error>>()?;
+    Track::new(keyframes).map(Some)
+}
+
+impl PostDescription {
+    fn build(&self) -> Result<PostStack, Error> {
+        let effects = self
+            .effects
+            .iter()
+            .map(EffectDescription::build)
+            .collect::<Result<_, _>>()?;
+
+        Ok(PostStack::new(effects).with_bypass(self.bypass))
+    }
+}
+
+impl EffectDescription {
+    fn build(&self) -> Result<PostEffect, Error> {
+        let effect = match self {
+            EffectDescription::Bloom {
+                threshold,
+                intensity,
+                radius,
+            } => {
+                let d = Bloom::default();
+                PostEffect::Bloom(Bloom {
+                    threshold: threshold.unwrap_or(d.threshold),
+                    intensity: intensity.unwrap_or(d.intensity),
+                    radius: radius.unwrap_or(d.radius),
+                })
+            }
+            EffectDescription::Vignette { strength } => PostEffect::Vignette(Vignette {
+                strength: strength.unwrap_or(Vignette::default().strength),
+            }),
+            EffectDescription::Grain { amount, seed } => {
+                let d = Grain::default();
+                PostEffect::Grain(Grain {
+                    amount: amount.unwrap_or(d.amount),
+                    seed: seed.unwrap_or(d.seed),
+                })
+            }
+            EffectDescription::ChromaticAberration { strength } => {
+                PostEffect::ChromaticAberration(ChromaticAberration {
+                    strength: strength.unwrap_or(ChromaticAberration::default().strength),
+                })
+            }
+            EffectDescription::WhiteBalance { temperature, tint } => {
+                let d = WhiteBalance::default();
+                PostEffect::WhiteBalance(WhiteBalance {
+                    temperature: temperature.unwrap_or(d.temperature),
+                    tint: tint.unwrap_or(d.tint),
+                })
+            }
+            EffectDescription::Lut { path } => {
+                PostEffect::ColorGrading(ColorGrading::from_file(path).map_err(Error::Scene)?)
+            }
+        };
+        effect.check().map_err(Error::Scene)?;
+
+        Ok(effect)
+    }
+}
+
+impl CameraDescription {
+    fn build(&self) -> Result<Camera, Error> {
+        let mut camera = Camera::new();
+        if let Some(background) = self.background {
+            camera = camera.with_clear_color(color(background));
+        }
+        if let Some(projection) = &self.projection {
+            let projection = projection
+                .parse::<Projection>()
+                .map_err(|e| Error::Scene(format!("invalid camera projection: {}", e)))?;
+            camera.set_projection(projection);
+        }
+        if let Some(stereo) = &self.stereo {
+            let stereo = Stereo {
+                ipd: stereo.ipd,
+                convergence: stereo.convergence,
+            };
+            stereo
+                .check()
+                .map_err(|e| Error::Scene(format!("invalid camera stereo: {}", e)))?;
+            camera.set_stereo(stereo);
+        }
+        if let Some([open, close]) = self.shutter {
+            camera.set_shutter(open, close)?;
+        }
+        if let Some(position) = self.position {
+            camera.transform().set_position(vec3(position));
+        }
+        camera
+            .transform()
+            .set_end_position(self.end_position.map(vec3));
+        if self.yaw.is_some() || self.pitch.is_some() {
+            camera.set_orientation(self.yaw.unwrap_or_default(), self.pitch.unwrap_or_default());
+        }
+        camera.set_end_orientation(self.end_rotation.map(|[yaw, pitch]| (yaw, pitch)));
+
+        Ok(camera)
+    }
+}
+
+impl PrimitiveDescription {
+    fn build(&self) -> Result<Arc<dyn Primitive>, Error> {
+        let primitive: Arc<dyn Primitive> = match self {
+            PrimitiveDescription::Sphere { radius } => Arc::new(Sphere::new(*radius)),
+            PrimitiveDescription::Mesh {
+                vertices,
+                triangles,
+            } => Arc::new(
+                Mesh::new(
+                    vertices.iter().copied().map(vec3).collect(),
+                    triangles.clone(),
+                )
+                .map_err(|e| Error::Scene(format!("invalid mesh: {}", e)))?,
+            ),
+        };
+
+        Ok(primitive)
+    }
+}
+
+impl EntityDescription {
+    /// Returns [Error::Scene] if the node, which only groups its children, has a material
+    /// property, as groups have no material that their children could inherit.
+    fn check_group(&self) -> Result<(), Error> {
+        let animation = self.animation.as_ref();
+        let animates_color = animation.is_some_and(|a| !a.color.is_empty());
+        let animates_emission = animation.is_some_and(|a| !a.emission.is_empty());
+        let property = match (self.color, self.emission) {
+            (Some(_), _) => "a color",
+            (_, Some(_)) => "an emission",
+            _ if animates_color => "an animated color",
+            _ if animates_emission => "an animated emission",
+            _ => return Ok(()),
+        };
+        let name = self
+            .name
+            .as_deref()
+            .map(|n| format!(" '{}'", n))
+            .unwrap_or_default();
+        Err(Error::Scene(format!(
+            "the group{} has {}, but no primitive or instance to apply it to",
+            name, property
+        )))
+    }
+
+    /// Adds the node and its children to the [Scene], and their keyframes to the [Animation].
+    fn build(
+        &self,
+        scene: &mut Scene,
+        parent: Option<NodeId>,
+        prototypes: &Prototypes,
+        animation: &mut Animation,
+    ) -> Result<(), Error> {
+        let entity = match (&self.primitive, &self.instance) {
+            (Some(_), Some(name)) => {
+                return Err(Error::Scene(format!(
+                    "an entity has both a primitive and an instance of '{}'",
+                    name
+                )))
+            }
+            (Some(primitive), None) => Some((primitive.build()?, default_color())),
+            (None, Some(name)) => {
+                let prototype = prototypes
+                    .get(name.as_str())
+                    .ok_or_else(|| Error::Scene(format!("unknown prototype '{}'", name)))?;
+                Some(prototype.clone())
+            }
+            (None, None) => {
+                self.check_group()?;
+                None
+            }
+        }
+        .map(|(primitive, c)| {
+            (
+                Material::from_diffuse(color(self.color.unwrap_or(c))),
+                primitive,
+            )
+        });
+        let entity = match (entity, self.emission) {
+            (_, Some(emission)) if !(emission >= 0.0 && emission.is_finite()) => {
+                return Err(Error::Scene(format!("invalid emission: {}", emission)))
+            }
+            (Some((material, primitive)), Some(emission)) => {
+                Some((material.with_emission(emission), primitive))
+            }
+            (entity, _) => entity,
+        };
+
+        let mut transform = Transform::default().with_position(vec3(self.position));
+        transform.set_end_position(self.end_position.map(vec3));
+        if let Some(rotation) = self.rotation {
+            transform.set_rotation(euler_rotation(rotation));
+        }
+        transform.set_end_rotation(self.end_rotation.map(euler_rotation));
+
+        let id = scene.add_node(parent, self.name.as_deref(), transform, entity)?;
+        if let Some(entity_animation) = &self.animation {
+            animation.entities.push((id, entity_animation.build()?));
+        }
+        for child in &self.children {
+            child.build(scene, Some(id), prototypes, animation)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rendering::{HdrBuffer, Pixel, RenderOpts, RenderTarget};
+
+    #[test]
+    fn parse() {
+        let desc = SceneDescription::parse(
+            r#"
+            [camera]
+            position = [0.0, 1.0, 4.0]
+            projection = "fisheye:200"
+            yaw = 45.0
+
+            [[entities]]
+            position = [0.0, 1.0, 0.0]
+            primitive = { type = "sphere", radius = 0.5 }
+            "#,
+        )
+        .unwrap();
+
+        let scene = desc.build().unwrap();
+        assert_eq!(
+            Projection::Fisheye { fov: 200.0 },
+            scene.camera().projection()
+        );
+        assert!((scene.camera().orientation().0 - 45.0).abs() < 1e-3);
+        assert_eq!(1, desc.entities.len());
+    }
+
+    #[test]
+    fn parse_post_effects() {
+        let scene = SceneDescription::parse(
+            r#"
+            [post]
+            bypass = true
+
+            [[post.effects]]
+            type = "chromatic-aberration"
+
+            [[post.effects]]
+            type = "bloom"
+            threshold = 0.8
+            "#,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+        let post = scene.post();
+        assert!(post.bypass);
+        assert_eq!(
+            "chromatic-aberration:0.005,bloom:0.8 (bypassed)",
+            post.to_string()
+        );
+        assert_eq!(
+            PostEffect::Bloom(Bloom {
+                threshold: 0.8,
+                ..Bloom::default()
+            }),
+            post.effects[1]
+        );
+    }
+
+    #[test]
+    fn camera_pose_roundtrip() {
+        let mut camera = Camera::new();
+        camera.transform().set_position(Vec3::new(1.0, 2.0, 3.0));
+        camera.set_orientation(30.0, -10.0);
+
+        let scene = SceneDescription::parse(&camera_pose(&camera))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(Vec3::new(1.0, 2.0, 3.0), scene.camera().position());
+        assert!((scene.camera().forward() - camera.forward()).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn parse_animation() {
+        let desc = SceneDescription::parse(
+            r#"
+            [camera.animation]
+            yaw = [{ frame = 1, value = 0.0 }, { frame = 11, value = 90.0 }]
+            fov = [{ frame = 1, value = 40.0 }, { frame = 11, value = 60.0 }]
+
+            [[entities]]
+            primitive = { type = "sphere", radius = 0.5 }
+
+            [entities.animation]
+            position = [{ frame = 1, value = [0.0, 0.0, 0.0] }, { frame = 5, value = [4.0, 0.0, 0.0] }]
+            rotation = [{ frame = 1, value = [0.0, 0.0, 0.0] }, { frame = 5, value = [0.0, 0.0, 90.0] }]
+            color = [{ frame = 3, value = [255, 0, 0], interpolation = "step" }, { frame = 4, value = [0, 0, 255] }]
+            emission = [{ frame = 1, value = 0.0 }, { frame = 5, value = 2.0 }]
+            "#,
+        )
+        .unwrap();
+
+        let mut scene = desc.build().unwrap();
+        assert_eq!(Some((1.0, 11.0)), scene.animation().range());
+        let entity = &scene.entities()[0];
+        assert_eq!(Vec3::new(0.0, 0.0, 0.0), entity.transform().position());
+        assert_eq!(
+            Some(Vec3::new(1.0, 0.0, 0.0)),
+            entity.transform().end_position()
+        );
+        // the rotation during the frame is blurred, as the position
+        let end_rotation = entity.transform().end_rotation().unwrap();
+        assert!((end_rotation.angle().to_degrees() - 22.5).abs() < 1e-3);
+        assert_eq!(0.0, entity.material().emission());
+        assert!((scene.camera().vertical_fov() - 40.0).abs() < 1e-3);
+
+        scene.set_frame(6);
+        assert!((scene.camera().orientation().0 - 45.0).abs() < 1e-3);
+        assert!((scene.camera().vertical_fov() - 50.0).abs() < 1e-3);
+        let entity = &scene.entities()[0];
+        assert_eq!(Vec3::new(4.0, 0.0, 0.0), entity.transform().position());
+        assert_eq!(None, entity.transform().end_position());
+        assert!((entity.transform().rotation().angle().to_degrees() - 90.0).abs() < 1e-3);
+        assert_eq!(None, entity.transform().end_rotation());
+        assert_eq!(Color::new(0, 0, 255), entity.material().diffuse_color());
+        assert_eq!(2.0, entity.material().emission());
+
+        for animation in [
+            "[camera.animation]\npitch = [{ frame = 1, value = 0.0, interpolation = \"cubic\" }]",
+            "[camera.animation]\nfov = [{ frame = 1, value = 0.0 }]",
+            "[camera.animation]\nheight = [{ frame = 1, value = inf }]",
+            "[[entities]]\nprimitive = { type = \"sphere\", radius = 0.5 }\n\
+             animation = { emission = [{ frame = 1, value = -1.0 }] }",
+        ] {
+            let desc = SceneDescription::parse(animation).unwrap();
+            assert!(
+                matches!(desc.build(), Err(Error::Scene(_))),
+                "{}",
+                animation
+            );
+        }
+    }
+
+    #[test]
+    fn parse_hierarchy() {
+        let mut scene = SceneDescription::parse(
+            r#"
+            [[entities]]
+            name = "robot"
+            position = [0.0, 1.0, 0.0]
+
+            [[entities.children]]
+            name = "arm"
+            position = [1.0, 0.0, 0.0]
+            primitive = { type = "sphere", radius = 0.2 }
+
+            [[entities.children.children]]
+            name = "hand"
+            position = [0.5, 0.0, 0.0]
+            primitive = { type = "sphere", radius = 0.1 }
+            "#,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+        assert_eq!(2, scene.entities().len());
+        assert_eq!(
+            Vec3::new(1.5, 1.0, 0.0),
+            scene.entities()[1].transform().position()
+        );
+
+        let robot = scene.find("robot").unwrap();
+        assert_eq!(scene.find("robot/arm/hand"), scene.find("hand"));
+        scene
+            .graph_mut()
+            .node_mut(robot)
+            .transform_mut()
+            .set_position(Vec3::new(0.0, 2.0, 0.0));
+        scene.resolve_transforms();
+        assert_eq!(
+            Vec3::new(1.0, 2.0, 0.0),
+            scene.entities()[0].transform().position()
+        );
+        assert_eq!(
+            Vec3::new(1.5, 2.0, 0.0),
+            scene.entities()[1].transform().position()
+        );
+    }
+
+    #[test]
+    fn parse_instances() {
+        let scene = SceneDescription::parse(
+            r#"
+            [prototypes.tree]
+            color = [0, 255, 0]
+            primitive = { type = "mesh", vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], triangles = [[0, 1, 2]] }
+
+            [[entities]]
+            instance = "tree"
+
+            [[entities]]
+            instance = "tree"
+            position = [5.0, 0.0, 0.0]
+            rotation = [0.0, 90.0, 0.0]
+            color = [255, 0, 0]
+            "#,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+        let entities = scene.entities();
+        assert_eq!(2, entities.len());
+        assert!(Arc::ptr_eq(
+            entities[0].shared_primitive(),
+            entities[1].shared_primitive()
+        ));
+        assert_eq!(
+            Color::new(0, 255, 0),
+            entities[0].material().diffuse_color()
+        );
+        assert_eq!(
+            Color::new(255, 0, 0),
+            entities[1].material().diffuse_color()
+        );
+        // the triangle is rotated to face +X
+        let bounds = entities[1].bounds();
+        assert!((bounds.min - Vec3::new(5.0, 0.0, -1.0)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn emissive_entities_are_brighter_than_white() {
+        let scene = SceneDescription::parse(
+            r#"
+            [[entities]]
+            position = [0.0, 0.0, -5.0]
+            color = [255, 128, 0]
+            emission = 3.0
+            primitive = { type = "sphere", radius = 2.0 }
+            "#,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+        let mut hdr = HdrBuffer::new(16, 9);
+        scene.render_hdr(&mut hdr, &RenderOpts::new(), &|_| {});
+        let [r, g, b] = hdr.get(Pixel::new(8, 4));
+        assert_eq!(4.0, r);
+        assert!(g > 0.8 && b == 0.0, "{} {}", g, b);
+        assert_eq!(
+            Color::new(255, 239, 0),
+            hdr.to_frame_buffer().get(Pixel::new(8, 4))
+        );
+    }
+
+    #[test]
+    fn spinning_entities_are_blurred() {
+        let render = |end_rotation: &str| {
+            let scene = SceneDescription::parse(&format!(
+                r#"
+                [camera]
+                background = [0, 0, 0]
+                shutter = [0.0, 1.0]
+
+                [[entities]]
+                position = [0.0, 0.0, -5.0]
+                {}
+                primitive = {{ type = "mesh", vertices = [[0.0, -0.2, 0.0], [2.0, -0.2, 0.0], [2.0, 0.2, 0.0], [0.0, 0.2, 0.0]], triangles = [[0, 1, 2], [0, 2, 3]] }}
+                "#,
+                end_rotation
+            ))
+            .unwrap()
+            .build()
+            .unwrap();
+
+            let mut hdr = HdrBuffer::new(64, 36);
+            scene.render_hdr(&mut hdr, &RenderOpts::new().with_samples(64), &|_| {});
+            (scene.entities()[0].bounds(), hdr)
+        };
+        let partial = |hdr: &HdrBuffer| {
+            let pixels = (0..36).flat_map(|y| (0..64).map(move |x| Pixel::new(x, y)));
+            pixels
+                .filter(|p| (0.1..0.9).contains(&hdr.get(*p)[0]))
+                .count()
+        };
+
+        // the bar sweeps a quarter of a disk, where the pixels are only partially covered
+        let (_, still) = render("");
+        let (bounds, spinning) = render("end_rotation = [0.0, 0.0, 90.0]");
+        assert!(bounds.max.y >= 1.99, "{:?}", bounds);
+        assert!(
+            partial(&spinning) > 3 * partial(&still),
+            "{} {}",
+            partial(&spinning),
+            partial(&still)
+        );
+    }
+
+    #[test]
+    fn bloom_spreads_emissive_highlights() {
+        let scene = SceneDescription::parse(
+            r#"
+            [[entities]]
+            position = [0.0, 0.0, -5.0]
+            emission = 7.0
+            primitive = { type = "sphere", radius = 0.5 }
+
+            [[post.effects]]
+            type = "bloom"
+            radius = 0.1
+            "#,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+        let mut hdr = HdrBuffer::new(32, 18);
+        scene.render_hdr(&mut hdr, &RenderOpts::new(), &|_| {});
+        let mut bloomed = hdr.clone();
+        scene.post().apply(&mut bloomed);
+
+        // the black background around the sphere glows
+        let near = Pixel::new(16, 3);
+        assert_eq!([0.0; 3], hdr.get(near));
+        assert!(bloomed.get(near)[0] > 0.0);
+        assert_ne!(
+            hdr.to_frame_buffer().bytes(),
+            bloomed.to_frame_buffer().bytes()
+        );
+    }
+
+    #[test]
+    fn invalid_descriptions() {
+        assert!(matches!(
+            SceneDescription::parse("[camera]\nzoom = 2"),
+            Err(Error::Scene(_))
+        ));
+
+        let desc = SceneDescription::parse("[camera]\nprojection = \"tilt-shift\"").unwrap();
+        assert!(matches!(desc.build(), Err(Error::Scene(_))));
+
+        let desc = SceneDescription::parse("[camera]\nprojection = \"orthographic:0\"").unwrap();
+        assert!(matches!(desc.build(), Err(Error::Scene(_))));
+
+        let desc = SceneDescription::parse("[camera]\nstereo = { ipd = -0.064 }").unwrap();
+        assert!(matches!(desc.build(), Err(Error::Scene(_))));
+
+        for shutter in ["[-1.0, 2.0]", "[0.8, 0.2]", "[0.0, nan]"] {
+            let desc =
+                SceneDescription::parse(&format!("[camera]\nshutter = {}", shutter)).unwrap();
+            assert!(matches!(desc.build(), Err(Error::Scene(_))), "{}", shutter);
+        }
+
+        let desc = SceneDescription::parse("[[entities]]\nname = \"a/b\"").unwrap();
+        assert!(matches!(desc.build(), Err(Error::Scene(_))));
+
+        let desc = SceneDescription::parse("[[entities]]\ninstance = \"tree\"").unwrap();
+        assert!(matches!(desc.build(), Err(Error::Scene(_))));
+
+        let desc = SceneDescription::parse("[[post.effects]]\ntype = \"bloom\"\nintensity = -0.5")
+            .unwrap();
+        assert!(matches!(desc.build(), Err(Error::Scene(_))));
+
+        let desc = SceneDescription::parse("[[post.effects]]\ntype = \"vignette\"\nstrength = nan")
+            .unwrap();
+        assert!(matches!(desc.build(), Err(Error::Scene(_))));
+
+        let desc = SceneDescription::parse(
+            "[[entities]]\nemission = -1.0\nprimitive = { type = \"sphere\", radius = 1.0 }",
+        )
+        .unwrap();
+        assert!(matches!(desc.build(), Err(Error::Scene(_))));
+
+        // groups have no material
+        for property in [
+            "color = [255, 0, 0]",
+            "emission = 2.0",
+            "animation = { color = [{ frame = 1, value = [0, 0, 0] }] }",
+            "animation = { emission = [{ frame = 1, value = 1.0 }] }",
+        ] {
+            let desc =
+                SceneDescription::parse(&format!("[[entities]]\nname = \"group\"\n{}", property))
+                    .unwrap();
+            assert!(
+                matches!(desc.build(), Err(Error::Scene(e)) if e.contains("'group'")),
+                "{}",
+                property
+            );
+        }
+
+        let desc = SceneDescription::parse(
+            "[[entities]]\nprimitive = { type = \"mesh\", vertices = [], triangles = [[0, 1, 2]] }",
+        )
+        .unwrap();
+        assert!(matches!(desc.build(), Err(Error::Scene(_))));
+    }
+}
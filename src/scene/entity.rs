@@ -1,26 +1,105 @@
-use crate::math::{Ray};
+use crate::math::{Aabb, Ray, Vec3};
 use crate::rendering::Material;
-use crate::scene::{Hittable, Transform, Hit, Primitive};
+use crate::scene::{Hit, Hittable, Primitive, Transform};
+use std::sync::Arc;
 
+/// An instance of a [Primitive] in a scene, with its own [Transform] and [Material].
+///
+/// The primitive can be shared by many entities, e.g the same tree mesh placed in a forest.
 #[derive(Debug)]
 pub struct Entity {
     transform: Transform,
     material: Material,
-    renderer: Box<dyn Primitive>,
+    renderer: Arc<dyn Primitive>,
+    /// The identifier of this entity in its scene, starting from 1.
+    id: u32,
 }
 
 impl Entity {
-    pub fn new(transform: Transform, material: Material, renderer: Box<dyn Primitive>) -> Self {
+    pub fn new(transform: Transform, material: Material, renderer: Arc<dyn Primitive>) -> Self {
         Self {
             transform,
             material,
             renderer,
+            id: 0,
         }
     }
+
+    pub fn with_id(self, id: u32) -> Self {
+        let mut s = self;
+        s.id = id;
+        s
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    pub fn primitive(&self) -> &dyn Primitive {
+        self.renderer.as_ref()
+    }
+
+    /// Gets the shared [Primitive], e.g to place another instance of it.
+    pub fn shared_primitive(&self) -> &Arc<dyn Primitive> {
+        &self.renderer
+    }
+
+    /// Returns the world-space bounds of this entity, covering its whole motion.
+    pub(crate) fn bounds(&self) -> Aabb {
+        let local = self.renderer.bounds();
+        let transform = &self.transform;
+        let pose = |time: f32| {
+            local
+                .rotated(&transform.rotation_at(time))
+                .translated(transform.position_at(time))
+        };
+        let bounds = match transform.is_moving() {
+            true => pose(0.0).union(&pose(1.0)),
+            false => pose(0.0),
+        };
+        if transform.end_rotation().is_none() {
+            return bounds;
+        }
+
+        // in between, a spinning entity stays within the sphere around its origin that
+        // contains its local bounds, moved along the path of its position
+        let radius = (0..8)
+            .map(|corner| {
+                let pick = |axis: usize| {
+                    if corner & (1 << axis) == 0 {
+                        local.min[axis]
+                    } else {
+                        local.max[axis]
+                    }
+                };
+                Vec3::new(pick(0), pick(1), pick(2)).magnitude()
+            })
+            .fold(0.0, f32::max);
+        let sphere = |time: f32| Aabb::from_sphere(transform.position_at(time), radius);
+        bounds.union(&sphere(0.0)).union(&sphere(1.0))
+    }
 }
 
 impl Hittable for Entity {
     fn hit(&self, ray: &Ray) -> Option<Hit> {
-        self.renderer.hit(&ray, &self.transform, self.material.clone())
+        self.renderer
+            .hit(ray, &self.transform, self.material.clone())
+            .map(|hit| hit.with_object_id(self.id))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,212 @@
+//! The hierarchy of the entities of a [Scene](crate::scene::Scene): each node is placed
+//! relative to its parent, and can be found by its name or its path.
+use crate::scene::Transform;
+use std::fmt::{Display, Formatter};
+
+/// The separator of the names of a path, e.g `robot/arm/hand`.
+pub const PATH_SEPARATOR: char = '/';
+
+/// The identifier of a [Node] in its [SceneGraph].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+impl Display for NodeId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// A node of a [SceneGraph]: a [Transform] relative to the parent node, and optionally the
+/// entity it places. A node without entity groups its children.
+#[derive(Debug, Clone)]
+pub struct Node {
+    name: Option<String>,
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// The index of the entity in the scene.
+    entity: Option<usize>,
+}
+
+impl Node {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Gets the [Transform] relative to the parent node, or to the world for roots.
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// Gets the index of the entity placed by this node in its scene, if any.
+    pub fn entity(&self) -> Option<usize> {
+        self.entity
+    }
+}
+
+/// A forest of [Node]s, in the order they were added: parents come before their children.
+#[derive(Debug, Clone, Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+}
+
+impl SceneGraph {
+    /// Adds a node under the parent, or as a root. Returns an error if the name contains
+    /// the path separator `/`.
+    pub fn add(
+        &mut self,
+        parent: Option<NodeId>,
+        name: Option<&str>,
+        transform: Transform,
+        entity: Option<usize>,
+    ) -> Result<NodeId, String> {
+        if let Some(name) = name.filter(|n| n.contains(PATH_SEPARATOR)) {
+            return Err(format!(
+                "the node name '{}' contains '{}'",
+                name, PATH_SEPARATOR
+            ));
+        }
+
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            name: name.map(String::from),
+            transform,
+            parent,
+            children: Vec::new(),
+            entity,
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(id);
+        }
+
+        Ok(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+
+    /// Iterates over the nodes, parents first.
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        self.nodes.iter().enumerate().map(|(i, n)| (NodeId(i), n))
+    }
+
+    /// Finds the first node with the name.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes()
+            .find(|(_, n)| n.name() == Some(name))
+            .map(|(id, _)| id)
+    }
+
+    /// Finds a node by the names of the nodes from a root, e.g `robot/arm/hand`.
+    pub fn find_path(&self, path: &str) -> Option<NodeId> {
+        let mut candidates: Vec<NodeId> = self
+            .nodes()
+            .filter(|(_, n)| n.parent.is_none())
+            .map(|(id, _)| id)
+            .collect();
+        let mut found = None;
+        for name in path.split(PATH_SEPARATOR) {
+            let id = *candidates
+                .iter()
+                .find(|id| self.node(**id).name() == Some(name))?;
+            candidates = self.node(id).children.clone();
+            found = Some(id);
+        }
+
+        found
+    }
+
+    /// Returns the path of the node, where unnamed nodes are named by their id.
+    pub fn path(&self, id: NodeId) -> String {
+        let node = self.node(id);
+        let name = node
+            .name()
+            .map(String::from)
+            .unwrap_or_else(|| id.to_string());
+        match node.parent {
+            Some(parent) => format!("{}{}{}", self.path(parent), PATH_SEPARATOR, name),
+            None => name,
+        }
+    }
+
+    /// Returns the world [Transform] of every node, by id.
+    pub fn world_transforms(&self) -> Vec<Transform> {
+        let mut world: Vec<Transform> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let transform = match node.parent {
+                Some(parent) => world[parent.0].compose(&node.transform),
+                None => node.transform.clone(),
+            };
+            world.push(transform);
+        }
+
+        world
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::Vec3;
+
+    #[test]
+    fn hierarchy() {
+        let at = |x, y, z| Transform::default().with_position(Vec3::new(x, y, z));
+        let mut graph = SceneGraph::default();
+        let robot = graph
+            .add(None, Some("robot"), at(0.0, 1.0, 0.0), None)
+            .unwrap();
+        let arm = graph
+            .add(Some(robot), Some("arm"), at(1.0, 0.0, 0.0), Some(0))
+            .unwrap();
+        let hand = graph
+            .add(Some(arm), Some("hand"), at(1.0, 0.0, 0.0), Some(1))
+            .unwrap();
+        let unnamed = graph
+            .add(Some(robot), None, at(0.0, 1.0, 0.0), Some(2))
+            .unwrap();
+        assert!(graph
+            .add(None, Some("a/b"), at(0.0, 0.0, 0.0), None)
+            .is_err());
+
+        assert_eq!(Some(hand), graph.find("hand"));
+        assert_eq!(Some(hand), graph.find_path("robot/arm/hand"));
+        assert_eq!(None, graph.find_path("arm/hand"));
+        assert_eq!("robot/arm/hand", graph.path(hand));
+        assert_eq!("robot/#3", graph.path(unnamed));
+
+        // moving the root moves the subtree
+        graph
+            .node_mut(robot)
+            .transform_mut()
+            .set_position(Vec3::new(0.0, 5.0, 0.0));
+        let world = graph.world_transforms();
+        assert_eq!(Vec3::new(2.0, 5.0, 0.0), world[2].position());
+        assert_eq!(Vec3::new(0.0, 6.0, 0.0), world[3].position());
+    }
+}
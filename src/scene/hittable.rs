@@ -13,7 +13,11 @@ pub struct Hit {
     normal: Vec3,
     /// The squared distance to the ray origin
     sqr_distance: f32,
-    material: Material
+    material: Material,
+    /// The texture coordinates of the intersection
+    uv: (f32, f32),
+    /// The identifier of the intersected entity, or 0 if unknown
+    object_id: u32,
 }
 
 impl Hit {
@@ -22,9 +26,21 @@ impl Hit {
             position,
             normal,
             sqr_distance,
-            material
+            material,
+            uv: (0.0, 0.0),
+            object_id: 0,
         }
     }
+    pub fn with_uv(self, uv: (f32, f32)) -> Self {
+        let mut s = self;
+        s.uv = uv;
+        s
+    }
+    pub fn with_object_id(self, id: u32) -> Self {
+        let mut s = self;
+        s.object_id = id;
+        s
+    }
     pub fn position(&self) -> Vec3 {
         self.position
     }
@@ -37,6 +53,12 @@ impl Hit {
     pub fn material(&self) -> &Material {
         &self.material
     }
+    pub fn uv(&self) -> (f32, f32) {
+        self.uv
+    }
+    pub fn object_id(&self) -> u32 {
+        self.object_id
+    }
 }
 
 /// A trait for objects than can interact with [Ray]s.
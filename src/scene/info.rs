@@ -0,0 +1,213 @@
+//! Statistics and sanity checks of a [Scene], to validate it before rendering.
+use crate::math::{Aabb, Vec3};
+use crate::rendering::film::FilmPixel;
+use crate::rendering::PixelSize;
+use crate::scene::{Entity, Projection, Scene};
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem::size_of;
+use std::sync::Arc;
+
+/// The content of a [Scene], and the problems that do not prevent rendering it.
+#[derive(Debug, Clone)]
+pub struct SceneInfo {
+    pub entities: usize,
+    /// The number of primitives of each kind, e.g `sphere`.
+    pub primitives: BTreeMap<&'static str, usize>,
+    /// The number of distinct primitives: entities can share theirs.
+    pub distinct_primitives: usize,
+    pub triangles: usize,
+    /// The number of distinct materials.
+    pub materials: usize,
+    /// The number of emissive entities: they are the only lights of a scene.
+    pub lights: usize,
+    /// Scenes do not have textures yet, so the count is zero and no texture can be missing.
+    pub textures: usize,
+    /// The minimum and maximum corners of the world-space bounds of the entities, covering
+    /// their motion.
+    pub bounds: Option<(Vec3, Vec3)>,
+    /// The number of animated nodes.
+    pub animated_nodes: usize,
+    /// The first and last frames with a keyframe, or `None` if nothing is animated.
+    pub keyframes: Option<(f32, f32)>,
+    /// The estimated memory used by the scene, in bytes. Shared primitives are counted once.
+    pub memory: usize,
+    /// The default size of the renders, see [Scene::render_size].
+    pub render_size: PixelSize,
+    /// The estimated memory used to render the scene at `render_size`, in bytes: the film,
+    /// the linear image and the 8-bit output.
+    pub render_memory: usize,
+    pub warnings: Vec<String>,
+}
+
+impl SceneInfo {
+    pub fn new(scene: &Scene) -> Self {
+        let mut primitives = BTreeMap::new();
+        let mut materials = BTreeSet::new();
+        let mut shared = BTreeSet::new();
+        let mut triangles = 0;
+        let mut lights = 0;
+        let mut bounds: Option<Aabb> = None;
+        let mut memory = size_of::<Scene>() + scene.bvh().memory_size();
+        let mut warnings = Vec::new();
+
+        for entity in scene.entities() {
+            let primitive = entity.primitive();
+            *primitives.entry(primitive.name()).or_insert(0) += 1;
+            materials.insert(entity.material().id());
+            triangles += primitive.triangle_count();
+            if entity.material().emission() > 0.0 {
+                lights += 1;
+            }
+            memory += size_of::<Entity>();
+            if shared.insert(Arc::as_ptr(entity.shared_primitive()) as *const u8) {
+                memory += primitive.memory_size();
+            }
+
+            let entity_bounds = entity.bounds();
+            bounds = Some(match bounds {
+                Some(b) => b.union(&entity_bounds),
+                None => entity_bounds,
+            });
+
+            let name = format!("entity {} ({})", entity.id(), primitive.name());
+            warnings.extend(
+                primitive
+                    .warnings()
+                    .into_iter()
+                    .map(|w| format!("{}: {}", name, w)),
+            );
+            let transform = entity.transform();
+            let positions = [Some(transform.position()), transform.end_position()];
+            if positions.iter().flatten().any(|p| !is_finite(p)) {
+                warnings.push(format!("{}: the position is not finite", name));
+            } else if is_behind_camera(scene, &entity_bounds) {
+                warnings.push(format!(
+                    "{}: the entity is behind the camera, it is invisible",
+                    name
+                ));
+            }
+        }
+        if scene.entities().is_empty() {
+            warnings.push(String::from("the scene is empty"));
+        }
+
+        let render_size = scene.render_size();
+        let pixel_memory = size_of::<FilmPixel>() + 3 * size_of::<f32>() + 3;
+
+        SceneInfo {
+            entities: scene.entities().len(),
+            primitives,
+            distinct_primitives: shared.len(),
+            triangles,
+            materials: materials.len(),
+            lights,
+            textures: 0,
+            bounds: bounds.map(|b| (b.min, b.max)),
+            animated_nodes: scene.animation().entities.len(),
+            keyframes: scene.animation().range(),
+            memory,
+            render_size,
+            render_memory: (render_size.width * render_size.height) as usize * pixel_memory,
+            warnings,
+        }
+    }
+}
+
+fn is_finite(v: &Vec3) -> bool {
+    v.iter().all(|c| c.is_finite())
+}
+
+/// Returns true if the bounds are entirely behind the camera, for the projections that
+/// do not see behind it.
+fn is_behind_camera(scene: &Scene, bounds: &Aabb) -> bool {
+    let camera = scene.camera();
+    match camera.projection() {
+        Projection::Perspective | Projection::Orthographic { .. } => {}
+        Projection::Fisheye { fov } if fov <= 180.0 => {}
+        Projection::Fisheye { .. } | Projection::Equirectangular => return false,
+    }
+
+    let (position, forward) = (camera.position(), camera.forward());
+    (0..8).all(|corner| {
+        let pick = |axis: usize| {
+            if corner & (1 << axis) == 0 {
+                bounds.min[axis]
+            } else {
+                bounds.max[axis]
+            }
+        };
+        let p = Vec3::new(pick(0), pick(1), pick(2));
+        (p - position).dot(&forward) <= 0.0
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scene::SceneDescription;
+
+    #[test]
+    fn counts_and_warnings() {
+        let scene = SceneDescription::parse(
+            r#"
+            [camera]
+            position = [0.0, 0.0, 0.0]
+
+            [[entities]]
+            position = [0.0, 0.0, -5.0]
+            primitive = { type = "sphere", radius = 1.0 }
+
+            [[entities]]
+            position = [0.0, 0.0, 5.0]
+            color = [255, 0, 0]
+            emission = 2.0
+            primitive = { type = "sphere", radius = 1.0 }
+
+            [[entities]]
+            position = [0.0, 0.0, -5.0]
+            primitive = { type = "sphere", radius = 0.0 }
+            "#,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+        let info = SceneInfo::new(&scene);
+        assert_eq!(3, info.entities);
+        assert_eq!(Some(&3), info.primitives.get("sphere"));
+        assert_eq!(3, info.distinct_primitives);
+        assert_eq!(2, info.materials);
+        assert_eq!(1, info.lights);
+        assert_eq!(0, info.textures);
+        assert_eq!(Vec3::new(-1.0, -1.0, -6.0), info.bounds.unwrap().0);
+        assert_eq!(2, info.warnings.len(), "{:?}", info.warnings);
+        assert!(info.warnings[0].starts_with("entity 2 (sphere): the entity is behind the camera"));
+        assert!(info.warnings[1].starts_with("entity 3 (sphere): the sphere has an invalid radius"));
+    }
+
+    #[test]
+    fn instances_share_their_memory() {
+        let scene = |count: usize| {
+            let mut toml = String::from(
+                "[prototypes.quad]\nprimitive = { type = \"mesh\", vertices = [[0.0, 0.0, -5.0], [1.0, 0.0, -5.0], [1.0, 1.0, -5.0], [0.0, 1.0, -5.0]], triangles = [[0, 1, 2], [0, 2, 3]] }\n",
+            );
+            for i in 0..count {
+                toml.push_str(&format!(
+                    "[[entities]]\ninstance = \"quad\"\nposition = [{}.0, 0.0, 0.0]\n",
+                    i
+                ));
+            }
+            SceneDescription::parse(&toml).unwrap().build().unwrap()
+        };
+
+        let (one, many) = (SceneInfo::new(&scene(1)), SceneInfo::new(&scene(100)));
+        assert_eq!(1, many.distinct_primitives);
+        assert_eq!(200, many.triangles);
+        // each instance only adds its entity, and its share of the top-level hierarchy
+        assert!(
+            many.memory - one.memory < 99 * (size_of::<Entity>() + 64),
+            "{} bytes",
+            many.memory - one.memory
+        );
+    }
+}
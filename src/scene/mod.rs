@@ -1,73 +1,401 @@
 use std::fmt::Debug;
+use std::fs;
+use std::ops::Range;
 
-use crate::math::{Vec3, Ray};
-use crate::rendering::{Camera, RenderTarget, RenderOpts, Material, GREEN, BLUE, GRAY, DARK_GREEN, RED, WHITE, DARK_GRAY};
+use crate::error::Error;
+use crate::math::random::hash_combine;
+use crate::math::{Bvh, Ray, Vec3};
+use crate::rendering::aov::AovImage;
+use crate::rendering::sampler::Sampler;
+use crate::rendering::{
+    Camera, Checkpoint, Film, HdrBuffer, Material, PixelSize, PostStack, RayKind, RayStats,
+    RenderOpts, RenderTarget, SampleCounts, BLUE, DARK_GRAY, GRAY, GREEN, RED, WHITE,
+};
 
+pub mod animation;
+pub mod benchmarks;
 pub mod camera;
+pub mod description;
 pub mod entity;
+pub mod graph;
 pub mod hittable;
+pub mod info;
 pub mod primitives;
+pub mod projection;
+pub mod stereo;
 pub mod transform;
+pub mod turntable;
 
-use crate::scene::entity::{Entity};
-pub use hittable::{Hittable, Hit};
-pub use primitives::{Primitive};
-pub use transform::Transform;
 use crate::scene::primitives::Sphere;
+pub use animation::{Animation, FrameRange};
+pub use description::SceneDescription;
+pub use entity::Entity;
+pub use graph::{Node, NodeId, SceneGraph};
+pub use hittable::{Hit, Hittable};
+pub use primitives::Primitive;
+pub use projection::Projection;
+use std::sync::Arc;
+pub use stereo::{Eye, Stereo, StereoLayout};
+pub use transform::Transform;
+pub use turntable::Turntable;
+
+/// The height of the renders, in pixels, see [Scene::render_size].
+pub const RENDER_HEIGHT: u32 = 512;
 
 #[derive(Debug)]
 pub struct Scene {
+    /// The entities, placed in world space by [Scene::resolve_transforms].
     entities: Vec<Entity>,
+    /// The hierarchy that places the entities.
+    graph: SceneGraph,
+    /// The top level of the acceleration structure: the world-space bounds of the
+    /// entities. Their primitives may have their own, in object space.
+    bvh: Bvh,
     camera: Camera,
+    /// The effects applied to the renders of the scene.
+    post: PostStack,
+    /// The keyframes of the entities and the camera, see [Scene::set_frame].
+    animation: Animation,
+}
+
+impl Default for Scene {
+    /// Creates the built-in scene. See [Scene::new].
+    fn default() -> Self {
+        Scene::new()
+    }
 }
 
 impl Scene {
+    /// Creates the built-in scene: four colored spheres on a large ground sphere.
     pub fn new() -> Self {
-        let small_sphere = Sphere::new(0.5);
-        let s0 = Box::new(Sphere::new(1.0));
-        let s1 = Box::new(small_sphere.clone());
-        let s2 = Box::new(small_sphere.clone());
-        let s3 = Box::new(small_sphere.clone());
-        let ground = Box::new(Sphere::new(100.0));
+        let small_sphere: Arc<dyn Primitive> = Arc::new(Sphere::new(0.5));
+        let s0 = Arc::new(Sphere::new(1.0));
+        let s1 = small_sphere.clone();
+        let s2 = small_sphere.clone();
+        let s3 = small_sphere;
+        let ground = Arc::new(Sphere::new(100.0));
         let entities = vec![
-            Entity::new(Transform::default().with_position(Vec3::new(0.0, 1.0, 0.0)), Material::from_diffuse(WHITE), s0),
-            Entity::new(Transform::default().with_position(Vec3::new(0.0, 1.0, 1.0)), Material::from_diffuse(BLUE), s1),
-            Entity::new(Transform::default().with_position(Vec3::new(0.0, 2.0, 0.0)), Material::from_diffuse(GREEN), s2),
-            Entity::new(Transform::default().with_position(Vec3::new(1.0, 1.0, 0.0)), Material::from_diffuse(RED), s3),
-            Entity::new(Transform::default().with_position(Vec3::new(0.0, -100.0, 0.0)), Material::from_diffuse(GRAY), ground),
+            Entity::new(
+                Transform::default().with_position(Vec3::new(0.0, 1.0, 0.0)),
+                Material::from_diffuse(WHITE),
+                s0,
+            ),
+            Entity::new(
+                Transform::default().with_position(Vec3::new(0.0, 1.0, 1.0)),
+                Material::from_diffuse(BLUE),
+                s1,
+            ),
+            Entity::new(
+                Transform::default().with_position(Vec3::new(0.0, 2.0, 0.0)),
+                Material::from_diffuse(GREEN),
+                s2,
+            ),
+            Entity::new(
+                Transform::default().with_position(Vec3::new(1.0, 1.0, 0.0)),
+                Material::from_diffuse(RED),
+                s3,
+            ),
+            Entity::new(
+                Transform::default().with_position(Vec3::new(0.0, -100.0, 0.0)),
+                Material::from_diffuse(GRAY),
+                ground,
+            ),
         ];
         let mut camera = Camera::new().with_clear_color(DARK_GRAY);
         camera.transform().set_position(Vec3::new(0.0, 1.0, 4.0));
 
-        Scene { entities, camera }
+        Scene::from_parts(entities, camera)
+    }
+
+    /// Creates a [Scene] from its entities and camera. The entities are identified
+    /// by their index, starting from 1, and are the roots of the [SceneGraph].
+    pub fn from_parts(entities: Vec<Entity>, camera: Camera) -> Self {
+        let entities: Vec<Entity> = entities
+            .into_iter()
+            .enumerate()
+            .map(|(i, e)| e.with_id(i as u32 + 1))
+            .collect();
+        let mut graph = SceneGraph::default();
+        for (i, entity) in entities.iter().enumerate() {
+            graph
+                .add(None, None, entity.transform().clone(), Some(i))
+                .expect("unnamed nodes are valid");
+        }
+
+        let bvh = Bvh::new(&entities.iter().map(Entity::bounds).collect::<Vec<_>>());
+
+        Scene {
+            entities,
+            graph,
+            bvh,
+            camera,
+            post: PostStack::default(),
+            animation: Animation::default(),
+        }
+    }
+
+    /// Loads a [Scene] from a TOML scene description file.
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        let toml = fs::read_to_string(path).map_err(Error::io(path))?;
+
+        SceneDescription::parse(&toml)
+            .and_then(|d| d.build())
+            .map_err(|e| match e {
+                Error::Scene(message) => Error::Scene(format!("{}: {}", path, message)),
+                e => e,
+            })
+    }
+
+    pub fn render(
+        &self,
+        target: &mut dyn RenderTarget,
+        opts: &RenderOpts,
+        progress_func: &dyn Fn(f32),
+    ) -> SampleCounts {
+        self.camera.render(self, target, opts, progress_func)
+    }
+
+    /// Renders into the linear [HdrBuffer]. See [Camera::render_hdr].
+    pub fn render_hdr(
+        &self,
+        target: &mut HdrBuffer,
+        opts: &RenderOpts,
+        progress_func: &dyn Fn(f32),
+    ) -> (SampleCounts, Vec<AovImage>) {
+        self.camera.render_hdr(self, target, opts, progress_func)
+    }
+
+    /// Renders pass by pass into the [Checkpoint]. See [Camera::render_resumable].
+    pub(crate) fn render_resumable(
+        &self,
+        checkpoint: &mut Checkpoint,
+        opts: &RenderOpts,
+        progress_func: &dyn Fn(f32),
+        on_pass: &mut dyn FnMut(&Checkpoint) -> bool,
+    ) {
+        self.camera
+            .render_resumable(self, checkpoint, opts, progress_func, on_pass)
+    }
+
+    /// Adds the sample `index` of every pixel to the [Film]. See [Camera::render_pass].
+    pub(crate) fn render_pass(
+        &self,
+        film: &mut Film,
+        opts: &RenderOpts,
+        sampler: &mut dyn Sampler,
+        index: u32,
+    ) {
+        self.camera.render_pass(self, film, opts, sampler, index)
+    }
+
+    /// Adds the sample `index` of the pixels of some rows to the [Film]. See
+    /// [Camera::render_pass_rows].
+    #[cfg_attr(not(feature = "window"), allow(dead_code))]
+    pub(crate) fn render_pass_rows(
+        &self,
+        film: &mut Film,
+        opts: &RenderOpts,
+        sampler: &mut dyn Sampler,
+        index: u32,
+        rows: Range<u32>,
+    ) {
+        self.camera
+            .render_pass_rows(self, film, opts, sampler, index, rows)
+    }
+
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Adds a node to the [SceneGraph], under the parent or as a root, with the entity
+    /// made of the material and primitive if specified. The primitive can be shared with
+    /// other entities. The entities are only placed, and traced, after
+    /// [Scene::resolve_transforms].
+    pub fn add_node(
+        &mut self,
+        parent: Option<NodeId>,
+        name: Option<&str>,
+        transform: Transform,
+        entity: Option<(Material, Arc<dyn Primitive>)>,
+    ) -> Result<NodeId, Error> {
+        let index = entity.as_ref().map(|_| self.entities.len());
+        let id = self
+            .graph
+            .add(parent, name, transform.clone(), index)
+            .map_err(Error::Scene)?;
+        if let Some((material, primitive)) = entity {
+            let id = self.entities.len() as u32 + 1;
+            self.entities
+                .push(Entity::new(transform, material, primitive).with_id(id));
+        }
+
+        Ok(id)
+    }
+
+    pub fn graph(&self) -> &SceneGraph {
+        &self.graph
+    }
+
+    /// Gets the [SceneGraph], e.g to move a node with its subtree. Call
+    /// [Scene::resolve_transforms] before rendering to apply the changes.
+    pub fn graph_mut(&mut self) -> &mut SceneGraph {
+        &mut self.graph
+    }
+
+    /// Finds a node by its path from a root, e.g `robot/arm`, or else by its name.
+    pub fn find(&self, path: &str) -> Option<NodeId> {
+        self.graph.find_path(path).or_else(|| self.graph.find(path))
     }
 
-    pub fn render(&self, target: &mut dyn RenderTarget, opts: &RenderOpts, progress_func: &dyn Fn(f32)) {
-        self.camera.render(self, target, opts, progress_func);
+    /// Places the entities in world space, by combining the transforms of their nodes with
+    /// the ones of their ancestors, and rebuilds the acceleration structure over them.
+    pub fn resolve_transforms(&mut self) {
+        let world = self.graph.world_transforms();
+        for ((_, node), transform) in self.graph.nodes().zip(world) {
+            if let Some(index) = node.entity() {
+                *self.entities[index].transform_mut() = transform;
+            }
+        }
+        self.bvh = Bvh::new(&self.entities.iter().map(Entity::bounds).collect::<Vec<_>>());
+    }
+
+    /// Gets the acceleration structure over the entities, by index.
+    pub(crate) fn bvh(&self) -> &Bvh {
+        &self.bvh
     }
 
     pub fn camera(&self) -> &Camera {
         &self.camera
     }
-}
 
-impl Hittable for Scene {
-    fn hit(&self, ray: &Ray) -> Option<Hit> {
-        let mut closest : Option<Hit> = None;
-        let mut distance = 9999999999999999999.0;
+    /// Returns the default size of the renders of this scene: 512 pixels high,
+    /// and as wide as the aspect of the camera requires.
+    pub fn render_size(&self) -> PixelSize {
+        let width = (self.camera.aspect() * RENDER_HEIGHT as f32) as u32;
+        PixelSize::new(width, RENDER_HEIGHT)
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
 
+    pub fn post(&self) -> &PostStack {
+        &self.post
+    }
+
+    pub fn set_post(&mut self, post: PostStack) {
+        self.post = post;
+    }
+
+    pub(crate) fn animation(&self) -> &Animation {
+        &self.animation
+    }
+
+    pub(crate) fn set_animation(&mut self, animation: Animation) {
+        self.animation = animation;
+    }
+
+    /// Hashes what the renders of the scene depend on: the placed entities with their
+    /// materials and primitives, and the camera. The post-processing effects are not
+    /// part of it.
+    pub(crate) fn fingerprint(&self) -> u32 {
+        fn push_vec3(values: &mut Vec<u32>, v: Vec3) {
+            values.extend(v.iter().map(|c| c.to_bits()));
+        }
+
+        let mut values = Vec::new();
         for entity in &self.entities {
-            match entity.hit(ray) {
-                Some(hit) => {
-                    if closest.is_none() || hit.sqr_distance() < distance {
-                        distance = hit.sqr_distance();
-                        closest = Some(hit);
-                    }
-                },
-                None => {}
-            }
+            let transform = entity.transform();
+            push_vec3(&mut values, transform.position());
+            push_vec3(
+                &mut values,
+                transform
+                    .end_position()
+                    .unwrap_or_else(|| transform.position()),
+            );
+            values.extend(transform.rotation().coords.iter().map(|c| c.to_bits()));
+            let end_rotation = transform
+                .end_rotation()
+                .unwrap_or_else(|| transform.rotation());
+            values.extend(end_rotation.coords.iter().map(|c| c.to_bits()));
+            values.push(entity.material().id());
+            let primitive = entity.primitive();
+            values.extend(primitive.name().bytes().map(u32::from));
+            push_vec3(&mut values, primitive.bounds().min);
+            push_vec3(&mut values, primitive.bounds().max);
+            values.push(primitive.triangle_count() as u32);
         }
 
+        let camera = &self.camera;
+        let clear_color = camera.clear_color();
+        let (yaw, pitch) = camera.orientation();
+        let (open, close) = camera.shutter();
+        let stereo = camera.stereo();
+        push_vec3(&mut values, camera.position());
+        values.extend(
+            [
+                yaw,
+                pitch,
+                camera.vertical_fov(),
+                open,
+                close,
+                stereo.ipd,
+                stereo.convergence.unwrap_or(0.0),
+            ]
+            .iter()
+            .map(|v| v.to_bits()),
+        );
+        values.extend(
+            [clear_color.r, clear_color.g, clear_color.b]
+                .iter()
+                .map(|c| *c as u32),
+        );
+        values.extend(camera.projection().to_string().bytes().map(u32::from));
+
+        hash_combine(&values)
+    }
+
+    /// Sets the animated properties of the nodes, the entities and the camera at the frame,
+    /// and resolves the transforms. The other properties are left unchanged.
+    pub fn set_frame(&mut self, frame: u32) {
+        self.animation.apply(
+            &mut self.graph,
+            &mut self.entities,
+            &mut self.camera,
+            frame as f32,
+        );
+        self.resolve_transforms();
+    }
+}
+
+impl Scene {
+    /// Finds the closest [Hit] of the ray, and counts the ray and its intersection tests.
+    pub(crate) fn trace(&self, ray: &Ray, kind: RayKind, stats: &mut RayStats) -> Option<Hit> {
+        let mut closest: Option<Hit> = None;
+        let mut intersection_tests = 0;
+        stats.add_ray(kind);
+
+        stats.bounds_tests += self.bvh.traverse(ray, f32::MAX, |index| {
+            intersection_tests += 1;
+            let hit = self.entities[index].hit(ray)?;
+            if closest
+                .as_ref()
+                .is_some_and(|c| c.sqr_distance() <= hit.sqr_distance())
+            {
+                return None;
+            }
+            let distance = hit.sqr_distance();
+            closest = Some(hit);
+            Some(distance)
+        });
+        stats.intersection_tests += intersection_tests;
+
         closest
     }
-}
\ No newline at end of file
+}
+
+impl Hittable for Scene {
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        self.trace(ray, RayKind::Primary, &mut RayStats::default())
+    }
+}
@@ -0,0 +1,180 @@
+use crate::math::{Aabb, Bvh, Ray, Vec3};
+use crate::rendering::Material;
+use crate::scene::{Hit, Primitive, Transform};
+use std::mem::size_of;
+
+/// The minimum determinant of a ray-triangle intersection, below which the ray is
+/// considered parallel to the triangle.
+const PARALLEL_EPSILON: f32 = 1e-8;
+
+/// A triangle mesh, with a bounding volume hierarchy of its triangles in object space.
+///
+/// The mesh is intersected in object space: the rays are brought into the space of the
+/// [Transform] of each entity, so that entities sharing a mesh share its hierarchy.
+#[derive(Debug)]
+pub struct Mesh {
+    vertices: Vec<Vec3>,
+    triangles: Vec<[u32; 3]>,
+    bvh: Bvh,
+}
+
+impl Mesh {
+    /// Creates a mesh from its vertices, and its triangles as indices of vertices. Returns
+    /// an error if an index is out of bounds.
+    pub fn new(vertices: Vec<Vec3>, triangles: Vec<[u32; 3]>) -> Result<Self, String> {
+        if let Some(index) = triangles
+            .iter()
+            .flatten()
+            .find(|i| **i as usize >= vertices.len())
+        {
+            return Err(format!(
+                "the vertex index {} is out of bounds ({} vertices)",
+                index,
+                vertices.len()
+            ));
+        }
+
+        let bounds: Vec<Aabb> = triangles
+            .iter()
+            .map(|t| {
+                Aabb::from_points(t.iter().map(|i| vertices[*i as usize]))
+                    .expect("triangles have vertices")
+            })
+            .collect();
+        let bvh = Bvh::new(&bounds);
+
+        Ok(Mesh {
+            vertices,
+            triangles,
+            bvh,
+        })
+    }
+
+    /// Intersects the triangle with the Möller-Trumbore algorithm. Returns the distance
+    /// along the ray and the barycentric coordinates of the hit.
+    fn hit_triangle(&self, ray: &Ray, triangle: usize, t_max: f32) -> Option<(f32, f32, f32)> {
+        let [a, b, c] = self.triangles[triangle].map(|i| self.vertices[i as usize]);
+        let (ab, ac) = (b - a, c - a);
+        let p = ray.direction().cross(&ac);
+        let det = ab.dot(&p);
+        if det.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let ao = ray.origin() - a;
+        let u = ao.dot(&p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = ao.cross(&ab);
+        let v = ray.direction().dot(&q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = ac.dot(&q) * inv_det;
+        (t > 0.0 && t < t_max).then_some((t, u, v))
+    }
+
+    fn normal(&self, triangle: usize) -> Vec3 {
+        let [a, b, c] = self.triangles[triangle].map(|i| self.vertices[i as usize]);
+        (b - a).cross(&(c - a)).normalize()
+    }
+}
+
+impl Primitive for Mesh {
+    fn hit(&self, ray: &Ray, transform: &Transform, material: Material) -> Option<Hit> {
+        let time = ray.time();
+        let origin = transform.inverse_transform_point(ray.origin(), time);
+        let local = Ray::new(
+            origin,
+            transform.inverse_transform_vector(ray.direction(), time),
+        )
+        .with_time(time);
+
+        let mut closest: Option<(usize, f32, f32, f32)> = None;
+        self.bvh.traverse(&local, f32::MAX, |triangle| {
+            let t_max = closest.map_or(f32::MAX, |c| c.1);
+            let (t, u, v) = self.hit_triangle(&local, triangle, t_max)?;
+            closest = Some((triangle, t, u, v));
+            Some(t)
+        });
+
+        let (triangle, t, u, v) = closest?;
+        // the rotation keeps the distances, so the distance along the ray is the same
+        let mut normal = transform.transform_vector(self.normal(triangle), time);
+        if normal.dot(&ray.direction()) > 0.0 {
+            normal = -normal;
+        }
+
+        Some(Hit::new(ray.at(t), normal, t, material).with_uv((u, v)))
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bvh
+            .bounds()
+            .unwrap_or_else(|| Aabb::new(Vec3::zeros(), Vec3::zeros()))
+    }
+
+    fn name(&self) -> &'static str {
+        "mesh"
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        if self.triangles.is_empty() {
+            vec![String::from("the mesh has no triangles, it is invisible")]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn memory_size(&self) -> usize {
+        size_of::<Self>()
+            + self.vertices.len() * size_of::<Vec3>()
+            + self.triangles.len() * size_of::<[u32; 3]>()
+            + self.bvh.memory_size()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::Rotation;
+    use crate::rendering::WHITE;
+
+    #[test]
+    fn hit_in_object_space() {
+        // a unit square in the XY plane, facing +Z
+        let vertices = vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let mesh = Mesh::new(vertices.clone(), vec![[0, 1, 2], [0, 2, 3]]).unwrap();
+        assert!(Mesh::new(vertices, vec![[0, 1, 4]]).is_err());
+        assert_eq!(2, mesh.triangle_count());
+
+        // rotated to face +X, and moved to x = 2
+        let mut transform = Transform::default().with_position(Vec3::new(2.0, 0.0, 0.0));
+        transform.set_rotation(Rotation::from_axis_angle(
+            &Vec3::y_axis(),
+            std::f32::consts::FRAC_PI_2,
+        ));
+        let material = Material::from_diffuse(WHITE);
+
+        let ray = Ray::new(Vec3::new(5.0, 0.25, -0.5), Vec3::new(-1.0, 0.0, 0.0));
+        let hit = mesh.hit(&ray, &transform, material.clone()).unwrap();
+        assert!((hit.position() - Vec3::new(2.0, 0.25, -0.5)).magnitude() < 1e-5);
+        assert!((hit.normal() - Vec3::new(1.0, 0.0, 0.0)).magnitude() < 1e-5);
+        assert!((hit.sqr_distance() - 3.0).abs() < 1e-5);
+
+        let miss = Ray::new(Vec3::new(5.0, 0.25, 0.5), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(mesh.hit(&miss, &transform, material).is_none());
+    }
+}
@@ -1,10 +1,76 @@
+pub mod mesh;
 pub mod sphere;
-pub use sphere::Sphere;
-use crate::scene::{Transform, Hit};
-use crate::math::Ray;
+use crate::math::{Aabb, Ray};
 use crate::rendering::Material;
+use crate::scene::{Hit, Transform};
+pub use mesh::Mesh;
+pub use sphere::Sphere;
 use std::fmt::Debug;
 
-pub trait Primitive : Debug {
+/// A shape that rays can hit. Only [Primitive::hit] is required: the other methods have
+/// defaults, so that new methods do not break the existing implementations.
+pub trait Primitive: Debug {
     fn hit(&self, ray: &Ray, transform: &Transform, material: Material) -> Option<Hit>;
+
+    /// Returns the bounds of this primitive, in object space. Unbounded by default, which
+    /// is correct but makes every ray test the primitive.
+    fn bounds(&self) -> Aabb {
+        Aabb::unbounded()
+    }
+
+    /// The name of the kind of primitive, e.g `sphere`.
+    fn name(&self) -> &'static str {
+        "primitive"
+    }
+
+    /// Returns the problems of this primitive that do not prevent rendering it, e.g
+    /// a degenerate shape.
+    fn warnings(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The number of triangles of this primitive, if it is a mesh.
+    fn triangle_count(&self) -> usize {
+        0
+    }
+
+    /// Estimates the memory used by this primitive, in bytes.
+    fn memory_size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::Vec3;
+    use crate::rendering::{Camera, RayKind, RayStats, RED};
+    use crate::scene::{Entity, Scene};
+    use std::sync::Arc;
+
+    /// The plane z = 0, implementing only the required method.
+    #[derive(Debug)]
+    struct Plane;
+
+    impl Primitive for Plane {
+        fn hit(&self, ray: &Ray, transform: &Transform, material: Material) -> Option<Hit> {
+            let origin = ray.origin() - transform.position();
+            let t = -origin.z / ray.direction().z;
+            (t > 0.0).then(|| Hit::new(ray.at(t), Vec3::new(0.0, 0.0, 1.0), t, material))
+        }
+    }
+
+    #[test]
+    fn primitives_only_need_to_be_hit() {
+        let transform = Transform::default().with_position(Vec3::new(0.0, 0.0, -5.0));
+        let plane = Entity::new(transform, Material::from_diffuse(RED), Arc::new(Plane));
+        let scene = Scene::from_parts(vec![plane], Camera::new());
+        assert_eq!("primitive", scene.entities()[0].primitive().name());
+
+        let ray = Ray::new(Vec3::new(3.0, 2.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = scene
+            .trace(&ray, RayKind::Primary, &mut RayStats::default())
+            .unwrap();
+        assert_eq!(RED, hit.material().diffuse_color());
+    }
 }
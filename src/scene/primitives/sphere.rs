@@ -1,6 +1,6 @@
-use crate::math::{Ray, Vec3};
-use crate::scene::{Transform, Hit, Primitive};
+use crate::math::{Aabb, Ray, Vec3};
 use crate::rendering::Material;
+use crate::scene::{Hit, Primitive, Transform};
 
 #[derive(Debug)]
 pub struct Sphere {
@@ -23,13 +23,23 @@ impl Sphere {
     }
 }
 
+impl Sphere {
+    /// Returns the spherical texture coordinates of the point with the specified normal:
+    /// U goes around the Y axis, starting from +Z, and V goes from the bottom to the top.
+    fn uv(normal: Vec3) -> (f32, f32) {
+        let u = 0.5 + normal.x.atan2(normal.z) / (2.0 * std::f32::consts::PI);
+        let v = 0.5 + normal.y.clamp(-1.0, 1.0).asin() / std::f32::consts::PI;
+        (u, v)
+    }
+}
+
 impl Primitive for Sphere {
     fn hit(&self, ray: &Ray, transform: &Transform, material: Material) -> Option<Hit> {
-        let center = transform.position();
+        let center = transform.position_at(ray.time());
         let oc = ray.origin() - center;
         let a = ray.direction().magnitude_squared();
         let half_b = Vec3::dot(&oc, &ray.direction());
-        let c = &oc.magnitude_squared() - self.sqr_radius;
+        let c = oc.magnitude_squared() - self.sqr_radius;
         let discriminant = half_b * half_b - a * c;
 
         if discriminant < 0.0 {
@@ -39,21 +49,40 @@ impl Primitive for Sphere {
         let sqrtd = discriminant.sqrt();
 
         let mut root = (-half_b - sqrtd) / a;
-        const T_MIN : f32 = 0.0;
-        const T_MAX : f32 = 99999999.0;
+        const T_MIN: f32 = 0.0;
+        const T_MAX: f32 = 99999999.0;
 
-        if root < T_MIN || T_MAX < root {
+        if !(T_MIN..=T_MAX).contains(&root) {
             root = (-half_b + sqrtd) / a;
-            if root < T_MIN || T_MAX < root {
-                return None
+            if !(T_MIN..=T_MAX).contains(&root) {
+                return None;
             }
         }
 
         let point = ray.at(root);
         let normal = (point - center) / self.radius;
         let sqr_dist = root;
-        let hit = Hit::new(point, normal, sqr_dist, material);
+        let hit = Hit::new(point, normal, sqr_dist, material).with_uv(Self::uv(normal));
 
         Some(hit)
     }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::from_sphere(Vec3::zeros(), self.radius)
+    }
+
+    fn name(&self) -> &'static str {
+        "sphere"
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        if self.radius > 0.0 {
+            Vec::new()
+        } else {
+            vec![format!(
+                "the sphere has an invalid radius ({}), it is invisible",
+                self.radius
+            )]
+        }
+    }
 }
@@ -0,0 +1,172 @@
+use crate::math::Vec3;
+use std::f32::consts::PI;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The projections supported by a [Camera](crate::scene::camera::Camera).
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Projection {
+    /// A pinhole perspective projection.
+    #[default]
+    Perspective,
+    /// A parallel projection. `height` is the height of the view volume in world units.
+    Orthographic { height: f32 },
+    /// An equidistant fisheye projection, with the field of view in degrees.
+    Fisheye { fov: f32 },
+    /// A full 360° x 180° latitude-longitude panorama.
+    Equirectangular,
+}
+
+impl Display for Projection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Projection::Perspective => write!(f, "perspective"),
+            Projection::Orthographic { height } => write!(f, "orthographic:{}", height),
+            Projection::Fisheye { fov } => write!(f, "fisheye:{}", fov),
+            Projection::Equirectangular => write!(f, "equirectangular"),
+        }
+    }
+}
+
+impl FromStr for Projection {
+    type Err = String;
+
+    /// Parses a projection of the form `name[:parameter]`, e.g `fisheye:220` or `orthographic`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let name = parts.next().unwrap_or_default().trim().to_lowercase();
+        let param = match parts.next() {
+            Some(p) => Some(
+                p.trim()
+                    .parse::<f32>()
+                    .map_err(|_| format!("invalid projection parameter: {}", p))?,
+            ),
+            None => None,
+        };
+
+        let projection = match name.as_str() {
+            "perspective" => Projection::Perspective,
+            "orthographic" | "ortho" => Projection::Orthographic {
+                height: param.unwrap_or(Self::DEFAULT_ORTHO_HEIGHT),
+            },
+            "fisheye" => Projection::Fisheye {
+                fov: param.unwrap_or(Self::DEFAULT_FISHEYE_FOV),
+            },
+            "equirectangular" | "equirect" | "360" => Projection::Equirectangular,
+            _ => return Err(format!("unknown projection: {}", s)),
+        };
+        projection.check()?;
+
+        Ok(projection)
+    }
+}
+
+impl Projection {
+    pub const DEFAULT_ORTHO_HEIGHT: f32 = 4.0;
+    pub const DEFAULT_FISHEYE_FOV: f32 = 180.0;
+
+    /// Returns an error if the parameter of the projection is out of range: the height of
+    /// orthographic projections must be positive, and the field of view of fisheyes within
+    /// ]0, 360] degrees. Other values produce invalid rays.
+    pub fn check(&self) -> Result<(), String> {
+        match *self {
+            Projection::Orthographic { height } if !(height > 0.0 && height.is_finite()) => Err(
+                format!("the orthographic height must be positive, not {}", height),
+            ),
+            Projection::Fisheye { fov } if !(fov > 0.0 && fov <= 360.0) => Err(format!(
+                "the fisheye field of view must be within ]0, 360] degrees, not {}",
+                fov
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the aspect ratio this projection requires, if any.
+    pub fn preferred_aspect(&self) -> Option<f32> {
+        match self {
+            Projection::Equirectangular => Some(2.0),
+            _ => None,
+        }
+    }
+
+    /// Returns the camera-space direction for the specified viewport coordinates, for
+    /// the projections that do not depend on the viewport size (fisheye and panoramas).
+    ///
+    /// The camera looks down the -Z axis, with +Y up. Returns `None` if the coordinates
+    /// fall outside of the projection (e.g the corners of a circular fisheye).
+    pub(crate) fn spherical_direction(&self, uv: (f32, f32), aspect: f32) -> Option<Vec3> {
+        match self {
+            Projection::Fisheye { fov } => {
+                let x = (uv.0 - 0.5) * 2.0 * aspect;
+                let y = (uv.1 - 0.5) * 2.0;
+                let r = (x * x + y * y).sqrt();
+                let half_fov = fov.to_radians() / 2.0;
+                let theta = r * half_fov;
+
+                if r > 1.0 || theta > PI {
+                    return None;
+                }
+
+                let phi = y.atan2(x);
+                Some(Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    -theta.cos(),
+                ))
+            }
+            Projection::Equirectangular => {
+                let longitude = (uv.0 - 0.5) * 2.0 * PI;
+                let latitude = (uv.1 - 0.5) * PI;
+                Some(Vec3::new(
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    -latitude.cos() * longitude.cos(),
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Ok(Projection::Perspective), "perspective".parse());
+        assert_eq!(
+            Ok(Projection::Fisheye { fov: 220.0 }),
+            "fisheye:220".parse()
+        );
+        assert_eq!(
+            Ok(Projection::Orthographic {
+                height: Projection::DEFAULT_ORTHO_HEIGHT
+            }),
+            "Orthographic".parse()
+        );
+        assert_eq!(Ok(Projection::Equirectangular), "equirect".parse());
+        assert!("cylindrical".parse::<Projection>().is_err());
+        assert!("fisheye:wide".parse::<Projection>().is_err());
+        assert!("fisheye:0".parse::<Projection>().is_err());
+        assert!("fisheye:400".parse::<Projection>().is_err());
+        assert!("orthographic:-2".parse::<Projection>().is_err());
+        assert!("orthographic:NaN".parse::<Projection>().is_err());
+    }
+
+    #[test]
+    fn equirectangular_center_looks_forward() {
+        let dir = Projection::Equirectangular
+            .spherical_direction((0.5, 0.5), 2.0)
+            .unwrap();
+        assert!((dir - Vec3::new(0.0, 0.0, -1.0)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn fisheye_corners_are_outside() {
+        let fisheye = Projection::Fisheye { fov: 180.0 };
+        assert!(fisheye.spherical_direction((0.0, 0.0), 1.0).is_none());
+        let edge = fisheye.spherical_direction((1.0, 0.5), 1.0).unwrap();
+        assert!((edge - Vec3::new(1.0, 0.0, 0.0)).magnitude() < 1e-6);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::math::{Ray, Vec3};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The eyes of a stereoscopic [Camera](crate::scene::camera::Camera).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    /// Returns -1 for the left eye, and 1 for the right eye.
+    fn sign(&self) -> f32 {
+        match self {
+            Eye::Left => -1.0,
+            Eye::Right => 1.0,
+        }
+    }
+}
+
+impl Display for Eye {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Eye::Left => write!(f, "left"),
+            Eye::Right => write!(f, "right"),
+        }
+    }
+}
+
+/// The parameters of a stereoscopic camera rig.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stereo {
+    /// The interpupillary distance, in world units.
+    pub ipd: f32,
+    /// The distance of the zero-parallax surface, or `None` for parallel eyes.
+    pub convergence: Option<f32>,
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Stereo {
+            ipd: Self::DEFAULT_IPD,
+            convergence: None,
+        }
+    }
+}
+
+impl Stereo {
+    /// The average human interpupillary distance, assuming the scene is in meters.
+    pub const DEFAULT_IPD: f32 = 0.064;
+
+    /// Returns an error if the interpupillary distance or the convergence distance is not a
+    /// positive number.
+    pub fn check(&self) -> Result<(), String> {
+        let is_positive = |v: f32| v > 0.0 && v.is_finite();
+        if !is_positive(self.ipd) {
+            return Err(format!(
+                "the interpupillary distance must be positive, not {}",
+                self.ipd
+            ));
+        }
+        match self.convergence {
+            Some(distance) if !is_positive(distance) => Err(format!(
+                "the convergence distance must be positive, not {}",
+                distance
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Offsets the camera-space primary `ray` for the specified [Eye].
+    ///
+    /// Planar projections shift the eyes along the X axis and converge on a plane
+    /// (off-axis stereo). Panoramic projections use omni-directional stereo (ODS):
+    /// the eyes are shifted perpendicular to the horizontal direction of each ray,
+    /// and converge on a sphere.
+    pub fn eye_ray(&self, ray: Ray, eye: Eye, panoramic: bool) -> Ray {
+        let half_ipd = eye.sign() * self.ipd / 2.0;
+        let dir = ray.direction().normalize();
+
+        let offset = if panoramic {
+            let horizontal = Vec3::new(dir.x, 0.0, dir.z);
+            if horizontal.magnitude_squared() < f32::EPSILON {
+                // looking straight up or down: there is no horizontal parallax
+                Vec3::zeros()
+            } else {
+                let h = horizontal.normalize();
+                Vec3::new(-h.z, 0.0, h.x) * half_ipd
+            }
+        } else {
+            Vec3::new(half_ipd, 0.0, 0.0)
+        };
+
+        let origin = ray.origin() + offset;
+
+        match self.convergence {
+            None => Ray::new(origin, ray.direction()),
+            Some(distance) => {
+                let t = if panoramic {
+                    distance
+                } else {
+                    distance / -dir.z
+                };
+                let target = ray.origin() + dir * t;
+                Ray::new(origin, target - origin)
+            }
+        }
+    }
+}
+
+/// How the left and right images of a stereo pair are output.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StereoLayout {
+    /// Each eye is presented separately (e.g written to its own file).
+    Separate,
+    /// Both eyes in the same image, left eye on the left.
+    SideBySide,
+    /// Both eyes in the same image, left eye on top.
+    TopBottom,
+}
+
+impl FromStr for StereoLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "separate" => Ok(StereoLayout::Separate),
+            "side-by-side" | "sbs" => Ok(StereoLayout::SideBySide),
+            "top-bottom" | "tb" => Ok(StereoLayout::TopBottom),
+            _ => Err(format!("unknown stereo layout: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn converged_eyes_meet_at_convergence_distance() {
+        let stereo = Stereo {
+            ipd: 0.1,
+            convergence: Some(2.0),
+        };
+        let ray = Ray::new(Vec3::zeros(), Vec3::new(0.0, 0.0, -1.0));
+
+        let left = stereo.eye_ray(ray, Eye::Left, false);
+        let right = stereo.eye_ray(ray, Eye::Right, false);
+
+        assert!((left.origin() - Vec3::new(-0.05, 0.0, 0.0)).magnitude() < 1e-6);
+        assert!((right.origin() - Vec3::new(0.05, 0.0, 0.0)).magnitude() < 1e-6);
+        assert!((left.at(1.0) - right.at(1.0)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn check() {
+        assert_eq!(Ok(()), Stereo::default().check());
+        let invalid = |ipd, convergence| Stereo { ipd, convergence }.check().is_err();
+        assert!(invalid(-0.1, None));
+        assert!(invalid(f32::NAN, None));
+        assert!(invalid(0.1, Some(0.0)));
+    }
+
+    #[test]
+    fn ods_offset_is_perpendicular_to_ray() {
+        let stereo = Stereo::default();
+        let ray = Ray::new(Vec3::zeros(), Vec3::new(1.0, 0.0, 0.0));
+
+        let right = stereo.eye_ray(ray, Eye::Right, true);
+
+        assert!(right.origin().dot(&ray.direction()).abs() < 1e-6);
+        assert!((right.origin().magnitude() - Stereo::DEFAULT_IPD / 2.0).abs() < 1e-6);
+    }
+}
@@ -1,8 +1,16 @@
-use crate::math::Vec3;
+use crate::math::{Rotation, Vec3};
 
-#[derive(Debug, Default)]
+/// The placement of an object in the scene.
+///
+/// A [Transform] can optionally move during the frame: the start pose is at time `0.0`,
+/// and the end pose at time `1.0`. Positions in between are linearly interpolated, and
+/// rotations spherically, the shortest way.
+#[derive(Debug, Default, Clone)]
 pub struct Transform {
     position: Vec3,
+    end_position: Option<Vec3>,
+    rotation: Rotation,
+    end_rotation: Option<Rotation>,
 }
 
 impl Transform {
@@ -18,4 +26,179 @@ impl Transform {
     pub fn set_position(&mut self, pos: Vec3) {
         self.position = pos;
     }
+
+    /// Gets the position at the end of the frame, if this [Transform] is moving.
+    pub fn end_position(&self) -> Option<Vec3> {
+        self.end_position
+    }
+
+    /// Sets the position at the end of the frame, or `None` for a static [Transform].
+    pub fn set_end_position(&mut self, pos: Option<Vec3>) {
+        self.end_position = pos;
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    /// Gets the rotation at the end of the frame, if this [Transform] is spinning.
+    pub fn end_rotation(&self) -> Option<Rotation> {
+        self.end_rotation
+    }
+
+    /// Sets the rotation at the end of the frame, or `None` for a [Transform] that does
+    /// not spin.
+    pub fn set_end_rotation(&mut self, rotation: Option<Rotation>) {
+        self.end_rotation = rotation;
+    }
+
+    /// Returns true if the pose changes during the frame.
+    pub fn is_moving(&self) -> bool {
+        self.end_position.is_some() || self.end_rotation.is_some()
+    }
+
+    /// Returns the world [Transform] of a child placed by the `local` [Transform] relative
+    /// to this one. The child moves if either of them moves: its start and end poses are
+    /// exact, but the poses in between are interpolated from them, so a child orbiting a
+    /// spinning parent moves along a chord rather than an arc.
+    pub fn compose(&self, local: &Transform) -> Transform {
+        let end_position = match self.is_moving() || local.end_position.is_some() {
+            false => None,
+            true => Some(self.rotation_at(1.0) * local.position_at(1.0) + self.position_at(1.0)),
+        };
+        let end_rotation = match self.end_rotation.is_some() || local.end_rotation.is_some() {
+            false => None,
+            true => Some(self.rotation_at(1.0) * local.rotation_at(1.0)),
+        };
+
+        Transform {
+            position: self.rotation * local.position + self.position,
+            end_position,
+            rotation: self.rotation * local.rotation,
+            end_rotation,
+        }
+    }
+
+    /// Transforms a point from local space to world space, at the specified time.
+    pub fn transform_point(&self, point: Vec3, time: f32) -> Vec3 {
+        self.rotation_at(time) * point + self.position_at(time)
+    }
+
+    /// Transforms a point from world space to local space, at the specified time.
+    pub fn inverse_transform_point(&self, point: Vec3, time: f32) -> Vec3 {
+        self.rotation_at(time).inverse() * (point - self.position_at(time))
+    }
+
+    /// Transforms a direction from local space to world space, at the specified time.
+    pub fn transform_vector(&self, vector: Vec3, time: f32) -> Vec3 {
+        self.rotation_at(time) * vector
+    }
+
+    /// Transforms a direction from world space to local space, at the specified time.
+    pub fn inverse_transform_vector(&self, vector: Vec3, time: f32) -> Vec3 {
+        self.rotation_at(time).inverse() * vector
+    }
+
+    /// Returns the interpolated rotation at the specified time.
+    pub fn rotation_at(&self, time: f32) -> Rotation {
+        match self.end_rotation {
+            Some(end) => self
+                .rotation
+                .try_slerp(&end, time, f32::EPSILON)
+                .unwrap_or(self.rotation),
+            None => self.rotation,
+        }
+    }
+
+    /// Returns the interpolated position at the specified time.
+    pub fn position_at(&self, time: f32) -> Vec3 {
+        match self.end_position {
+            Some(end) => self.position.lerp(&end, time),
+            None => self.position,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn position_at() {
+        let static_transform = Transform::default().with_position(Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(Vec3::new(1.0, 0.0, 0.0), static_transform.position_at(0.7));
+
+        let mut moving = static_transform;
+        moving.set_end_position(Some(Vec3::new(3.0, 0.0, 0.0)));
+        assert_eq!(Vec3::new(1.0, 0.0, 0.0), moving.position_at(0.0));
+        assert_eq!(Vec3::new(2.0, 0.0, 0.0), moving.position_at(0.5));
+        assert_eq!(Vec3::new(3.0, 0.0, 0.0), moving.position_at(1.0));
+    }
+
+    #[test]
+    fn transform_point_rotates_then_translates() {
+        let mut transform = Transform::default().with_position(Vec3::new(0.0, 1.0, 0.0));
+        transform.set_rotation(Rotation::from_axis_angle(
+            &Vec3::y_axis(),
+            std::f32::consts::FRAC_PI_2,
+        ));
+
+        let p = transform.transform_point(Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!((p - Vec3::new(-1.0, 1.0, 0.0)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn compose() {
+        let mut parent = Transform::default().with_position(Vec3::new(0.0, 1.0, 0.0));
+        parent.set_rotation(Rotation::from_axis_angle(
+            &Vec3::y_axis(),
+            std::f32::consts::FRAC_PI_2,
+        ));
+        let local = Transform::default().with_position(Vec3::new(0.0, 0.0, -1.0));
+
+        let world = parent.compose(&local);
+        assert!((world.position() - Vec3::new(-1.0, 1.0, 0.0)).magnitude() < 1e-6);
+        assert_eq!(None, world.end_position());
+
+        parent.set_end_position(Some(Vec3::new(0.0, 3.0, 0.0)));
+        let world = parent.compose(&local);
+        assert!((world.end_position().unwrap() - Vec3::new(-1.0, 3.0, 0.0)).magnitude() < 1e-6);
+        assert_eq!(None, world.end_rotation());
+
+        // the child of a spinning parent orbits it, and spins with it
+        parent.set_end_rotation(Some(Rotation::from_axis_angle(
+            &Vec3::y_axis(),
+            std::f32::consts::PI,
+        )));
+        let world = parent.compose(&local);
+        assert!((world.end_position().unwrap() - Vec3::new(0.0, 3.0, 1.0)).magnitude() < 1e-6);
+        let forward = world.transform_vector(Vec3::new(0.0, 0.0, -1.0), 1.0);
+        assert!((forward - Vec3::new(0.0, 0.0, 1.0)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn rotations_are_interpolated() {
+        let mut transform = Transform::default().with_position(Vec3::new(0.0, 1.0, 0.0));
+        transform.set_end_rotation(Some(Rotation::from_axis_angle(
+            &Vec3::y_axis(),
+            std::f32::consts::FRAC_PI_2,
+        )));
+
+        let point = Vec3::new(0.0, 0.0, -1.0);
+        let halfway = transform.transform_point(point, 0.5);
+        let expected = Vec3::new(
+            -std::f32::consts::FRAC_1_SQRT_2,
+            1.0,
+            -std::f32::consts::FRAC_1_SQRT_2,
+        );
+        assert!((halfway - expected).magnitude() < 1e-6);
+        assert!((transform.inverse_transform_point(halfway, 0.5) - point).magnitude() < 1e-6);
+        assert!(
+            (transform.transform_vector(point, 1.0) - Vec3::new(-1.0, 0.0, 0.0)).magnitude() < 1e-6
+        );
+    }
 }
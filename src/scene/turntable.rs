@@ -0,0 +1,154 @@
+//! An orbit of the camera around the entities of a [Scene], e.g to preview an asset.
+use crate::error::Error;
+use crate::math::{Aabb, Vec3};
+use crate::scene::animation::{CameraAnimation, Interpolation, Keyframe, Track};
+use crate::scene::{Projection, Scene};
+
+/// The ratio between the size of an entity and the size of all the others, above which it
+/// is ignored to frame the orbit, e.g a ground modeled as a huge sphere.
+const BACKDROP_RATIO: f32 = 10.0;
+
+/// The space left around the entities, as a fraction of their size.
+const MARGIN: f32 = 0.1;
+
+/// A full orbit of the camera around the entities, over a number of frames.
+///
+/// The camera looks at the center of the entities from above, at a distance where their
+/// bounding sphere fits in its field of view, horizontally and vertically. The orbit starts
+/// from the current yaw of the camera, and the first frame is frame 1.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Turntable {
+    pub frames: u32,
+    /// The angle of the camera above the horizon, in degrees.
+    pub elevation: f32,
+}
+
+impl Turntable {
+    pub const DEFAULT_ELEVATION: f32 = 20.0;
+
+    pub fn new(frames: u32) -> Self {
+        Turntable {
+            frames,
+            elevation: Self::DEFAULT_ELEVATION,
+        }
+    }
+
+    /// Returns the animation of the camera orbiting the entities of the [Scene], with a
+    /// keyframe per frame. Fails if there are no frames, if the scene is empty, or if the
+    /// camera does not have a perspective projection.
+    pub fn camera_animation(&self, scene: &Scene) -> Result<CameraAnimation, Error> {
+        if self.frames == 0 {
+            return Err(Error::InvalidOption(String::from(
+                "a turntable needs at least one frame",
+            )));
+        }
+        let camera = scene.camera();
+        if camera.projection() != Projection::Perspective {
+            return Err(Error::InvalidOption(format!(
+                "cannot orbit with a {} projection, only with a perspective one",
+                camera.projection()
+            )));
+        }
+        let bounds = subject_bounds(scene).ok_or_else(|| {
+            Error::InvalidOption(String::from("cannot orbit around an empty scene"))
+        })?;
+        let center = (bounds.min + bounds.max) / 2.0;
+        let radius = ((bounds.max - bounds.min).magnitude() / 2.0).max(f32::EPSILON);
+        let half_fov = (camera.vertical_fov().min(camera.horizontal_fov()) / 2.0).to_radians();
+        let distance = radius * (1.0 + MARGIN) / half_fov.sin();
+
+        let start = camera.orientation().0;
+        let pitch = -self.elevation;
+        let keyframes = (0..self.frames).map(|i| {
+            let frame = (i + 1) as f32;
+            let yaw = start + 360.0 * i as f32 / self.frames as f32;
+            let (yaw_rad, pitch_rad) = (yaw.to_radians(), pitch.to_radians());
+            let forward = Vec3::new(
+                -yaw_rad.sin() * pitch_rad.cos(),
+                pitch_rad.sin(),
+                -yaw_rad.cos() * pitch_rad.cos(),
+            );
+            (
+                Keyframe::new(frame, center - forward * distance, Interpolation::Linear),
+                Keyframe::new(frame, yaw, Interpolation::Linear),
+            )
+        });
+        let (positions, yaws): (Vec<_>, Vec<_>) = keyframes.unzip();
+
+        Ok(CameraAnimation {
+            position: Some(Track::new(positions)?),
+            yaw: Some(Track::new(yaws)?),
+            pitch: Some(Track::new(vec![Keyframe::new(
+                1.0,
+                pitch,
+                Interpolation::Step,
+            )])?),
+            ..Default::default()
+        })
+    }
+}
+
+/// Returns the bounds of the entities, without the ones much larger than all the others.
+fn subject_bounds(scene: &Scene) -> Option<Aabb> {
+    let size = |b: &Aabb| (b.max - b.min).magnitude();
+    let mut bounds: Vec<Aabb> = scene.entities().iter().map(|e| e.bounds()).collect();
+    bounds.sort_by(|a, b| size(a).total_cmp(&size(b)));
+
+    let union = |bounds: &[Aabb]| bounds.iter().copied().reduce(|a, b| a.union(&b));
+    while bounds.len() > 1 {
+        let others = union(&bounds[..bounds.len() - 1]).unwrap();
+        if size(&bounds[bounds.len() - 1]) <= BACKDROP_RATIO * size(&others) {
+            break;
+        }
+        bounds.pop();
+    }
+
+    union(&bounds)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rendering::Camera;
+
+    #[test]
+    fn orbits_the_default_scene() {
+        let mut scene = Scene::new();
+        let animation = Turntable::new(4).camera_animation(&scene).unwrap();
+        assert_eq!((1.0, 4.0), animation.position.as_ref().unwrap().range());
+
+        // the ground is ignored: the camera looks at the spheres from nearby
+        let center = Vec3::new(0.25, 1.25, 0.25);
+        for frame in 1..=4 {
+            animation.apply(scene.camera_mut(), frame as f32);
+            let camera = scene.camera();
+            let to_center = (center - camera.position()).normalize();
+            assert!(
+                (camera.forward() - to_center).magnitude() < 1e-3,
+                "frame {}",
+                frame
+            );
+            assert!((camera.position() - center).magnitude() < 10.0);
+        }
+        assert!((scene.camera().orientation().0 + 90.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn orbits_have_frames() {
+        assert!(Turntable::new(0).camera_animation(&Scene::new()).is_err());
+        let animation = Turntable::new(1).camera_animation(&Scene::new()).unwrap();
+        assert_eq!((1.0, 1.0), animation.position.unwrap().range());
+    }
+
+    #[test]
+    fn only_perspective_cameras_can_orbit() {
+        let mut scene = Scene::new();
+        scene
+            .camera_mut()
+            .set_projection(Projection::Equirectangular);
+        assert!(Turntable::new(4).camera_animation(&scene).is_err());
+        assert!(Turntable::new(4)
+            .camera_animation(&Scene::from_parts(Vec::new(), Camera::new()))
+            .is_err());
+    }
+}
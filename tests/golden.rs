@@ -0,0 +1,174 @@
+//! Golden-image regression tests: reference scenes are rendered at low resolution with fixed
+//! seeds, and compared with the images stored in `tests/golden`.
+//!
+//! When a render differs from its golden image, the render and an amplified difference
+//! image are written into `target/tmp/golden`. After an intentional change, the golden
+//! images are replaced by the new renders with:
+//!
+//! ```sh
+//! RAYTRACER_BLESS=1 cargo test --test golden
+//! ```
+use raytracer::{
+    Adaptive, Backend, Color, FileBackend, FrameBuffer, HdrBuffer, Pixel, RenderOpts, RenderTarget,
+    SamplerKind, Scene,
+};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The width of the renders. The height depends on the aspect of the camera.
+const WIDTH: u32 = 96;
+
+/// The maximum root mean square error between a render and its golden image, where
+/// channel values range from 0 to 1.
+const TOLERANCE: f32 = 0.01;
+
+/// The environment variable that replaces the golden images with the renders.
+const BLESS_VAR: &str = "RAYTRACER_BLESS";
+
+/// The factor applied to the differences between the images, so that small ones are visible.
+const DIFF_SCALE: f32 = 8.0;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+}
+
+fn load_scene(name: &str) -> Scene {
+    let path = golden_dir().join("scenes").join(format!("{}.toml", name));
+    Scene::from_file(path.to_str().unwrap()).unwrap()
+}
+
+/// The options of the reference renders: few samples, with a deterministic sampler.
+fn opts() -> RenderOpts {
+    RenderOpts::new()
+        .with_samples(8)
+        .with_sampler(SamplerKind::Sobol)
+        .with_seed(7)
+}
+
+/// Renders the [Scene] as the command line would, post-processing included.
+fn render(scene: &Scene, opts: &RenderOpts) -> FrameBuffer {
+    let height = ((WIDTH as f32 / scene.camera().aspect()).round() as u32).max(1);
+    let mut hdr = HdrBuffer::new(WIDTH, height);
+    scene.render_hdr(&mut hdr, opts, &|_| {});
+    scene.post().apply(&mut hdr);
+    hdr.to_frame_buffer()
+}
+
+fn load_png(path: &Path) -> Option<FrameBuffer> {
+    let image = image::open(path).ok()?.to_rgb8();
+    let mut fb = FrameBuffer::new(image.width(), image.height());
+    for (x, y, rgb) in image.enumerate_pixels() {
+        fb.set(Pixel::new(x, y), Color::new(rgb[0], rgb[1], rgb[2]));
+    }
+    Some(fb)
+}
+
+fn save_png(fb: &FrameBuffer, path: &Path) {
+    FileBackend::new(path.to_str().unwrap())
+        .present(fb)
+        .unwrap();
+}
+
+fn rmse(a: &FrameBuffer, b: &FrameBuffer) -> f32 {
+    let sum: f32 = a
+        .bytes()
+        .iter()
+        .zip(b.bytes())
+        .map(|(a, b)| (*a as f32 - *b as f32) / 255.0)
+        .map(|d| d * d)
+        .sum();
+    (sum / a.bytes().len() as f32).sqrt()
+}
+
+/// Returns the absolute differences between the images, scaled by [DIFF_SCALE].
+fn diff_image(a: &FrameBuffer, b: &FrameBuffer) -> FrameBuffer {
+    let size = a.size();
+    let mut diff = FrameBuffer::new(size.width, size.height);
+    for y in 0..size.height {
+        for x in 0..size.width {
+            let pixel = Pixel::new(x, y);
+            let (ca, cb) = (a.get(pixel), b.get(pixel));
+            let d = |a: u8, b: u8| ((a as f32 - b as f32).abs() * DIFF_SCALE).min(255.0) as u8;
+            diff.set(
+                pixel,
+                Color::new(d(ca.r, cb.r), d(ca.g, cb.g), d(ca.b, cb.b)),
+            );
+        }
+    }
+    diff
+}
+
+/// Compares the render of the [Scene] with the golden image `name.png`, or replaces it when blessing.
+fn check(name: &str, scene: &Scene, opts: &RenderOpts) {
+    let actual = render(scene, opts);
+    let golden = golden_dir().join(format!("{}.png", name));
+
+    if env::var_os(BLESS_VAR).is_some() {
+        save_png(&actual, &golden);
+        return;
+    }
+
+    let expected = load_png(&golden).unwrap_or_else(|| {
+        panic!(
+            "missing golden image {}, create it with {}=1 cargo test --test golden",
+            golden.display(),
+            BLESS_VAR
+        )
+    });
+    assert_eq!(
+        expected.size(),
+        actual.size(),
+        "{}: the render size changed",
+        name
+    );
+
+    let error = rmse(&expected, &actual);
+    if error > TOLERANCE {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        fs::create_dir_all(&dir).unwrap();
+        save_png(&actual, &dir.join(format!("{}.png", name)));
+        save_png(
+            &diff_image(&expected, &actual),
+            &dir.join(format!("{}_diff.png", name)),
+        );
+        panic!(
+            "{}: RMSE {:.4} exceeds the tolerance ({}), the render and the difference are in {}",
+            name,
+            error,
+            TOLERANCE,
+            dir.display()
+        );
+    }
+}
+
+#[test]
+fn default_scene() {
+    check("default_scene", &Scene::new(), &opts());
+}
+
+#[test]
+fn adaptive_gaussian() {
+    let opts = opts()
+        .with_adaptive(Some(Adaptive::new(4, 32, 0.05)))
+        .with_sampler(SamplerKind::Halton)
+        .with_filter("gaussian:1.5".parse().unwrap());
+    check("adaptive_gaussian", &Scene::new(), &opts);
+}
+
+#[test]
+fn fisheye() {
+    check("fisheye", &load_scene("fisheye"), &opts());
+}
+
+#[test]
+fn motion_blur() {
+    check("motion_blur", &load_scene("motion_blur"), &opts());
+}
+
+#[test]
+fn post_effects() {
+    check("post_effects", &load_scene("post_effects"), &opts());
+}